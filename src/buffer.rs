@@ -0,0 +1,403 @@
+use std::fmt::{self, Display};
+
+use ropey::Rope;
+use syntect::parsing::SyntaxReference;
+
+use crate::comment::CommentTokens;
+use crate::encoding::Encoding;
+use crate::utils::{viewport_columns, DEFAULT_COLUMNS};
+use crate::PS;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Indent {
+    Tabs,
+    Spaces(usize),
+}
+
+impl Indent {
+    pub const OPTIONS: [Indent; 4] = [
+        Indent::Tabs,
+        Indent::Spaces(2),
+        Indent::Spaces(4),
+        Indent::Spaces(8),
+    ];
+
+    /// Guesses the indent from the leading whitespace of the text, defaulting to four spaces.
+    pub fn detect(rope: &Rope) -> Self {
+        let mut tabs = 0;
+        let mut spaces = 0;
+        let mut width = usize::MAX;
+        for line in rope.lines() {
+            match line.chars().next() {
+                Some('\t') => tabs += 1,
+                Some(' ') => {
+                    let leading = line.chars().take_while(|c| *c == ' ').count();
+                    // a single space is usually alignment, not indentation
+                    if leading > 1 {
+                        spaces += 1;
+                        width = width.min(leading);
+                    }
+                }
+                _ => {}
+            }
+        }
+        if tabs > spaces {
+            Indent::Tabs
+        } else if spaces > 0 {
+            Indent::Spaces(width.min(8))
+        } else {
+            Indent::Spaces(4)
+        }
+    }
+
+    /// The number of columns an indent level takes up, tabs are drawn four columns wide.
+    pub fn width(&self) -> usize {
+        match self {
+            Indent::Tabs => 4,
+            Indent::Spaces(n) => *n,
+        }
+    }
+
+    /// The text inserted by the tab key.
+    pub fn text(&self) -> String {
+        match self {
+            Indent::Tabs => "\t".to_string(),
+            Indent::Spaces(n) => " ".repeat(*n),
+        }
+    }
+}
+
+impl Display for Indent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Indent::Tabs => write!(f, "Tabs"),
+            Indent::Spaces(n) => write!(f, "Spaces: {}", n),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LineEnding {
+    Lf,
+    CrLf,
+    Cr,
+}
+
+impl LineEnding {
+    pub const OPTIONS: [LineEnding; 3] = [LineEnding::Lf, LineEnding::CrLf, LineEnding::Cr];
+
+    /// Uses the most common line ending in the text, defaulting to LF.
+    pub fn detect(rope: &Rope) -> Self {
+        let (mut lf, mut crlf, mut cr) = (0, 0, 0);
+        let mut chars = rope.chars().peekable();
+        while let Some(c) = chars.next() {
+            match c {
+                '\r' if chars.peek() == Some(&'\n') => {
+                    chars.next();
+                    crlf += 1;
+                }
+                '\r' => cr += 1,
+                '\n' => lf += 1,
+                _ => {}
+            }
+        }
+        if crlf > lf && crlf >= cr {
+            LineEnding::CrLf
+        } else if cr > lf && cr > crlf {
+            LineEnding::Cr
+        } else {
+            LineEnding::Lf
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            LineEnding::Lf => "\n",
+            LineEnding::CrLf => "\r\n",
+            LineEnding::Cr => "\r",
+        }
+    }
+
+    /// Rewrites every line break in the text with this line ending.
+    pub fn convert(&self, text: &str) -> String {
+        let text = text.replace("\r\n", "\n").replace('\r', "\n");
+        match self {
+            LineEnding::Lf => text,
+            _ => text.replace('\n', self.as_str()),
+        }
+    }
+}
+
+impl Display for LineEnding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LineEnding::Lf => write!(f, "LF"),
+            LineEnding::CrLf => write!(f, "CRLF"),
+            LineEnding::Cr => write!(f, "CR"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Wrap {
+    Off,
+    Viewport,
+    Column(usize),
+}
+
+impl Wrap {
+    pub const OPTIONS: [Wrap; 5] = [
+        Wrap::Off,
+        Wrap::Viewport,
+        Wrap::Column(80),
+        Wrap::Column(100),
+        Wrap::Column(120),
+    ];
+
+    /// The ways to wrap that can be picked, wrapping at the viewport needs a window that can be measured.
+    pub fn options() -> Vec<Wrap> {
+        Wrap::OPTIONS
+            .into_iter()
+            .filter(|w| *w != Wrap::Viewport || viewport_columns().is_some())
+            .collect()
+    }
+
+    /// The number of columns to wrap at given the number of columns of text that fit on screen, if they are known.
+    /// Without them lines wrap at the columns the desktop window opens with.
+    pub fn width(&self, viewport_columns: Option<usize>) -> Option<usize> {
+        match self {
+            Wrap::Off => None,
+            Wrap::Viewport => Some(viewport_columns.unwrap_or(DEFAULT_COLUMNS)),
+            Wrap::Column(n) => Some(viewport_columns.map_or(*n, |v| (*n).min(v))),
+        }
+    }
+}
+
+impl Display for Wrap {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Wrap::Off => write!(f, "No Wrap"),
+            Wrap::Viewport => write!(f, "Wrap: Viewport"),
+            Wrap::Column(n) => write!(f, "Wrap: {}", n),
+        }
+    }
+}
+
+// the most changes that can be undone
+const MAX_HISTORY: usize = 1000;
+
+/// Earlier and undone versions of the text of a buffer.
+#[derive(Debug, Clone, Default)]
+pub struct History {
+    undo: Vec<Rope>,
+    redo: Vec<Rope>,
+    // if the last change was typing, more typing is undone with it
+    typing: bool,
+    // counts changes to the history, to tell if an edit already recorded itself
+    version: usize,
+}
+
+/// The text of a file and the settings used to display and edit it.
+pub struct Buffer {
+    pub rope: Rope,
+    // the text when the buffer was last saved
+    pub saved: Rope,
+    pub syntax: &'static SyntaxReference,
+    pub indent: Indent,
+    pub line_ending: LineEnding,
+    pub wrap: Wrap,
+    // the encoding of the file the text was decoded from
+    pub encoding: Encoding,
+    // files with sequences that are invalid in their encoding can not be edited, saving them would lose data
+    pub read_only: bool,
+    history: History,
+}
+
+impl Buffer {
+    pub fn new(text: &str, extension: &str) -> Self {
+        let rope = Rope::from_str(text);
+        Self {
+            syntax: PS
+                .find_syntax_by_extension(extension)
+                .unwrap_or_else(|| PS.find_syntax_plain_text()),
+            indent: Indent::detect(&rope),
+            line_ending: LineEnding::detect(&rope),
+            wrap: Wrap::Off,
+            encoding: Encoding::default(),
+            read_only: false,
+            saved: rope.clone(),
+            rope,
+            history: History::default(),
+        }
+    }
+
+    pub fn history_version(&self) -> usize {
+        self.history.version
+    }
+
+    /// Records the text before a change so it can be undone.
+    pub fn record(&mut self, old: Rope, typing: bool) {
+        let history = &mut self.history;
+        if !(typing && history.typing) {
+            history.undo.push(old);
+            if history.undo.len() > MAX_HISTORY {
+                history.undo.remove(0);
+            }
+        }
+        history.redo.clear();
+        history.typing = typing;
+        history.version += 1;
+    }
+
+    /// Why the buffer can not be changed.
+    pub fn read_only_message(&self) -> String {
+        format!(
+            "The file has sequences that are not valid {} and can not be edited",
+            self.encoding
+        )
+    }
+
+    /// Replaces the whole text as a single change that can be undone, read-only buffers are not changed.
+    pub fn replace_text(&mut self, text: &str) -> Result<(), String> {
+        if self.read_only {
+            return Err(self.read_only_message());
+        }
+        self.replace(text);
+        Ok(())
+    }
+
+    fn replace(&mut self, text: &str) {
+        let old = std::mem::replace(&mut self.rope, Rope::from_str(text));
+        self.record(old, false);
+    }
+
+    /// Replaces the text with the text of the file on disk, as a change that can be undone that leaves the buffer saved.
+    /// Read-only buffers are reloaded too, their text is the file.
+    pub fn reload(&mut self, text: &str) {
+        if self.rope != text {
+            self.replace(text);
+        }
+        self.saved = self.rope.clone();
+        self.line_ending = LineEnding::detect(&self.rope);
+    }
+
+    pub fn undo(&mut self) -> bool {
+        let history = &mut self.history;
+        match history.undo.pop() {
+            Some(rope) => {
+                history.redo.push(std::mem::replace(&mut self.rope, rope));
+                history.typing = false;
+                history.version += 1;
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn redo(&mut self) -> bool {
+        let history = &mut self.history;
+        match history.redo.pop() {
+            Some(rope) => {
+                history.undo.push(std::mem::replace(&mut self.rope, rope));
+                history.typing = false;
+                history.version += 1;
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn is_dirty(&self) -> bool {
+        self.rope != self.saved
+    }
+
+    pub fn comment_tokens(&self) -> CommentTokens {
+        CommentTokens::for_syntax(self.syntax)
+    }
+
+    /// Rewrites every line break in the buffer with the new line ending.
+    pub fn set_line_ending(&mut self, line_ending: LineEnding) -> Result<(), String> {
+        let text = line_ending.convert(&self.rope.to_string());
+        if self.rope != text.as_str() {
+            self.replace_text(&text)?;
+        }
+        self.line_ending = line_ending;
+        Ok(())
+    }
+}
+
+#[test]
+fn detect_indent() {
+    assert_eq!(
+        Indent::detect(&Rope::from_str("fn main() {\n\tlet a;\n}")),
+        Indent::Tabs
+    );
+    assert_eq!(
+        Indent::detect(&Rope::from_str("a:\n  b:\n    c: 1\n  d: 2")),
+        Indent::Spaces(2)
+    );
+    assert_eq!(
+        Indent::detect(&Rope::from_str("/*\n * doc\n */")),
+        Indent::Spaces(4)
+    );
+}
+
+#[test]
+fn line_endings() {
+    let mut buffer = Buffer::new("a\r\nb\r\nc\n", "txt");
+    assert_eq!(buffer.line_ending, LineEnding::CrLf);
+
+    buffer.set_line_ending(LineEnding::Lf).unwrap();
+    assert_eq!(buffer.rope.to_string(), "a\nb\nc\n");
+    assert_eq!(LineEnding::detect(&buffer.rope), LineEnding::Lf);
+
+    buffer.set_line_ending(LineEnding::CrLf).unwrap();
+    assert_eq!(buffer.rope.to_string(), "a\r\nb\r\nc\r\n");
+
+    buffer.set_line_ending(LineEnding::Cr).unwrap();
+    assert_eq!(buffer.rope.to_string(), "a\rb\rc\r");
+    assert_eq!(LineEnding::detect(&buffer.rope), LineEnding::Cr);
+
+    // read-only buffers keep their line endings
+    buffer.read_only = true;
+    assert!(buffer.set_line_ending(LineEnding::Lf).is_err());
+    assert!(buffer.replace_text("a").is_err());
+    assert_eq!(buffer.rope.to_string(), "a\rb\rc\r");
+    assert_eq!(buffer.line_ending, LineEnding::Cr);
+}
+
+#[test]
+fn undo_redo() {
+    let mut buffer = Buffer::new("a", "txt");
+    let old = buffer.rope.clone();
+    buffer.rope.insert(1, "b");
+    buffer.record(old, true);
+    let old = buffer.rope.clone();
+    buffer.rope.insert(2, "c");
+    buffer.record(old, true);
+    buffer.replace_text("abc d").unwrap();
+
+    // typing is undone together
+    assert!(buffer.undo());
+    assert_eq!(buffer.rope.to_string(), "abc");
+    assert!(buffer.undo());
+    assert_eq!(buffer.rope.to_string(), "a");
+    assert!(!buffer.undo());
+    assert!(!buffer.is_dirty());
+
+    assert!(buffer.redo());
+    assert_eq!(buffer.rope.to_string(), "abc");
+    // a new change can not be redone past
+    buffer.replace_text("x").unwrap();
+    assert!(!buffer.redo());
+}
+
+#[test]
+fn wrap_width() {
+    assert_eq!(Wrap::Off.width(Some(100)), None);
+    assert_eq!(Wrap::Viewport.width(Some(100)), Some(100));
+    assert_eq!(Wrap::Column(80).width(Some(60)), Some(60));
+    // windows that can not be measured wrap at fixed columns
+    assert_eq!(Wrap::Column(80).width(None), Some(80));
+    assert_eq!(Wrap::Viewport.width(None), Some(DEFAULT_COLUMNS));
+}
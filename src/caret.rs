@@ -0,0 +1,74 @@
+use crate::settings::CaretShape;
+use crate::utils::color_to_string;
+use dioxus::prelude::*;
+use syntect::highlighting::{Color, Style};
+
+/// The blink animation of carets, defined once at the root of the editor.
+pub const CARET_KEYFRAMES: &str = "@keyframes caret-blink { 50% { opacity: 0; } }";
+
+/// The character under a cursor with the caret drawn over it.
+#[derive(Props, PartialEq)]
+pub struct CaretProps {
+    style: Style,
+    text: String,
+    color: Color,
+    shape: CaretShape,
+    blink: bool,
+}
+pub fn Caret(cx: Scope<CaretProps>) -> Element {
+    let text = &cx.props.text;
+    let bg = color_to_string(cx.props.style.background);
+    let caret = color_to_string(cx.props.color);
+
+    // terminals can not draw over a cell or animate it, so the cell itself is styled instead
+    #[cfg(feature = "term")]
+    {
+        let (fg, bg, text_decoration) = match cx.props.shape {
+            CaretShape::Bar | CaretShape::Block => (bg, caret, "none"),
+            CaretShape::Underline => (caret, bg, "underline"),
+        };
+        cx.render(rsx! {
+            span{
+                background_color: "{bg}",
+                text_decoration: "{text_decoration}",
+                color: "{fg}",
+                "{text}"
+            }
+        })
+    }
+    #[cfg(not(feature = "term"))]
+    {
+        let fg = color_to_string(cx.props.style.foreground);
+        let (width, height, opacity) = match cx.props.shape {
+            CaretShape::Bar => ("2px", "100%", "1"),
+            CaretShape::Block => ("100%", "100%", "0.5"),
+            CaretShape::Underline => ("100%", "2px", "1"),
+        };
+        let animation = if cx.props.blink {
+            "caret-blink 1s step-end infinite"
+        } else {
+            "none"
+        };
+        cx.render(rsx! {
+            span{
+                position: "relative",
+                font_family: "monospace",
+                white_space: "pre",
+                background_color: "{bg}",
+                color: "{fg}",
+                "{text}"
+                // absolutely positioned so the caret takes up no space in the line
+                span{
+                    position: "absolute",
+                    left: "0px",
+                    bottom: "0px",
+                    width: "{width}",
+                    height: "{height}",
+                    opacity: "{opacity}",
+                    background_color: "{caret}",
+                    animation: "{animation}",
+                }
+            }
+        })
+    }
+}
@@ -1,58 +1,458 @@
+use std::cell::{Cell, RefCell};
+use std::rc::Rc;
+
+use crate::buffer::Buffer;
+use crate::caret::Caret;
+use crate::commands::{command_for, palette_input, Context};
+use crate::completion::{completion_input, open_completion, receive_completion};
+use crate::completion_popup::CompletionPopup;
 use crate::cursor::{Cursor, Pos};
 use crate::cursors::{Cursors, SelectionMarkerType};
+use crate::diagnostics::Severity;
+use crate::explorer::explorer_input;
+use crate::fold::Folds;
+use crate::gutter::{blank_gutter, fold_marker, gutter_line, gutter_width, Fold, GutterMarkers};
+use crate::highlight::HighlightCache;
+use crate::layout::{Layout, WrappedLine};
+use crate::macros::{record, Step};
+use crate::palette::CommandPalette;
+use crate::settings::Settings;
+use crate::snippet::{snippet_input, update_snippet};
 use crate::span::Span;
-use crate::utils::color_to_string;
-use crate::{PS, THEME};
+use crate::utils::{color_to_string, mix, sleep, viewport_columns, viewport_rows};
+use crate::whitespace::{active_guide, Kind, LineWhitespace, LINE_ENDING};
+use crate::word::{occurrences, word_at};
+use crate::workspace::{ViewState, Workspace};
+use crate::{editor_colors, theme};
+use dioxus::events::KeyboardData;
 use dioxus::prelude::*;
-use ropey::Rope;
-use syntect::easy::HighlightLines;
+use dioxus_html::KeyCode;
 use syntect::highlighting::{Color, Style};
 
-fn highlight_mod(mut s: Style) -> Style {
-    s.background.r += 40;
-    s.background.g += 40;
-    s.background.b += 40;
-    s
+// a highlighted piece of a line on a visual row, caret pieces are drawn with the caret over them
+// and underlined pieces have the color of their diagnostic
+type Segment = (usize, Style, String, Option<Color>, Option<Color>);
+
+// how long the cursor has to stay still before other occurrences of the word under it are highlighted
+const WORD_HIGHLIGHT_DELAY: u32 = 500;
+// how often the focused view checks if the language server answered a completion request
+const COMPLETION_POLL_INTERVAL: u32 = 50;
+// the lines scrolled by a step of the mouse wheel
+const WHEEL_LINES: f32 = 3.0;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Marker {
+    // the start or end of a selection and if it belongs to the primary cursor
+    Selection(SelectionMarkerType, bool),
+    // the start or end of another occurrence of the word under the primary cursor
+    Word,
+    // the start or end of the range of a diagnostic
+    DiagnosticStart(Severity),
+    DiagnosticEnd(Severity),
+}
+
+fn segment_style(mut style: Style, selected: bool, word: bool, focused: bool) -> Style {
+    if word {
+        style.background = editor_colors().word_highlight;
+    }
+    if selected {
+        style = editor_colors().selected(style, focused);
+    }
+    style
+}
+
+fn whitespace_style(kind: Kind, style: Style) -> Style {
+    match kind {
+        Kind::Text => style,
+        Kind::Whitespace => Style {
+            foreground: editor_colors().whitespace,
+            ..style
+        },
+        Kind::Trailing => Style {
+            foreground: editor_colors().whitespace,
+            background: editor_colors().trailing_whitespace,
+            ..style
+        },
+        Kind::Guide => Style {
+            foreground: editor_colors().guide,
+            ..style
+        },
+        Kind::ActiveGuide => Style {
+            foreground: editor_colors().active_guide,
+            ..style
+        },
+    }
+}
+
+/// Moves the character after each caret marker into the marker so the caret is drawn over it.
+/// Carets at the end of a row are drawn over an empty cell.
+fn place_carets(segments: Vec<Segment>, end_style: Style) -> Vec<Segment> {
+    let mut placed = Vec::new();
+    let mut segments = segments.into_iter().peekable();
+    while let Some((row, style, text, caret, underline)) = segments.next() {
+        if caret.is_none() {
+            placed.push((row, style, text, caret, underline));
+            continue;
+        }
+        match segments.peek_mut() {
+            Some((next_row, next_style, next_text, None, _))
+                if *next_row == row && !next_text.is_empty() =>
+            {
+                let len = next_text.chars().next().unwrap().len_utf8();
+                let rest = next_text.split_off(len);
+                placed.push((
+                    row,
+                    *next_style,
+                    std::mem::replace(next_text, rest),
+                    caret,
+                    None,
+                ));
+            }
+            _ => placed.push((row, end_style, " ".to_string(), caret, None)),
+        }
+    }
+    placed.retain(|(_, _, text, _, _)| !text.is_empty());
+    placed
 }
 
-#[derive(Props, PartialEq)]
-pub struct CodeAreaProps {
-    initial_text: String,
+/// Runs the command bound to a key press, or types it.
+fn handle_key(k: &KeyboardData, ctx: &mut Context) {
+    if ctx.workspace.read().message.is_some() {
+        ctx.workspace.write().message = None;
+    }
+    let old = ctx.buffer.rope.clone();
+    let version = ctx.buffer.history_version();
+    let mut typed = false;
+    let completing = ctx.workspace.read().completion.is_some();
+    // the language server has not answered the completions asked for yet
+    let asking = ctx.workspace.read().completion_request.is_some();
+    let explorer_focused = ctx
+        .workspace
+        .read()
+        .explorer
+        .as_ref()
+        .map_or(false, |e| e.focused);
+    if ctx.workspace.read().palette.is_some() {
+        palette_input(k, ctx);
+    } else if explorer_focused && explorer_input(k, &mut ctx.workspace.write()) {
+        // the key was used by the file explorer
+    } else if completing && completion_input(k, ctx) {
+        // the key was used by the completion popup
+    } else if snippet_input(k, ctx) {
+        // the key moved between the tab stops of a snippet
+    } else {
+        // other keys close the popup, typing a word opens it again
+        if completing || asking {
+            ctx.workspace.write().close_completion();
+        }
+        if let Some(command) = command_for(k) {
+            command.run(ctx);
+        } else {
+            ctx.cursors.process_input(k, &mut ctx.buffer.rope);
+            typed = true;
+            if k.key.chars().count() == 1 {
+                record(ctx.workspace, Step::Type(k.key.clone()));
+            }
+        }
+        // the popup is not opened while recording a macro, what is picked in it would not be replayed
+        let recording = ctx.workspace.read().recording.is_some();
+        let reopen = (completing || asking) && k.key_code == KeyCode::Backspace;
+        if !recording && (typed || reopen) {
+            open_completion(ctx, false);
+        }
+    }
+    // read-only buffers are only changed by reopening their file
+    if ctx.buffer.read_only && ctx.buffer.is_dirty() && ctx.buffer.rope != old {
+        let changed = std::mem::replace(&mut ctx.buffer.rope, old.clone());
+        ctx.cursors.update(&changed, &old);
+        ctx.workspace.write().message = Some(ctx.buffer.read_only_message());
+    }
+    // changes that did not record themselves, like undo does, can be undone
+    if ctx.buffer.history_version() == version && ctx.buffer.rope != old {
+        ctx.buffer.record(old.clone(), typed);
+    }
+    ctx.folds.update(&old, &ctx.buffer.rope);
+    ctx.cursors.move_out_of_folds(ctx.folds, &ctx.buffer.rope);
+    update_snippet(ctx);
 }
-pub fn CodeArea(cx: Scope<CodeAreaProps>) -> Element {
-    let (scroll_y, set_scroll_y) = use_state(&cx, || 0.0);
-    let rope = use_ref(&cx, || Rope::from_str(&cx.props.initial_text));
-    let cursors = use_ref(&cx, || Cursors::default());
 
-    let text = rope.read().clone();
+#[derive(Props)]
+pub struct CodeAreaProps<'a> {
+    buffer: &'a UseRef<Buffer>,
+    cursors: &'a UseRef<Cursors>,
+    settings: &'a UseRef<Settings>,
+    folds: &'a UseRef<Folds>,
+    workspace: &'a UseRef<Workspace>,
+    // the pane the view is shown in
+    pane: usize,
+    tab: usize,
+}
+pub fn CodeArea<'a>(cx: Scope<'a, CodeAreaProps<'a>>) -> Element {
+    let (scroll_y, set_scroll_y) = use_state(&cx, || {
+        cx.props
+            .workspace
+            .read()
+            .views
+            .get(&(cx.props.pane, cx.props.tab))
+            .map_or(0.0, |v| v.scroll)
+    });
+    // the row a drag in the gutter started on
+    let (drag_start, set_drag_start) = use_state(&cx, || None::<usize>);
+    let (focused, set_focused) = use_state(&cx, || false);
+    let highlights = cx.use_hook(|_| HighlightCache::default(), |h| h);
+    // counts inputs, the word under the cursor is highlighted once no input happened for a while
+    let idle = cx.use_hook(|_| Rc::new(Cell::new(0usize)), |i| &*i);
+    // a single timer waits for the inputs to stop
+    let waiting = cx.use_hook(|_| Rc::new(Cell::new(false)), |w| &*w);
+    let (word_ready, set_word_ready) = use_state(&cx, || true);
+    let (tick, set_tick) = use_state(&cx, || 0usize);
+    let polling = cx.use_hook(|_| Rc::new(Cell::new(false)), |p| &*p);
+    let restart_idle = move || {
+        idle.set(idle.get() + 1);
+        if *word_ready {
+            set_word_ready(false);
+        }
+        if waiting.get() {
+            return;
+        }
+        waiting.set(true);
+        let idle = idle.clone();
+        let waiting = waiting.clone();
+        let set_word_ready = set_word_ready.clone();
+        cx.push_future(async move {
+            // wait again for every input during the delay
+            loop {
+                let input = idle.get();
+                sleep(WORD_HIGHLIGHT_DELAY).await;
+                if idle.get() == input {
+                    break;
+                }
+            }
+            waiting.set(false);
+            set_word_ready(true);
+        });
+    };
+    let buffer = cx.props.buffer;
+    let cursors = cx.props.cursors;
+    let folds = cx.props.folds;
+    let workspace = cx.props.workspace;
+    let pane = cx.props.pane;
+    let tab = cx.props.tab;
+    let settings = cx.props.settings;
+    let line_numbers = settings.read().line_numbers;
+
+    let (pane_width, pane_height) = workspace
+        .read()
+        .rects()
+        .into_iter()
+        .find(|(id, _)| *id == pane)
+        .map_or((100.0, 100.0), |(_, rect)| (rect.width, rect.height));
+    let columns = viewport_columns().map(|c| (c as f32 * pane_width / 100.0) as usize);
+    // the tab bar and status bar take a row each
+    let rows = ((viewport_rows() as f32 * pane_height / 100.0) as usize)
+        .saturating_sub(2)
+        .max(1);
+    let gutter = gutter_width(buffer.read().rope.len_lines());
+    let wrap_width = buffer
+        .read()
+        .wrap
+        .width(columns.map(|c| c.saturating_sub(gutter)));
+
+    // the text the cursors were last moved for, edits from other views of the buffer move this view's cursors
+    let seen = cx.use_hook(|_| RefCell::new(buffer.read().rope.clone()), |s| &*s);
+    // keys pressed in another pane after this pane was focused with a shortcut
+    if workspace.read().focused == pane && workspace.read().has_forwarded() {
+        let keys = workspace.write().take_forwarded();
+        for k in keys {
+            handle_key(
+                &k,
+                &mut Context {
+                    buffer: &mut buffer.write(),
+                    cursors: &mut cursors.write(),
+                    folds: &mut folds.write(),
+                    settings,
+                    workspace,
+                    wrap_width,
+                    tab,
+                    keywords: &highlights.keywords(),
+                    fold_ranges: highlights.fold_ranges(),
+                },
+            );
+        }
+        *seen.borrow_mut() = buffer.read().rope.clone();
+    }
+    if *seen.borrow() != buffer.read().rope {
+        let new = buffer.read().rope.clone();
+        cursors.write().update(&seen.borrow(), &new);
+        folds.write().update(&seen.borrow(), &new);
+        *seen.borrow_mut() = new;
+    }
+    // completions of the language server are added to the popup once they are received
+    let request = workspace.read().completion_request.clone();
+    if let Some(request) = request.filter(|_| workspace.read().focused == pane) {
+        if request.is_received() {
+            receive_completion(&mut workspace.write());
+        } else if !polling.get() {
+            polling.set(true);
+            let polling = polling.clone();
+            let set_tick = set_tick.clone();
+            let tick = *tick;
+            cx.push_future(async move {
+                while !request.is_received() {
+                    sleep(COMPLETION_POLL_INTERVAL).await;
+                }
+                polling.set(false);
+                set_tick(tick + 1);
+            });
+        }
+    }
+    // the workspace keeps the state of every view for the session
+    let view = ViewState {
+        cursors: cursors.read().clone(),
+        folds: folds.read().clone(),
+        scroll: *scroll_y,
+    };
+    if workspace.read().views.get(&(pane, tab)) != Some(&view) {
+        workspace.write_silent().views.insert((pane, tab), view);
+    }
+    // jump to a line or match after opening a file from the finder or the search panel
+    let goto = workspace.read().goto.clone();
+    if let Some((goto_tab, cursor)) = goto {
+        if goto_tab == tab && workspace.read().focused == pane {
+            workspace.write().goto = None;
+            let last_row = buffer.read().rope.len_lines() - 1;
+            let cursor = if cursor.last().row() <= last_row {
+                cursor
+            } else {
+                Cursor::from_start(Pos::new(0, last_row))
+            };
+            *cursors.write() = Cursors::new(vec![cursor]);
+        }
+    }
+
+    // the view scrolls to the primary caret when it moves, the wheel scrolls it away
+    let followed = cx.use_hook(|_| RefCell::new(None::<Pos>), |f| &*f);
+    let caret = cursors.read().primary().caret().clone();
+    let mut scroll = *scroll_y as usize;
+    if followed.borrow().as_ref() != Some(&caret) {
+        let current_folds = folds.read();
+        let layout = Layout::new(&current_folds, wrap_width);
+        scroll = layout.scroll_to(&buffer.read().rope, scroll, caret.row(), rows);
+        drop(current_folds);
+        if scroll != *scroll_y as usize {
+            set_scroll_y(scroll as f32);
+        }
+        *followed.borrow_mut() = Some(caret);
+    }
+
+    let text = buffer.read().rope.clone();
+    let syntax = buffer.read().syntax;
     let num_lines = text.len_lines();
     let lines = text.clone();
     let lines = lines.lines();
+    let current_folds = folds.read().clone();
+    highlights.update(&text, syntax);
+    let highlights = &*highlights;
+    let foldable: std::collections::BTreeSet<usize> =
+        highlights.fold_ranges().iter().map(|r| r.start).collect();
 
     let current_cursors = cursors.read().clone();
-    let cursor_sections = current_cursors.sorted();
-    let mut cursor_sections_iter = cursor_sections
+    let current_rows: Vec<usize> = current_cursors.list.iter().map(|c| c.caret().row()).collect();
+    let mut markers: Vec<_> = current_cursors
+        .sorted()
         .into_iter()
-        .map(|section| (section.pos.idx(&text), section.marker_type))
-        .peekable();
+        .map(|section| {
+            (
+                section.pos.idx(&text),
+                Marker::Selection(section.marker_type, section.is_primary()),
+            )
+        })
+        .collect();
+    if *word_ready {
+        if let Some((start, end)) = word_at(current_cursors.primary().caret(), &text) {
+            let word = text.slice(start..end).to_string();
+            // only the lines on screen are searched
+            let shown = Layout::new(&current_folds, wrap_width).shown(&text, scroll, rows);
+            for (s, e) in occurrences(&word, &text, shown) {
+                if s != start {
+                    markers.push((s, Marker::Word));
+                    markers.push((e, Marker::Word));
+                }
+            }
+        }
+    }
+    let diagnostics = workspace
+        .read()
+        .diagnostics
+        .get(&tab)
+        .cloned()
+        .unwrap_or_default();
+    for (start, end, severity) in diagnostics.ranges(&text) {
+        markers.push((start, Marker::DiagnosticStart(severity)));
+        markers.push((end, Marker::DiagnosticEnd(severity)));
+    }
+    // the sort is stable so cursor markers at the same position keep their order
+    markers.sort_by_key(|(idx, _)| *idx);
+    let mut markers_iter = markers.into_iter().peekable();
+
+    let indent_width = buffer.read().indent.width();
+    let show_whitespace = cx.props.settings.read().show_whitespace;
+    let indent_guides = cx.props.settings.read().indent_guides;
+    let active = active_guide(&text, current_cursors.primary().caret().row(), indent_width);
 
-    let syntax = PS.find_syntax_by_extension("rs").unwrap();
-    let mut h = HighlightLines::new(syntax, &THEME);
-    let bg = &color_to_string(THEME.settings.background.unwrap());
+    let bg = &color_to_string(editor_colors().background);
 
     let mut text_pos = 0;
     let mut highlighted = false;
+    let mut word_highlighted = false;
+    // the severities of the diagnostics the text is in, the underline has the color of the most severe
+    let mut underlines: Vec<Severity> = Vec::new();
+
+    let fold_style = Style {
+        foreground: editor_colors().foreground,
+        background: editor_colors().line_highlight,
+        ..Default::default()
+    };
 
-    let cursor_style = Style {
-        foreground: Color::WHITE,
-        background: THEME.settings.background.unwrap(),
+    // the style of the empty space after the end of a line
+    let end_style = Style {
+        background: editor_colors().background,
         ..Default::default()
     };
 
+    let caret_shape = cx.props.settings.read().caret_shape;
+    let caret_blink = cx.props.settings.read().caret_blink;
+    // secondary cursors are faded into the background
+    let primary_caret = theme().settings.caret.unwrap_or(editor_colors().foreground);
+    let caret_color = |primary: bool| {
+        if primary {
+            primary_caret
+        } else {
+            mix(primary_caret, editor_colors().background, 0.5)
+        }
+    };
+
+    let show_palette = workspace.read().focused == pane && workspace.read().palette.is_some();
+    // the popup is shown below the line of the primary cursor
+    let completion_row = (workspace.read().focused == pane
+        && workspace.read().completion.is_some())
+    .then(|| current_cursors.primary().caret().row());
+    let message = workspace
+        .read()
+        .message
+        .clone()
+        .filter(|_| workspace.read().focused == pane);
+    let message_bg = color_to_string(
+        theme()
+            .settings
+            .gutter
+            .unwrap_or(editor_colors().background),
+    );
+
     cx.render(rsx! {
         div{
             width: "100%",
-            height: "100%",
+            flex_grow: "1",
             flex_direction: "column",
             align_items: "left",
             justify_content: "left",
@@ -60,65 +460,327 @@ pub fn CodeArea(cx: Scope<CodeAreaProps>) -> Element {
             tabindex: "0",
             display: "flex",
             box_sizing: "border-box",
+            position: "relative",
 
             prevent_default: "onkeydown",
-            onkeydown: |k| {
-                cursors.write().process_input(&*k, &mut rope.write())
+            onmouseup: move |_| set_drag_start(None),
+            onfocus: move |_| {
+                set_focused(true);
+                if workspace.read().focused != pane {
+                    workspace.write().focus(pane);
+                }
+            },
+            onmousedown: move |_| {
+                if workspace.read().focused != pane {
+                    workspace.write().focus(pane);
+                }
+            },
+            onblur: move |_| set_focused(false),
+            onkeydown: move |k| {
+                if workspace.read().focused != pane {
+                    workspace.write().forward((*k).clone());
+                    return;
+                }
+                handle_key(&*k, &mut Context {
+                    buffer: &mut buffer.write(),
+                    cursors: &mut cursors.write(),
+                    folds: &mut folds.write(),
+                    settings,
+                    workspace,
+                    wrap_width,
+                    tab,
+                    keywords: &highlights.keywords(),
+                    fold_ranges: highlights.fold_ranges(),
+                });
+                *seen.borrow_mut() = buffer.read().rope.clone();
+                restart_idle();
+            },
+            onwheel: move |w| {
+                let lines = w.data.delta_y.signum() as f32 * WHEEL_LINES;
+                set_scroll_y((scroll as f32 + lines).clamp(0.0, (num_lines - 1) as f32));
             },
-            // onwheel: move |w| set_scroll_y((scroll_y + w.data.delta_y.signum() as f32).max(0.0)),
+
+            show_palette.then(|| rsx! {
+                CommandPalette{
+                    workspace: workspace,
+                }
+            })
+            message.map(|message| rsx! {
+                div{
+                    position: "absolute",
+                    bottom: "0px",
+                    right: "0px",
+                    max_width: "60%",
+                    max_height: "50%",
+                    overflow: "auto",
+                    padding: "2px 6px",
+                    white_space: "pre-wrap",
+                    z_index: "1",
+                    background_color: "{message_bg}",
+                    "{message}"
+                }
+            })
 
             lines.enumerate().map(|(i, l)| {
                 let cs: std::borrow::Cow<str> = l.into();
-                let ranges = h.highlight(&cs, &PS);
+                let ranges = highlights.line(i);
+                let current_line = current_rows.contains(&i);
+                let line_style = |s: Style| if current_line {
+                    Style { background: editor_colors().line_highlight, ..s }
+                } else {
+                    s
+                };
+                let line_end_style = line_style(end_style);
 
-                let mut ranges: Vec<_> = ranges.into_iter().map(|(text_style, t)|{
-                    let final_text_pos = text_pos + t.len();
+                // split the highlighted sections where the line wraps, each section is tagged with its visual row
+                let wrapped = WrappedLine::new(&cs, wrap_width);
+                let breaks: Vec<usize> = wrapped.starts.iter().skip(1).map(|col| {
+                    cs.char_indices().nth(*col).map_or(cs.len(), |(b, _)| b)
+                }).collect();
+                let mut line_pos = 0;
+                let ranges: Vec<_> = ranges.into_iter().flat_map(|(text_style, mut t)|{
+                    let mut pieces = Vec::new();
+                    loop {
+                        let row = breaks.iter().filter(|b| **b <= line_pos).count();
+                        match breaks.get(row) {
+                            Some(b) if *b < line_pos + t.len() => {
+                                let (before, after) = t.split_at(b - line_pos);
+                                pieces.push((row, text_style, before));
+                                line_pos += before.len();
+                                t = after;
+                            }
+                            _ => {
+                                pieces.push((row, text_style, t));
+                                line_pos += t.len();
+                                break;
+                            }
+                        }
+                    }
+                    pieces
+                }).collect();
+
+                let mut ranges: Vec<_> = ranges.into_iter().map(|(row, text_style, t)|{
+                    let text_style = line_style(text_style);
+                    // the markers are char indexes of the text
+                    let final_text_pos = text_pos + t.chars().count();
                     let mut tail = t;
                     let mut segments = Vec::new();
-                    while let Some((idx, marker_type)) = cursor_sections_iter.next_if(|(idx, _)|{
+                    while let Some((idx, marker)) = markers_iter.next_if(|(idx, _)|{
                         final_text_pos > *idx
                     }){
-                        let (before, new_tail) = tail.split_at(idx - text_pos);
-                        text_pos += before.len();
+                        let at = tail.char_indices().nth(idx - text_pos).map_or(tail.len(), |(b, _)| b);
+                        let (before, new_tail) = tail.split_at(at);
+                        text_pos += before.chars().count();
                         tail = new_tail;
-                        segments.push((if highlighted{highlight_mod(text_style)}else{text_style}, before));
-                        highlighted = !highlighted;
-                        if marker_type == SelectionMarkerType::End{
-                            segments.push((cursor_style, "|"));
+                        let underline = underlines.iter().min().map(Severity::color);
+                        segments.push((row, segment_style(text_style, highlighted, word_highlighted, *focused), before, None, underline));
+                        match marker {
+                            Marker::Selection(marker_type, primary) => {
+                                highlighted = !highlighted;
+                                if marker_type == SelectionMarkerType::End{
+                                    segments.push((row, text_style, "", Some(caret_color(primary)), None));
+                                }
+                            }
+                            Marker::Word => word_highlighted = !word_highlighted,
+                            Marker::DiagnosticStart(severity) => underlines.push(severity),
+                            Marker::DiagnosticEnd(severity) => {
+                                if let Some(i) = underlines.iter().position(|s| *s == severity) {
+                                    underlines.remove(i);
+                                }
+                            }
                         }
                     }
-                    text_pos += tail.len();
-                    segments.push((if highlighted{highlight_mod(text_style)}else{text_style}, tail.trim_end_matches('\n')));
+                    text_pos += tail.chars().count();
+                    let underline = underlines.iter().min().map(Severity::color);
+                    segments.push((row, segment_style(text_style, highlighted, word_highlighted, *focused), tail.trim_end_matches(&['\n', '\r'][..]), None, underline));
                     segments.into_iter()
-                }).flatten().filter(|(_, t)| t.len() > 0).collect();
+                }).flatten().filter(|(_, _, t, caret, _)| t.len() > 0 || caret.is_some()).collect();
+
+                // replace the whitespace and draw the indent guides
+                let line_guide = active.filter(|(_, start, end)| (*start..=*end).contains(&i)).map(|(level, _, _)| level);
+                let whitespace = LineWhitespace::new(&cs, indent_width, show_whitespace, indent_guides, line_guide);
+                let mut col = 0;
+                let mut ranges: Vec<Segment> = ranges.into_iter().flat_map(|(row, text_style, t, caret, underline)|{
+                    if caret.is_some() {
+                        return vec![(row, text_style, String::new(), caret, None)];
+                    }
+                    let pieces = whitespace.render(t, col);
+                    col += t.chars().count();
+                    pieces.into_iter().map(|(kind, text)| (row, whitespace_style(kind, text_style), text, None, underline)).collect()
+                }).collect();
+                let last_row = wrapped.starts.len() - 1;
+                if show_whitespace && i < num_lines - 1 {
+                    ranges.push((last_row, whitespace_style(Kind::Whitespace, line_end_style), LINE_ENDING.to_string(), None, None));
+                }
                 // if this is the last line add any unrendered cursors
                 if i == num_lines - 1{
-                    if cursor_sections_iter.find(|(_, marker_type)| *marker_type == SelectionMarkerType::Start).is_some(){
-                        ranges.push((cursor_style, "|"));
+                    let unrendered = markers_iter.find_map(|(_, marker)| match marker {
+                        Marker::Selection(SelectionMarkerType::Start, primary) => Some(primary),
+                        _ => None,
+                    });
+                    if let Some(primary) = unrendered{
+                        ranges.push((last_row, line_end_style, String::new(), Some(caret_color(primary)), None));
                     }
                 }
-                // force rendering of line
-                if ranges.len() == 0{
-                    ranges.push((Style {
-                        background: THEME.settings.background.unwrap(),
-                        ..Default::default()
-                    }, " "))
+                let mut ranges = place_carets(ranges, line_end_style);
+                let folded = current_folds.is_folded(i);
+                if folded{
+                    ranges.push((last_row, fold_style, " ⋯ ".to_string(), None, None));
+                }
+                // the message of the most severe diagnostic on the line is shown after it
+                let diagnostic = diagnostics.on_row(i);
+                if let Some(diagnostic) = diagnostic {
+                    let style = Style {
+                        foreground: mix(editor_colors().background, diagnostic.severity.color(), 0.8),
+                        ..line_end_style
+                    };
+                    ranges.push((last_row, style, format!("    {}", diagnostic.summary()), None, None));
+                }
+                if current_folds.is_hidden(i){
+                    return None;
+                }
+                let mut visual_rows = vec![Vec::new(); wrapped.starts.len()];
+                for (row, s, t, caret, underline) in ranges {
+                    visual_rows[row].push((s, t, caret, underline));
+                }
+                // force rendering of rows
+                for row in visual_rows.iter_mut() {
+                    if row.is_empty(){
+                        row.push((line_end_style, " ".to_string(), None, None))
+                    }
                 }
+                let first_row = visual_rows.remove(0);
+                let markers = GutterMarkers {
+                    diagnostic: diagnostic.map(|d| (d.severity.icon(), d.severity.color())),
+                    fold: foldable.contains(&i).then(|| if folded { Fold::Closed } else { Fold::Open }),
+                    ..Default::default()
+                };
+                let gutter = gutter_line(i, &current_rows, num_lines, line_numbers, &markers);
+                let (fold_marker_style, fold_marker_text) = fold_marker(&markers);
+                let (blank_gutter_style, blank_gutter_text) = blank_gutter(num_lines);
+                let continuation_indent = " ".repeat(wrapped.indent);
+                let line_bg = color_to_string(line_end_style.background);
+                // the column of the primary cursor on its visual row
+                let popup_left = (completion_row == Some(i)).then(|| {
+                    let col = current_cursors.primary().caret().col(&text);
+                    let start = wrapped.starts.iter().rev().find(|s| **s <= col).copied().unwrap_or(0);
+                    let indent = if start > 0 { wrapped.indent } else { 0 };
+                    gutter_width(num_lines) + col - start + indent
+                });
                 cx.render(rsx! {
                     div{
                         width: "100%",
-                        flex_direction: "row",
-                        ranges.into_iter().map(|(s, t)|{
-                            cx.render(rsx! {
+                        background_color: "{line_bg}",
+                        div{
+                            width: "100%",
+                            flex_direction: "row",
+                            span{
+                                cursor: "pointer",
+                                onmousedown: move |_| {
+                                    restart_idle();
+                                    set_drag_start(Some(i));
+                                    *cursors.write() = Cursors::new(vec![Cursor::select_lines(i, i, &buffer.read().rope)]);
+                                },
+                                onmouseover: move |m| {
+                                    if let Some(start) = *drag_start {
+                                        if m.buttons & 1 != 0 {
+                                            *cursors.write() = Cursors::new(vec![Cursor::select_lines(start, i, &buffer.read().rope)]);
+                                        }
+                                    }
+                                },
+                                gutter.into_iter().map(|(s, t)|{
+                                    cx.render(rsx! {
+                                        Span{
+                                            style: s,
+                                            text: t
+                                        }
+                                    })
+                                })
+                            }
+                            span{
+                                cursor: "pointer",
+                                onclick: move |_| {
+                                    let buffer = buffer.read();
+                                    folds.write().toggle(highlights.fold_ranges(), i);
+                                    cursors.write().move_out_of_folds(&folds.read(), &buffer.rope);
+                                },
                                 Span{
-                                    style: s,
-                                    text: t.to_string()
+                                    style: fold_marker_style,
+                                    text: fold_marker_text
+                                }
+                            }
+                            first_row.into_iter().map(|(s, t, caret, underline)|{
+                                match caret {
+                                    Some(color) => cx.render(rsx! {
+                                        Caret{
+                                            style: s,
+                                            text: t,
+                                            color: color,
+                                            shape: caret_shape,
+                                            blink: caret_blink,
+                                        }
+                                    }),
+                                    None => cx.render(rsx! {
+                                        Span{
+                                            style: s,
+                                            text: t,
+                                            underline: underline,
+                                        }
+                                    }),
+                                }
+                            })
+                        }
+                        visual_rows.into_iter().map(|row| {
+                            let blank_gutter_text = blank_gutter_text.clone();
+                            let continuation_indent = continuation_indent.clone();
+                            cx.render(rsx! {
+                                div{
+                                    width: "100%",
+                                    flex_direction: "row",
+                                    Span{
+                                        style: blank_gutter_style,
+                                        text: blank_gutter_text
+                                    }
+                                    Span{
+                                        style: line_end_style,
+                                        text: continuation_indent
+                                    }
+                                    row.into_iter().map(|(s, t, caret, underline)|{
+                                        match caret {
+                                            Some(color) => cx.render(rsx! {
+                                                Caret{
+                                                    style: s,
+                                                    text: t,
+                                                    color: color,
+                                                    shape: caret_shape,
+                                                    blink: caret_blink,
+                                                }
+                                            }),
+                                            None => cx.render(rsx! {
+                                                Span{
+                                                    style: s,
+                                                    text: t,
+                                                    underline: underline,
+                                                }
+                                            }),
+                                        }
+                                    })
                                 }
                             })
                         })
+                        popup_left.map(|left| rsx! {
+                            div{
+                                position: "relative",
+                                height: "0px",
+                                CompletionPopup{
+                                    workspace: workspace,
+                                    left: left,
+                                }
+                            }
+                        })
                     }
                 })
-            }).skip(*scroll_y as usize)
+            }).skip(scroll)
         }
     })
 }
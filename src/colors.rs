@@ -0,0 +1,176 @@
+use syntect::highlighting::{Color, Style, ThemeSettings};
+
+use crate::utils::mix;
+
+/// The relative luminance of a color from 0 for black to 1 for white.
+pub fn luminance(c: Color) -> f32 {
+    let channel = |v: u8| {
+        let v = v as f32 / 255.0;
+        if v <= 0.03928 {
+            v / 12.92
+        } else {
+            ((v + 0.055) / 1.055).powf(2.4)
+        }
+    };
+    0.2126 * channel(c.r) + 0.7152 * channel(c.g) + 0.0722 * channel(c.b)
+}
+
+/// Draws a possibly transparent color over an opaque background.
+fn over(background: Color, c: Color) -> Color {
+    let opaque = Color { a: 255, ..c };
+    mix(background, opaque, c.a as f32 / 255.0)
+}
+
+/// Moves the background towards white on dark themes and towards black on light themes.
+fn contrast(background: Color, amount: f32) -> Color {
+    if luminance(background) < 0.5 {
+        mix(background, Color::WHITE, amount)
+    } else {
+        mix(background, Color::BLACK, amount)
+    }
+}
+
+/// Colors used to draw the editor that are not part of the syntax highlighting.
+/// They are taken from the theme if it defines them, otherwise they are derived from the background.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct EditorColors {
+    pub background: Color,
+    pub foreground: Color,
+    pub selection: Color,
+    pub selection_foreground: Option<Color>,
+    // used while the editor is not focused
+    pub inactive_selection: Color,
+    pub line_highlight: Color,
+    // other occurrences of the word under the cursor
+    pub word_highlight: Color,
+    pub find_highlight: Color,
+    pub find_highlight_foreground: Option<Color>,
+    pub whitespace: Color,
+    pub trailing_whitespace: Color,
+    pub guide: Color,
+    pub active_guide: Color,
+}
+
+impl EditorColors {
+    pub fn new(settings: &ThemeSettings) -> Self {
+        let background = settings.background.unwrap_or(Color::WHITE);
+        let foreground = settings.foreground.unwrap_or(Color::BLACK);
+        let pick = |c: Option<Color>, amount: f32| {
+            c.map(|c| over(background, c))
+                .unwrap_or_else(|| contrast(background, amount))
+        };
+        // foreground colors are faded into the background
+        let fade = |c: Option<Color>, amount: f32| {
+            c.map(|c| over(background, c))
+                .unwrap_or_else(|| mix(background, foreground, amount))
+        };
+        let selection = pick(settings.selection, 0.25);
+        Self {
+            background,
+            foreground,
+            selection,
+            selection_foreground: settings.selection_foreground,
+            inactive_selection: settings
+                .inactive_selection
+                .map(|c| over(background, c))
+                .unwrap_or_else(|| mix(background, selection, 0.5)),
+            line_highlight: pick(settings.line_highlight, 0.06),
+            word_highlight: pick(settings.highlight, 0.15),
+            find_highlight: settings
+                .find_highlight
+                .map(|c| over(background, c))
+                .unwrap_or_else(|| {
+                    let yellow = Color {
+                        r: 255,
+                        g: 200,
+                        b: 0,
+                        a: 255,
+                    };
+                    mix(background, yellow, 0.35)
+                }),
+            find_highlight_foreground: settings.find_highlight_foreground,
+            whitespace: mix(background, foreground, 0.3),
+            trailing_whitespace: mix(
+                background,
+                Color {
+                    r: 255,
+                    g: 0,
+                    b: 0,
+                    a: 255,
+                },
+                0.35,
+            ),
+            guide: fade(settings.guide, 0.15),
+            active_guide: fade(settings.active_guide, 0.45),
+        }
+    }
+
+    /// The style of selected text, focused is false while the editor does not have focus.
+    pub fn selected(&self, style: Style, focused: bool) -> Style {
+        if focused {
+            Style {
+                foreground: self.selection_foreground.unwrap_or(style.foreground),
+                background: self.selection,
+                ..style
+            }
+        } else {
+            Style {
+                background: self.inactive_selection,
+                ..style
+            }
+        }
+    }
+}
+
+#[test]
+fn fallback_colors() {
+    let dark = EditorColors::new(&ThemeSettings {
+        background: Some(Color::BLACK),
+        ..Default::default()
+    });
+    assert!(luminance(dark.selection) > luminance(dark.inactive_selection));
+    assert!(luminance(dark.inactive_selection) > luminance(dark.line_highlight));
+    assert!(luminance(dark.line_highlight) > 0.0);
+
+    // light themes are darkened instead of overflowing
+    let light = EditorColors::new(&ThemeSettings {
+        background: Some(Color::WHITE),
+        ..Default::default()
+    });
+    assert!(luminance(light.selection) < luminance(light.inactive_selection));
+    assert!(luminance(light.line_highlight) < 1.0);
+}
+
+#[test]
+fn theme_colors() {
+    let selection = Color {
+        r: 0,
+        g: 0,
+        b: 255,
+        a: 255,
+    };
+    let colors = EditorColors::new(&ThemeSettings {
+        background: Some(Color::WHITE),
+        selection: Some(selection),
+        // half transparent black
+        line_highlight: Some(Color {
+            a: 128,
+            ..Color::BLACK
+        }),
+        ..Default::default()
+    });
+    assert_eq!(colors.selection, selection);
+    assert_eq!(
+        colors.line_highlight,
+        Color {
+            r: 127,
+            g: 127,
+            b: 127,
+            a: 255,
+        }
+    );
+    assert_eq!(
+        colors.selected(Style::default(), true).background,
+        selection
+    );
+}
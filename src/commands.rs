@@ -0,0 +1,1393 @@
+use std::collections::{BTreeSet, HashMap};
+use std::fmt::{self, Display};
+use std::path::{Path, PathBuf};
+
+use dioxus::events::KeyboardData;
+use dioxus::prelude::UseRef;
+use dioxus_html::KeyCode;
+use lsp_types::{Location, Position, TextEdit, Url};
+use ropey::Rope;
+
+use crate::buffer::{Buffer, Indent, LineEnding, Wrap};
+use crate::completion::open_completion;
+use crate::cursor::{Cursor, Motion, Pos};
+use crate::cursors::Cursors;
+use crate::encoding::Encoding;
+use crate::finder::{parse_query, MAX_RESULTS};
+use crate::fold::{FoldRange, Folds};
+use crate::fuzzy::{fuzzy_match, FuzzyMatch};
+use crate::layout::Layout;
+use crate::lsp::{to_idx, to_position, LspClient, Pending};
+use crate::macros::{
+    play, play_on_lines, record, save_macro, saved_macros, toggle_recording, Step,
+};
+use crate::settings::{LineNumbers, Settings};
+use crate::utils::modified;
+use crate::word::word_at;
+use crate::workspace::{Direction, Edit, Workspace};
+use crate::{set_theme, PS, TS};
+
+/// A key combination that runs a command.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Keybinding {
+    key: KeyCode,
+    ctrl: bool,
+    shift: bool,
+    alt: bool,
+}
+
+impl Keybinding {
+    pub fn new(key: KeyCode) -> Self {
+        Self {
+            key,
+            ctrl: false,
+            shift: false,
+            alt: false,
+        }
+    }
+
+    pub fn ctrl(self) -> Self {
+        Self { ctrl: true, ..self }
+    }
+
+    pub fn shift(self) -> Self {
+        Self {
+            shift: true,
+            ..self
+        }
+    }
+
+    pub fn alt(self) -> Self {
+        Self { alt: true, ..self }
+    }
+
+    pub fn matches(&self, k: &KeyboardData) -> bool {
+        k.key_code == self.key
+            && k.ctrl_key == self.ctrl
+            && k.shift_key == self.shift
+            && k.alt_key == self.alt
+    }
+}
+
+impl Display for Keybinding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.ctrl {
+            write!(f, "Ctrl-")?;
+        }
+        if self.alt {
+            write!(f, "Alt-")?;
+        }
+        if self.shift {
+            write!(f, "Shift-")?;
+        }
+        match self.key {
+            KeyCode::UpArrow => write!(f, "Up"),
+            KeyCode::DownArrow => write!(f, "Down"),
+            KeyCode::LeftArrow => write!(f, "Left"),
+            KeyCode::RightArrow => write!(f, "Right"),
+            KeyCode::ForwardSlash => write!(f, "/"),
+            KeyCode::BackSlash => write!(f, "\\"),
+            KeyCode::OpenBracket => write!(f, "["),
+            KeyCode::CloseBraket => write!(f, "]"),
+            KeyCode::Num0
+            | KeyCode::Num1
+            | KeyCode::Num2
+            | KeyCode::Num3
+            | KeyCode::Num4
+            | KeyCode::Num5
+            | KeyCode::Num6
+            | KeyCode::Num7
+            | KeyCode::Num8
+            | KeyCode::Num9 => write!(f, "{}", self.key as usize - KeyCode::Num0 as usize),
+            key => write!(f, "{:?}", key),
+        }
+    }
+}
+
+/// Everything a command can change.
+pub struct Context<'a> {
+    pub buffer: &'a mut Buffer,
+    pub cursors: &'a mut Cursors,
+    pub folds: &'a mut Folds,
+    pub settings: &'a UseRef<Settings>,
+    pub workspace: &'a UseRef<Workspace>,
+    pub wrap_width: Option<usize>,
+    // the tab the buffer belongs to
+    pub tab: usize,
+    // the words of the buffer highlighted as part of the language
+    pub keywords: &'a BTreeSet<String>,
+    // the blocks of the buffer that can be folded
+    pub fold_ranges: &'a [FoldRange],
+}
+
+type Action = Box<dyn Fn(&mut Context) + Send + Sync>;
+
+pub struct Command {
+    pub category: &'static str,
+    pub name: String,
+    // the first keybinding is the one shown in the command palette
+    pub keybindings: Vec<Keybinding>,
+    action: Action,
+}
+
+impl Command {
+    fn new(
+        category: &'static str,
+        name: impl Into<String>,
+        keybindings: Vec<Keybinding>,
+        action: impl Fn(&mut Context) + Send + Sync + 'static,
+    ) -> Self {
+        Self {
+            category,
+            name: name.into(),
+            keybindings,
+            action: Box::new(action),
+        }
+    }
+
+    /// The name shown in the command palette.
+    pub fn title(&self) -> String {
+        format!("{}: {}", self.category, self.name)
+    }
+
+    pub fn run(&self, ctx: &mut Context) {
+        (self.action)(ctx);
+        // a command that leaves the palette open is finished by picking an entry, which is not recorded
+        if self.category != "Macro" && ctx.workspace.read().palette.is_none() {
+            record(ctx.workspace, Step::Command(self.title()));
+        }
+    }
+}
+
+lazy_static! {
+    /// Every command of the editor, keys run the first command bound to them.
+    pub static ref COMMANDS: Vec<Command> = commands();
+}
+
+/// The command bound to a key press.
+pub fn command_for(k: &KeyboardData) -> Option<&'static Command> {
+    COMMANDS
+        .iter()
+        .find(|c| c.keybindings.iter().any(|b| b.matches(k)))
+}
+
+fn motion_commands(commands: &mut Vec<Command>) {
+    let motions = [
+        ("Up", Motion::Up, KeyCode::UpArrow, false),
+        ("Down", Motion::Down, KeyCode::DownArrow, false),
+        ("Left", Motion::Left, KeyCode::LeftArrow, false),
+        ("Right", Motion::Right, KeyCode::RightArrow, false),
+        ("Block Up", Motion::BlockUp, KeyCode::UpArrow, true),
+        ("Block Down", Motion::BlockDown, KeyCode::DownArrow, true),
+        ("Word Left", Motion::WordLeft, KeyCode::LeftArrow, true),
+        ("Word Right", Motion::WordRight, KeyCode::RightArrow, true),
+        ("Line Start", Motion::Home, KeyCode::Home, false),
+        ("Line End", Motion::End, KeyCode::End, false),
+    ];
+    for (name, motion, key, ctrl) in motions {
+        let binding = if ctrl {
+            Keybinding::new(key).ctrl()
+        } else {
+            Keybinding::new(key)
+        };
+        let variants = [
+            ("Move", binding, false, false),
+            ("Select", binding.shift(), true, false),
+            ("Add Cursor", binding.alt(), false, true),
+            ("Add Cursor and Select", binding.alt().shift(), true, true),
+        ];
+        for (prefix, binding, select, spawn) in variants {
+            commands.push(Command::new(
+                "Cursor",
+                format!("{} {}", prefix, name),
+                vec![binding],
+                move |ctx| {
+                    let layout = Layout::new(ctx.folds, ctx.wrap_width);
+                    ctx.cursors
+                        .move_by(motion, select, spawn, &ctx.buffer.rope, &layout)
+                },
+            ));
+        }
+    }
+}
+
+fn edit_commands(commands: &mut Vec<Command>) {
+    commands.push(Command::new(
+        "Edit",
+        "Undo",
+        vec![Keybinding::new(KeyCode::Z).ctrl()],
+        |ctx| {
+            let old = ctx.buffer.rope.clone();
+            if ctx.buffer.undo() {
+                ctx.cursors.update(&old, &ctx.buffer.rope);
+            }
+        },
+    ));
+    commands.push(Command::new(
+        "Edit",
+        "Redo",
+        vec![
+            Keybinding::new(KeyCode::Z).ctrl().shift(),
+            Keybinding::new(KeyCode::Y).ctrl(),
+        ],
+        |ctx| {
+            let old = ctx.buffer.rope.clone();
+            if ctx.buffer.redo() {
+                ctx.cursors.update(&old, &ctx.buffer.rope);
+            }
+        },
+    ));
+    commands.push(Command::new(
+        "Edit",
+        "Delete Left",
+        vec![Keybinding::new(KeyCode::Backspace)],
+        |ctx| ctx.cursors.backspace(&mut ctx.buffer.rope),
+    ));
+    commands.push(Command::new(
+        "Edit",
+        "New Line",
+        vec![Keybinding::new(KeyCode::Enter)],
+        |ctx| {
+            let line_ending = ctx.buffer.line_ending.as_str();
+            ctx.cursors.newline(line_ending, &mut ctx.buffer.rope)
+        },
+    ));
+    commands.push(Command::new(
+        "Edit",
+        "Indent",
+        vec![Keybinding::new(KeyCode::Tab)],
+        |ctx| {
+            let indent = ctx.buffer.indent.text();
+            ctx.cursors.insert_text(&indent, &mut ctx.buffer.rope)
+        },
+    ));
+    for (name, block, binding) in [
+        (
+            "Toggle Line Comment",
+            false,
+            Keybinding::new(KeyCode::ForwardSlash).ctrl(),
+        ),
+        (
+            "Toggle Block Comment",
+            true,
+            Keybinding::new(KeyCode::ForwardSlash).ctrl().shift(),
+        ),
+    ] {
+        commands.push(Command::new("Edit", name, vec![binding], move |ctx| {
+            ctx.buffer
+                .comment_tokens()
+                .toggle(ctx.cursors, &mut ctx.buffer.rope, block)
+        }));
+    }
+}
+
+fn fold_commands(commands: &mut Vec<Command>) {
+    commands.push(Command::new(
+        "Fold",
+        "Fold",
+        vec![Keybinding::new(KeyCode::OpenBracket).ctrl().shift()],
+        |ctx| {
+            let row = ctx.cursors.primary().caret().row();
+            ctx.folds.fold(ctx.fold_ranges, row)
+        },
+    ));
+    commands.push(Command::new(
+        "Fold",
+        "Unfold",
+        vec![Keybinding::new(KeyCode::CloseBraket).ctrl().shift()],
+        |ctx| {
+            let row = ctx.cursors.primary().caret().row();
+            ctx.folds.unfold(row)
+        },
+    ));
+    commands.push(Command::new(
+        "Fold",
+        "Fold All",
+        vec![Keybinding::new(KeyCode::OpenBracket).ctrl().alt()],
+        |ctx| ctx.folds.fold_all(ctx.fold_ranges),
+    ));
+    commands.push(Command::new(
+        "Fold",
+        "Unfold All",
+        vec![Keybinding::new(KeyCode::CloseBraket).ctrl().alt()],
+        |ctx| ctx.folds.unfold_all(),
+    ));
+    let levels = [
+        KeyCode::Num1,
+        KeyCode::Num2,
+        KeyCode::Num3,
+        KeyCode::Num4,
+        KeyCode::Num5,
+        KeyCode::Num6,
+        KeyCode::Num7,
+        KeyCode::Num8,
+        KeyCode::Num9,
+    ];
+    for (i, key) in levels.into_iter().enumerate() {
+        let level = i + 1;
+        commands.push(Command::new(
+            "Fold",
+            format!("Fold Level {}", level),
+            vec![Keybinding::new(key).ctrl().alt()],
+            move |ctx| ctx.folds.fold_to_level(ctx.fold_ranges, level),
+        ));
+    }
+}
+
+fn view_commands(commands: &mut Vec<Command>) {
+    commands.push(Command::new(
+        "View",
+        "Command Palette",
+        vec![Keybinding::new(KeyCode::P).ctrl().shift()],
+        |ctx| toggle_palette(&mut ctx.workspace.write(), PaletteMode::Commands),
+    ));
+    for (name, direction, binding) in [
+        (
+            "Split Right",
+            Direction::Horizontal,
+            Keybinding::new(KeyCode::BackSlash).ctrl(),
+        ),
+        (
+            "Split Down",
+            Direction::Vertical,
+            Keybinding::new(KeyCode::BackSlash).ctrl().shift(),
+        ),
+    ] {
+        commands.push(Command::new("View", name, vec![binding], move |ctx| {
+            ctx.workspace.write().split(direction)
+        }));
+    }
+    commands.push(Command::new(
+        "View",
+        "Close Pane",
+        vec![Keybinding::new(KeyCode::W).ctrl().alt()],
+        |ctx| ctx.workspace.write().close_pane(),
+    ));
+    // every combination of the arrows with ctrl, alt and shift moves the cursors, so panes use the keys of vim
+    for (name, key, dx, dy) in [
+        ("Focus Left Pane", KeyCode::H, -1.0, 0.0),
+        ("Focus Right Pane", KeyCode::L, 1.0, 0.0),
+        ("Focus Upper Pane", KeyCode::K, 0.0, -1.0),
+        ("Focus Lower Pane", KeyCode::J, 0.0, 1.0),
+    ] {
+        commands.push(Command::new(
+            "View",
+            name,
+            vec![Keybinding::new(key).ctrl().alt()],
+            move |ctx| ctx.workspace.write().focus_direction(dx, dy),
+        ));
+    }
+    commands.push(Command::new(
+        "View",
+        "Toggle File Explorer",
+        vec![Keybinding::new(KeyCode::B).ctrl()],
+        |ctx| {
+            if let Some(explorer) = &mut ctx.workspace.write().explorer {
+                explorer.open = !explorer.open;
+                explorer.focused = explorer.open;
+            }
+        },
+    ));
+    commands.push(Command::new(
+        "View",
+        "Focus File Explorer",
+        vec![Keybinding::new(KeyCode::E).ctrl().shift()],
+        |ctx| {
+            if let Some(explorer) = &mut ctx.workspace.write().explorer {
+                explorer.open = true;
+                explorer.focused = true;
+            }
+        },
+    ));
+    commands.push(Command::new(
+        "View",
+        "Toggle Problems",
+        vec![Keybinding::new(KeyCode::M).ctrl().shift()],
+        |ctx| {
+            let mut workspace = ctx.workspace.write();
+            workspace.problems_panel = !workspace.problems_panel;
+        },
+    ));
+    commands.push(Command::new(
+        "View",
+        "Toggle Whitespace",
+        Vec::new(),
+        |ctx| {
+            let mut settings = ctx.settings.write();
+            settings.show_whitespace = !settings.show_whitespace;
+        },
+    ));
+    commands.push(Command::new(
+        "View",
+        "Toggle Indent Guides",
+        Vec::new(),
+        |ctx| {
+            let mut settings = ctx.settings.write();
+            settings.indent_guides = !settings.indent_guides;
+        },
+    ));
+    commands.push(Command::new(
+        "View",
+        "Toggle Relative Line Numbers",
+        Vec::new(),
+        |ctx| {
+            let mut settings = ctx.settings.write();
+            settings.line_numbers = match settings.line_numbers {
+                LineNumbers::Absolute => LineNumbers::Relative,
+                LineNumbers::Relative => LineNumbers::Absolute,
+            };
+        },
+    ));
+    for wrap in Wrap::options() {
+        commands.push(Command::new(
+            "View",
+            wrap.to_string(),
+            Vec::new(),
+            move |ctx| ctx.buffer.wrap = wrap,
+        ));
+    }
+}
+
+fn file_commands(commands: &mut Vec<Command>) {
+    commands.push(Command::new(
+        "File",
+        "Go to File",
+        vec![Keybinding::new(KeyCode::P).ctrl()],
+        |ctx| toggle_palette(&mut ctx.workspace.write(), PaletteMode::Files),
+    ));
+    commands.push(Command::new(
+        "File",
+        "Find in Files",
+        vec![Keybinding::new(KeyCode::F).ctrl().shift()],
+        |ctx| {
+            let mut workspace = ctx.workspace.write();
+            workspace.search_panel = !workspace.search_panel;
+        },
+    ));
+    commands.push(Command::new(
+        "File",
+        "New File",
+        vec![Keybinding::new(KeyCode::N).ctrl()],
+        |ctx| {
+            let mut workspace = ctx.workspace.write();
+            let name = format!("Untitled-{}", workspace.tabs.tabs.len() + 1);
+            workspace.open(&name, "", "txt");
+        },
+    ));
+    commands.push(Command::new(
+        "File",
+        "Save",
+        vec![Keybinding::new(KeyCode::S).ctrl()],
+        |ctx| {
+            if let Err(e) = save_buffer(ctx) {
+                ctx.workspace.write().message = Some(e);
+            }
+        },
+    ));
+    commands.push(Command::new(
+        "File",
+        "Close Tab",
+        vec![Keybinding::new(KeyCode::W).ctrl()],
+        |ctx| {
+            let mut workspace = ctx.workspace.write();
+            let active = workspace.tabs.active;
+            workspace.close_tab(active);
+        },
+    ));
+    commands.push(Command::new(
+        "File",
+        "Next Tab",
+        vec![
+            Keybinding::new(KeyCode::Tab).ctrl(),
+            Keybinding::new(KeyCode::PageDown).ctrl(),
+        ],
+        |ctx| ctx.workspace.write().cycle_tab(1),
+    ));
+    commands.push(Command::new(
+        "File",
+        "Previous Tab",
+        vec![
+            Keybinding::new(KeyCode::Tab).ctrl().shift(),
+            Keybinding::new(KeyCode::PageUp).ctrl(),
+        ],
+        |ctx| ctx.workspace.write().cycle_tab(-1),
+    ));
+    commands.push(Command::new("File", "Toggle Pin Tab", Vec::new(), |ctx| {
+        let mut workspace = ctx.workspace.write();
+        let active = workspace.tabs.active;
+        workspace.tabs.toggle_pin(active);
+    }));
+    for indent in Indent::OPTIONS {
+        commands.push(Command::new(
+            "File",
+            format!("Indent Using {}", indent),
+            Vec::new(),
+            move |ctx| ctx.buffer.indent = indent,
+        ));
+    }
+    for line_ending in LineEnding::OPTIONS {
+        commands.push(Command::new(
+            "File",
+            format!("Change Line Endings to {}", line_ending),
+            Vec::new(),
+            move |ctx| {
+                if let Err(e) = ctx.buffer.set_line_ending(line_ending) {
+                    ctx.workspace.write().message = Some(e);
+                }
+            },
+        ));
+    }
+    for encoding in Encoding::OPTIONS {
+        commands.push(Command::new(
+            "File",
+            format!("Reopen with Encoding {}", encoding),
+            Vec::new(),
+            move |ctx| {
+                if let Err(e) = reopen_with_encoding(ctx, encoding) {
+                    ctx.workspace.write().message = Some(e);
+                }
+            },
+        ));
+        commands.push(Command::new(
+            "File",
+            format!("Save with Encoding {}", encoding),
+            Vec::new(),
+            move |ctx| {
+                if let Err(e) = save_with_encoding(ctx, encoding) {
+                    ctx.workspace.write().message = Some(e);
+                }
+            },
+        ));
+    }
+}
+
+// the file of the tab of the buffer
+fn buffer_path(ctx: &Context) -> Result<PathBuf, String> {
+    ctx.workspace
+        .read()
+        .tabs
+        .get(ctx.tab)
+        .and_then(|t| t.path.clone())
+        .ok_or_else(|| "The buffer is not saved to a file".to_string())
+}
+
+/// Writes the buffer to its file in the encoding and with the line endings it was opened with.
+fn save_buffer(ctx: &mut Context) -> Result<(), String> {
+    if ctx.buffer.read_only {
+        return Err(format!(
+            "The file has sequences that are not valid {} and can not be saved",
+            ctx.buffer.encoding
+        ));
+    }
+    let path = buffer_path(ctx)?;
+    // the file is not written if it would lose chars
+    let bytes = ctx
+        .buffer
+        .encoding
+        .encode(&ctx.buffer.rope.to_string())
+        .map_err(|c| {
+            format!(
+                "{:?} can not be written in {}, save with encoding {} to keep it",
+                c,
+                ctx.buffer.encoding,
+                Encoding::Utf8
+            )
+        })?;
+    std::fs::write(&path, bytes)
+        .map_err(|e| format!("Could not save {}: {}", path.display(), e))?;
+    ctx.buffer.saved = ctx.buffer.rope.clone();
+    // the file is not reloaded for a change the editor made
+    ctx.workspace
+        .write()
+        .disk_times
+        .insert(ctx.tab, modified(&path));
+    Ok(())
+}
+
+/// Writes the buffer to its file in another encoding, which it keeps for later saves.
+fn save_with_encoding(ctx: &mut Context, encoding: Encoding) -> Result<(), String> {
+    let old = ctx.buffer.encoding;
+    ctx.buffer.encoding = encoding;
+    if let Err(e) = save_buffer(ctx) {
+        ctx.buffer.encoding = old;
+        return Err(e);
+    }
+    let read_only = ctx.buffer.read_only;
+    ctx.workspace
+        .write()
+        .tabs
+        .set_encoding(ctx.tab, encoding, read_only);
+    Ok(())
+}
+
+/// Reads the file of the buffer again in another encoding.
+fn reopen_with_encoding(ctx: &mut Context, encoding: Encoding) -> Result<(), String> {
+    let path = buffer_path(ctx)?;
+    if ctx.buffer.is_dirty() {
+        return Err("Save or undo the changes before reopening the file".to_string());
+    }
+    let bytes = std::fs::read(&path).map_err(|e| e.to_string())?;
+    let decoded = encoding.decode(&bytes);
+    let old = ctx.buffer.rope.clone();
+    ctx.buffer.reload(&decoded.text);
+    ctx.cursors.update(&old, &ctx.buffer.rope);
+    ctx.buffer.encoding = encoding;
+    ctx.buffer.read_only = !decoded.valid;
+    ctx.workspace
+        .write()
+        .tabs
+        .set_encoding(ctx.tab, encoding, !decoded.valid);
+    if decoded.valid {
+        Ok(())
+    } else {
+        Err(format!(
+            "{} has sequences that are not valid {} and was opened read-only",
+            path.display(),
+            encoding
+        ))
+    }
+}
+
+fn theme_commands(commands: &mut Vec<Command>) {
+    for name in TS.themes.keys() {
+        let theme = name.clone();
+        commands.push(Command::new(
+            "Theme",
+            name.clone(),
+            Vec::new(),
+            move |ctx| {
+                set_theme(&theme);
+                // the theme is global, so every view has to render again
+                ctx.workspace.write();
+            },
+        ));
+    }
+}
+
+fn language_commands(commands: &mut Vec<Command>) {
+    let mut names: Vec<_> = PS
+        .syntaxes()
+        .iter()
+        .filter(|s| !s.hidden)
+        .map(|s| s.name.clone())
+        .collect();
+    names.sort_unstable();
+    for name in names {
+        commands.push(Command::new(
+            "Language",
+            name.clone(),
+            Vec::new(),
+            move |ctx| {
+                if let Some(syntax) = PS.find_syntax_by_name(&name) {
+                    ctx.buffer.syntax = syntax;
+                }
+            },
+        ));
+    }
+}
+
+/// The language server of the buffer, its document and the position of the primary cursor.
+pub fn document(ctx: &Context) -> Result<(LspClient, Url, Position), String> {
+    let workspace = ctx.workspace.read();
+    let syntax = &ctx.buffer.syntax.name;
+    let client = workspace
+        .language_servers
+        .get(syntax)
+        .ok_or_else(|| format!("No language server is running for {}", syntax))?;
+    let path = workspace
+        .tabs
+        .get(ctx.tab)
+        .and_then(|t| t.path.clone())
+        .ok_or_else(|| "The buffer is not saved to a file".to_string())?;
+    let uri = Url::from_file_path(&path).map_err(|_| format!("Invalid path {}", path.display()))?;
+    let rope = &ctx.buffer.rope;
+    let position = to_position(rope, ctx.cursors.primary().caret().idx(rope));
+    Ok((client, uri, position))
+}
+
+/// Where a location is in its file, the file is read to find the chars of the range.
+fn location_cursor(path: &Path, location: &Location) -> Cursor {
+    let rope = std::fs::read_to_string(path)
+        .map(|text| Rope::from_str(&text))
+        .unwrap_or_default();
+    let start = Pos::from_idx(to_idx(&rope, location.range.start), &rope);
+    let end = Pos::from_idx(to_idx(&rope, location.range.end), &rope);
+    Cursor::new(start, end)
+}
+
+/// Opens a location, or lists them in the palette if there are several.
+fn show_locations(workspace: &mut Workspace, locations: Vec<Location>) -> Result<(), String> {
+    let root = workspace.project.as_ref().map(|p| p.root.clone());
+    let locations: Vec<_> = locations
+        .into_iter()
+        .filter_map(|location| {
+            let path = location.uri.to_file_path().ok()?;
+            let cursor = location_cursor(&path, &location);
+            let shown = root
+                .as_ref()
+                .and_then(|root| path.strip_prefix(root).ok())
+                .unwrap_or(&path);
+            let text = format!(
+                "{}:{}:{}",
+                shown.display(),
+                cursor.first().row() + 1,
+                location.range.start.character + 1
+            );
+            Some((text, path, cursor))
+        })
+        .collect();
+    match locations.as_slice() {
+        [] => return Err("Nothing found".to_string()),
+        [(_, path, cursor)] => {
+            if let Some(tab) = workspace.open_path(path) {
+                workspace.goto = Some((tab, cursor.clone()));
+            }
+        }
+        _ => {
+            workspace.locations = locations;
+            workspace.palette = Some(Palette::new(PaletteMode::Locations));
+        }
+    }
+    Ok(())
+}
+
+/// What a code command does with the answer of the language server.
+#[derive(Debug, Clone)]
+pub enum Answer {
+    Hover(Pending<Option<String>>),
+    Locations(Pending<Vec<Location>>),
+    Rename(Pending<HashMap<Url, Vec<TextEdit>>>),
+    Formatting(Pending<Vec<TextEdit>>),
+}
+
+/// A code command waiting for the answer of the language server, the editor keeps running until it is received.
+#[derive(Debug, Clone)]
+pub struct CodeRequest {
+    // the tab the command ran in and its text when the request was sent
+    tab: usize,
+    rope: Rope,
+    answer: Answer,
+}
+
+impl CodeRequest {
+    pub fn is_received(&self) -> bool {
+        match &self.answer {
+            Answer::Hover(pending) => pending.is_received(),
+            Answer::Locations(pending) => pending.is_received(),
+            Answer::Rename(pending) => pending.is_received(),
+            Answer::Formatting(pending) => pending.is_received(),
+        }
+    }
+}
+
+/// Shows the answer to the code request once it is received, errors are shown instead.
+pub fn receive_code_request(workspace: &mut Workspace) {
+    let request = match workspace.code_request.take() {
+        Some(request) if request.is_received() => request,
+        request => {
+            workspace.code_request = request;
+            return;
+        }
+    };
+    // edits are only applied to the text they were made for
+    let changed = workspace.buffers.get(&request.tab) != Some(&request.rope);
+    let stale = || "The text changed before the language server answered".to_string();
+    let shown = match request.answer {
+        Answer::Hover(pending) => pending.take().unwrap().map(|hover| {
+            workspace.message = Some(hover.unwrap_or_else(|| "No information".to_string()));
+        }),
+        Answer::Locations(pending) => pending
+            .take()
+            .unwrap()
+            .and_then(|locations| show_locations(workspace, locations)),
+        Answer::Rename(pending) => pending.take().unwrap().and_then(|changes| {
+            if changed {
+                return Err(stale());
+            }
+            rename_files(workspace, changes);
+            Ok(())
+        }),
+        Answer::Formatting(pending) => pending.take().unwrap().and_then(|edits| {
+            if changed {
+                return Err(stale());
+            }
+            workspace.edits.push((request.tab, Edit::Lsp(edits)));
+            Ok(())
+        }),
+    };
+    if let Err(e) = shown {
+        workspace.message = Some(e);
+    }
+}
+
+fn code_command(
+    commands: &mut Vec<Command>,
+    name: &str,
+    keybinding: Keybinding,
+    // returns what to do with the answer of the server, None if nothing is asked from it
+    action: impl Fn(&mut Context, LspClient, Url, Position) -> Result<Option<Answer>, String>
+        + Send
+        + Sync
+        + 'static,
+) {
+    commands.push(Command::new("Code", name, vec![keybinding], move |ctx| {
+        // errors are shown instead of the result
+        match document(ctx).and_then(|(client, uri, position)| action(ctx, client, uri, position)) {
+            Ok(Some(answer)) => ask(ctx, answer),
+            Ok(None) => {}
+            Err(e) => ctx.workspace.write().message = Some(e),
+        }
+    }));
+}
+
+// waits for the answer in the background, a request sent earlier is not shown anymore
+fn ask(ctx: &mut Context, answer: Answer) {
+    ctx.workspace.write().code_request = Some(CodeRequest {
+        tab: ctx.tab,
+        rope: ctx.buffer.rope.clone(),
+        answer,
+    });
+}
+
+fn code_commands(commands: &mut Vec<Command>) {
+    commands.push(Command::new(
+        "Code",
+        "Go to Next Problem",
+        vec![Keybinding::new(KeyCode::F8)],
+        |ctx| goto_problem(ctx, true),
+    ));
+    commands.push(Command::new(
+        "Code",
+        "Go to Previous Problem",
+        vec![Keybinding::new(KeyCode::F8).shift()],
+        |ctx| goto_problem(ctx, false),
+    ));
+    code_command(
+        commands,
+        "Show Hover",
+        Keybinding::new(KeyCode::I).ctrl(),
+        |_, client, uri, position| {
+            Ok(Some(Answer::Hover(Pending::start(move || {
+                client.hover(&uri, position)
+            }))))
+        },
+    );
+    code_command(
+        commands,
+        "Go to Definition",
+        Keybinding::new(KeyCode::F12),
+        |_, client, uri, position| {
+            Ok(Some(Answer::Locations(Pending::start(move || {
+                client.definition(&uri, position)
+            }))))
+        },
+    );
+    code_command(
+        commands,
+        "Find References",
+        Keybinding::new(KeyCode::F12).shift(),
+        |_, client, uri, position| {
+            Ok(Some(Answer::Locations(Pending::start(move || {
+                client.references(&uri, position)
+            }))))
+        },
+    );
+    code_command(
+        commands,
+        "Rename Symbol",
+        Keybinding::new(KeyCode::F2),
+        |ctx, _, _, _| {
+            let rope = &ctx.buffer.rope;
+            let word = word_at(ctx.cursors.primary().caret(), rope)
+                .map(|(start, end)| rope.slice(start..end).to_string())
+                .ok_or_else(|| "No symbol under the cursor".to_string())?;
+            let mut palette = Palette::new(PaletteMode::Rename);
+            palette.query = word;
+            ctx.workspace.write().palette = Some(palette);
+            Ok(None)
+        },
+    );
+    code_command(
+        commands,
+        "Format Document",
+        Keybinding::new(KeyCode::I).ctrl().shift(),
+        |ctx, client, uri, _| {
+            let indent = ctx.buffer.indent;
+            Ok(Some(Answer::Formatting(Pending::start(move || {
+                client.formatting(&uri, indent)
+            }))))
+        },
+    );
+    commands.push(Command::new(
+        "Code",
+        "Trigger Completion",
+        vec![Keybinding::new(KeyCode::Space).ctrl()],
+        |ctx| open_completion(ctx, true),
+    ));
+}
+
+fn macro_commands(commands: &mut Vec<Command>) {
+    commands.push(Command::new(
+        "Macro",
+        "Toggle Recording",
+        vec![Keybinding::new(KeyCode::R).ctrl().shift()],
+        |ctx| toggle_recording(&mut ctx.workspace.write()),
+    ));
+    commands.push(Command::new(
+        "Macro",
+        "Replay",
+        vec![Keybinding::new(KeyCode::R).ctrl().alt()],
+        |ctx| play(ctx, 1),
+    ));
+    commands.push(Command::new("Macro", "Replay N Times", Vec::new(), |ctx| {
+        ctx.workspace.write().palette = Some(Palette::new(PaletteMode::Repeat))
+    }));
+    commands.push(Command::new(
+        "Macro",
+        "Replay on Each Selected Line",
+        Vec::new(),
+        play_on_lines,
+    ));
+    commands.push(Command::new(
+        "Macro",
+        "Save Last Macro",
+        Vec::new(),
+        |ctx| ctx.workspace.write().palette = Some(Palette::new(PaletteMode::MacroName)),
+    ));
+    commands.push(Command::new(
+        "Macro",
+        "Run Saved Macro",
+        Vec::new(),
+        |ctx| ctx.workspace.write().palette = Some(Palette::new(PaletteMode::Macros)),
+    ));
+}
+
+/// Moves the cursor to the next or previous diagnostic of the buffer and shows its message.
+fn goto_problem(ctx: &mut Context, next: bool) {
+    let caret = ctx.cursors.primary().caret().clone();
+    let found = ctx
+        .workspace
+        .read()
+        .diagnostics
+        .get(&ctx.tab)
+        .and_then(|d| {
+            if next {
+                d.next(&caret)
+            } else {
+                d.previous(&caret)
+            }
+        })
+        .cloned();
+    ctx.workspace.write().message = Some(match found {
+        Some(diagnostic) => {
+            *ctx.cursors = Cursors::new(vec![Cursor::from_start(diagnostic.start.clone())]);
+            diagnostic.message
+        }
+        None => "No problems".to_string(),
+    });
+}
+
+/// Asks the language server to rename the symbol under the primary cursor.
+fn rename_symbol(ctx: &mut Context, new_name: &str) -> Result<(), String> {
+    let (client, uri, position) = document(ctx)?;
+    let new_name = new_name.to_string();
+    let pending = Pending::start(move || client.rename(&uri, position, &new_name));
+    ask(ctx, Answer::Rename(pending));
+    Ok(())
+}
+
+/// Applies the edits of a rename to every file the language server changed, opening the files that are not open.
+fn rename_files(workspace: &mut Workspace, changes: HashMap<Url, Vec<TextEdit>>) {
+    for (uri, edits) in changes {
+        let path = match uri.to_file_path() {
+            Ok(path) => path,
+            Err(_) => continue,
+        };
+        let tab = workspace
+            .tabs
+            .tabs
+            .iter()
+            .find(|t| t.path.as_deref() == Some(&path))
+            .map(|t| t.id)
+            .or_else(|| workspace.open_path(&path));
+        if let Some(tab) = tab {
+            workspace.edits.push((tab, Edit::Lsp(edits)));
+        }
+    }
+}
+
+fn commands() -> Vec<Command> {
+    let mut commands = Vec::new();
+    motion_commands(&mut commands);
+    edit_commands(&mut commands);
+    fold_commands(&mut commands);
+    view_commands(&mut commands);
+    file_commands(&mut commands);
+    theme_commands(&mut commands);
+    language_commands(&mut commands);
+    code_commands(&mut commands);
+    macro_commands(&mut commands);
+    commands
+}
+
+/// What the palette searches.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PaletteMode {
+    Commands,
+    Files,
+    // the locations found by a language server
+    Locations,
+    // the query is the new name of the symbol under the cursor
+    Rename,
+    // the query is the number of times the last macro is played
+    Repeat,
+    // the query is the name the last macro is saved as
+    MacroName,
+    // the saved macros
+    Macros,
+}
+
+/// The state of the command palette.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Palette {
+    pub mode: PaletteMode,
+    pub query: String,
+    pub selected: usize,
+}
+
+impl Palette {
+    pub fn new(mode: PaletteMode) -> Self {
+        Self {
+            mode,
+            query: String::new(),
+            selected: 0,
+        }
+    }
+}
+
+/// Opens the palette, or closes it if it is already open in the same mode.
+fn toggle_palette(workspace: &mut Workspace, mode: PaletteMode) {
+    workspace.palette = match &workspace.palette {
+        Some(palette) if palette.mode == mode => None,
+        _ => Some(Palette::new(mode)),
+    };
+}
+
+/// An entry of the palette.
+pub struct PaletteItem {
+    pub text: String,
+    // the chars of the text that matched the query
+    pub indexes: Vec<usize>,
+    // shown after the text, like the keybinding of a command
+    pub detail: String,
+}
+
+/// The commands matching a query, recently used commands first.
+pub fn palette_matches(query: &str, recent: &[String]) -> Vec<(&'static Command, FuzzyMatch)> {
+    let mut matches: Vec<_> = COMMANDS
+        .iter()
+        .filter_map(|c| fuzzy_match(query, &c.title()).map(|m| (c, m)))
+        .collect();
+    matches.sort_by_key(|(c, m)| {
+        let title = c.title();
+        let recency = recent.iter().position(|r| *r == title);
+        (recency.unwrap_or(usize::MAX), -m.score)
+    });
+    matches
+}
+
+/// The entries matching the query of the palette.
+pub fn palette_items(palette: &Palette, workspace: &Workspace) -> Vec<PaletteItem> {
+    match palette.mode {
+        PaletteMode::Commands => palette_matches(&palette.query, &workspace.recent_commands)
+            .into_iter()
+            .map(|(command, m)| PaletteItem {
+                text: command.title(),
+                indexes: m.indexes,
+                detail: command
+                    .keybindings
+                    .first()
+                    .map(|b| b.to_string())
+                    .unwrap_or_default(),
+            })
+            .collect(),
+        PaletteMode::Files => {
+            let (query, _) = parse_query(&palette.query);
+            let files = workspace
+                .project
+                .as_ref()
+                .map(|project| project.search(query, MAX_RESULTS))
+                .unwrap_or_default();
+            files
+                .into_iter()
+                .map(|(file, m)| PaletteItem {
+                    text: file,
+                    indexes: m.indexes,
+                    detail: String::new(),
+                })
+                .collect()
+        }
+        PaletteMode::Locations => workspace
+            .locations
+            .iter()
+            .filter_map(|(text, _, _)| {
+                fuzzy_match(&palette.query, text).map(|m| PaletteItem {
+                    text: text.clone(),
+                    indexes: m.indexes,
+                    detail: String::new(),
+                })
+            })
+            .collect(),
+        PaletteMode::Rename => vec![PaletteItem {
+            text: palette.query.clone(),
+            indexes: Vec::new(),
+            detail: "Rename Symbol".to_string(),
+        }],
+        PaletteMode::Repeat => vec![PaletteItem {
+            text: palette.query.clone(),
+            indexes: Vec::new(),
+            detail: "Replay Macro".to_string(),
+        }],
+        PaletteMode::MacroName => vec![PaletteItem {
+            text: palette.query.clone(),
+            indexes: Vec::new(),
+            detail: "Save Macro".to_string(),
+        }],
+        PaletteMode::Macros => saved_macros()
+            .into_iter()
+            .filter_map(|(name, steps)| {
+                fuzzy_match(&palette.query, &name).map(|m| PaletteItem {
+                    text: name,
+                    indexes: m.indexes,
+                    detail: format!("{} steps", steps.len()),
+                })
+            })
+            .collect(),
+    }
+}
+
+fn run_selected(palette: &Palette, ctx: &mut Context) {
+    let item = palette_items(palette, &ctx.workspace.read())
+        .into_iter()
+        .nth(palette.selected);
+    let item = match item {
+        Some(item) => item,
+        None => return,
+    };
+    match palette.mode {
+        PaletteMode::Commands => {
+            if let Some(command) = COMMANDS.iter().find(|c| c.title() == item.text) {
+                ctx.workspace.write().record_command(&item.text);
+                command.run(ctx);
+            }
+        }
+        PaletteMode::Files => {
+            let project = ctx.workspace.read().project.clone();
+            let path = match project {
+                Some(project) => project.path(&item.text),
+                None => return,
+            };
+            let mut workspace = ctx.workspace.write();
+            if let Some(tab) = workspace.open_path(&path) {
+                workspace.goto = parse_query(&palette.query)
+                    .1
+                    .map(|row| (tab, Cursor::from_start(Pos::new(0, row))));
+            }
+        }
+        PaletteMode::Locations => {
+            let mut workspace = ctx.workspace.write();
+            let location = workspace.locations.iter().find(|(t, _, _)| *t == item.text);
+            if let Some((_, path, cursor)) = location.cloned() {
+                if let Some(tab) = workspace.open_path(&path) {
+                    workspace.goto = Some((tab, cursor));
+                }
+            }
+        }
+        PaletteMode::Rename => {
+            if let Err(e) = rename_symbol(ctx, &item.text) {
+                ctx.workspace.write().message = Some(e);
+            }
+        }
+        PaletteMode::Repeat => match item.text.trim().parse() {
+            Ok(times) => play(ctx, times),
+            Err(_) => {
+                ctx.workspace.write().message = Some(format!("{} is not a number", item.text))
+            }
+        },
+        PaletteMode::MacroName => {
+            let mut workspace = ctx.workspace.write();
+            let message = match save_macro(&mut workspace, item.text.trim()) {
+                Ok(()) => format!("Saved the macro as {}", item.text.trim()),
+                Err(e) => e,
+            };
+            workspace.message = Some(message);
+        }
+        PaletteMode::Macros => {
+            if let Some(steps) = saved_macros().remove(&item.text) {
+                ctx.workspace.write().last_macro = steps;
+                play(ctx, 1);
+            }
+        }
+    }
+}
+
+/// Handles a key press while the palette is open.
+pub fn palette_input(k: &KeyboardData, ctx: &mut Context) {
+    let mut workspace = ctx.workspace.write();
+    let mut palette = match workspace.palette.clone() {
+        Some(palette) => palette,
+        None => return,
+    };
+    match k.key_code {
+        KeyCode::Escape => {
+            workspace.palette = None;
+            return;
+        }
+        // the keybindings that open the palette switch its mode or close it
+        KeyCode::P if k.ctrl_key => {
+            drop(workspace);
+            if let Some(command) = command_for(k) {
+                command.run(ctx);
+            }
+            return;
+        }
+        KeyCode::Enter => {
+            workspace.palette = None;
+            drop(workspace);
+            run_selected(&palette, ctx);
+            return;
+        }
+        KeyCode::UpArrow => palette.selected = palette.selected.saturating_sub(1),
+        KeyCode::DownArrow => {
+            let len = palette_items(&palette, &workspace).len();
+            palette.selected = (palette.selected + 1).min(len.saturating_sub(1));
+        }
+        KeyCode::Backspace => {
+            palette.query.pop();
+            palette.selected = 0;
+        }
+        _ if k.key.chars().count() == 1 && !k.ctrl_key && !k.alt_key => {
+            palette.query.push_str(&k.key);
+            palette.selected = 0;
+        }
+        _ => {}
+    }
+    workspace.palette = Some(palette);
+}
+
+#[test]
+fn keybindings() {
+    let binding = Keybinding::new(KeyCode::P).ctrl().shift();
+    assert_eq!(binding.to_string(), "Ctrl-Shift-P");
+    assert_eq!(
+        Keybinding::new(KeyCode::Num3).ctrl().alt().to_string(),
+        "Ctrl-Alt-3"
+    );
+
+    // every keybinding runs a single command
+    let mut bindings: Vec<_> = COMMANDS
+        .iter()
+        .flat_map(|c| c.keybindings.clone())
+        .collect();
+    let len = bindings.len();
+    bindings.sort_by_key(|b| b.to_string());
+    bindings.dedup();
+    assert_eq!(bindings.len(), len);
+
+    // panes are focused without taking the shortcuts of the cursors
+    let title = |binding: Keybinding| {
+        COMMANDS
+            .iter()
+            .find(|c| c.keybindings.contains(&binding))
+            .map(|c| c.title())
+    };
+    assert_eq!(
+        title(Keybinding::new(KeyCode::LeftArrow).ctrl().alt()),
+        Some("Cursor: Add Cursor Word Left".to_string())
+    );
+    assert_eq!(
+        title(Keybinding::new(KeyCode::UpArrow).ctrl().alt().shift()),
+        Some("Cursor: Add Cursor and Select Block Up".to_string())
+    );
+    assert_eq!(
+        title(Keybinding::new(KeyCode::H).ctrl().alt()),
+        Some("View: Focus Left Pane".to_string())
+    );
+}
+
+#[test]
+fn recent_commands_rank_first() {
+    let matches = palette_matches("split", &[]);
+    assert_eq!(matches[0].0.title(), "View: Split Right");
+
+    let recent = vec!["View: Split Down".to_string()];
+    let matches = palette_matches("split", &recent);
+    assert_eq!(matches[0].0.title(), "View: Split Down");
+    assert_eq!(matches[1].0.title(), "View: Split Right");
+
+    let matches = palette_matches("", &recent);
+    assert_eq!(matches.len(), COMMANDS.len());
+    assert_eq!(matches[0].0.title(), "View: Split Down");
+}
+
+#[test]
+fn formatting_moves_cursors() {
+    use lsp_types::Range;
+
+    let text = "fn main() {\n    a();\n\n\n}\n\n\n";
+    let mut workspace = Workspace::default();
+    let tab = workspace.open("main.rs", text, "rs");
+    workspace.buffers.insert(tab, Rope::from_str(text));
+    // the blank lines below the first cursor and at the end of the file are removed
+    let edits = vec![
+        TextEdit::new(
+            Range::new(Position::new(2, 0), Position::new(4, 0)),
+            String::new(),
+        ),
+        TextEdit::new(
+            Range::new(Position::new(5, 0), Position::new(7, 0)),
+            String::new(),
+        ),
+    ];
+    let request = |edits: Vec<TextEdit>| {
+        let pending = Pending::start(move || Ok(edits));
+        while !pending.is_received() {
+            std::thread::yield_now();
+        }
+        Some(CodeRequest {
+            tab,
+            rope: Rope::from_str(text),
+            answer: Answer::Formatting(pending),
+        })
+    };
+
+    // the answer is applied as an edit of the tab, the cursors of views follow it
+    workspace.code_request = request(edits.clone());
+    receive_code_request(&mut workspace);
+    assert!(workspace.code_request.is_none());
+    let (_, edit) = workspace.edits.pop().unwrap();
+    let old = Rope::from_str(text);
+    let new = Rope::from_str(&edit.apply(text).unwrap());
+    assert_eq!(new.to_string(), "fn main() {\n    a();\n}\n");
+    let mut cursors = Cursors::new(vec![
+        Cursor::from_start(Pos::new(3, 1)),
+        Cursor::from_start(Pos::new(0, 7)),
+    ]);
+    cursors.update(&old, &new);
+    assert_eq!(
+        cursors,
+        Cursors::new(vec![
+            Cursor::from_start(Pos::new(3, 1)),
+            Cursor::from_start(Pos::new(0, 3)),
+        ])
+    );
+
+    // edits for text that changed while the server formatted it are not applied
+    workspace
+        .buffers
+        .insert(tab, Rope::from_str("fn main() {}\n"));
+    workspace.code_request = request(edits);
+    receive_code_request(&mut workspace);
+    assert!(workspace.edits.is_empty());
+    assert!(workspace.message.is_some());
+}
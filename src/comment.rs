@@ -0,0 +1,393 @@
+use ropey::Rope;
+use syntect::parsing::SyntaxReference;
+
+use crate::cursor::Cursor;
+#[cfg(test)]
+use crate::cursor::Pos;
+use crate::cursors::Cursors;
+use crate::PS;
+
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct CommentTokens {
+    pub line: Option<String>,
+    pub block: Option<(String, String)>,
+}
+
+impl CommentTokens {
+    pub fn new(line: Option<&str>, block: Option<(&str, &str)>) -> Self {
+        Self {
+            line: line.map(|l| l.to_string()),
+            block: block.map(|(s, e)| (s.to_string(), e.to_string())),
+        }
+    }
+
+    /// Reads the comment tokens from the syntax's TM_COMMENT_START/TM_COMMENT_END shell variables.
+    /// The bundled syntax dump does not ship metadata, so fall back to a table of common languages.
+    pub fn for_syntax(syntax: &SyntaxReference) -> Self {
+        let metadata = PS.metadata().metadata_for_scope(&[syntax.scope]);
+        let mut tokens = Self::default();
+        for (_, set) in &metadata.items {
+            let vars = &set.items.shell_variables;
+            for suffix in ["", "_2", "_3"] {
+                if let Some(start) = vars.get(&format!("TM_COMMENT_START{}", suffix)) {
+                    match vars.get(&format!("TM_COMMENT_END{}", suffix)) {
+                        Some(end) => {
+                            if tokens.block.is_none() {
+                                tokens.block =
+                                    Some((start.trim().to_string(), end.trim().to_string()));
+                            }
+                        }
+                        None => {
+                            if tokens.line.is_none() {
+                                tokens.line = Some(start.trim().to_string());
+                            }
+                        }
+                    }
+                }
+            }
+        }
+        if tokens == Self::default() {
+            Self::fallback(&syntax.name)
+        } else {
+            tokens
+        }
+    }
+
+    fn fallback(name: &str) -> Self {
+        match name {
+            "Rust" | "C" | "C++" | "C#" | "Java" | "JavaScript" | "Go" | "Objective-C"
+            | "Objective-C++" | "Scala" | "D" | "Groovy" | "PHP" => {
+                Self::new(Some("//"), Some(("/*", "*/")))
+            }
+            "Python"
+            | "Ruby"
+            | "Perl"
+            | "R"
+            | "Makefile"
+            | "YAML"
+            | "TOML"
+            | "TCL"
+            | "Bourne Again Shell (bash)"
+            | "Shell-Unix-Generic" => Self::new(Some("#"), None),
+            "Haskell" => Self::new(Some("--"), Some(("{-", "-}"))),
+            "Lua" => Self::new(Some("--"), Some(("--[[", "]]"))),
+            "SQL" => Self::new(Some("--"), Some(("/*", "*/"))),
+            "HTML" | "XML" | "Markdown" => Self::new(None, Some(("<!--", "-->"))),
+            "CSS" => Self::new(None, Some(("/*", "*/"))),
+            "OCaml" => Self::new(None, Some(("(*", "*)"))),
+            "Lisp" | "Clojure" => Self::new(Some(";"), None),
+            "Erlang" | "LaTeX" | "TeX" | "Matlab" => Self::new(Some("%"), None),
+            "Batch File" => Self::new(Some("REM"), None),
+            _ => Self::default(),
+        }
+    }
+
+    /// Toggles comments on every line touched by a cursor.
+    /// If every line is already commented they are all uncommented, otherwise they are all commented.
+    pub fn toggle(&self, cursors: &mut Cursors, rope: &mut Rope, block: bool) {
+        match (&self.line, &self.block) {
+            (Some(line), _) if !block || self.block.is_none() => toggle_line(cursors, rope, line),
+            (_, Some((start, end))) => toggle_block(cursors, rope, start, end),
+            _ => {}
+        }
+    }
+}
+
+fn realize_cursors(cursors: &mut Cursors, rope: &Rope) {
+    for c in cursors.list.iter_mut() {
+        c.start.realize_col(rope);
+        if let Some(e) = &mut c.end {
+            e.realize_col(rope);
+        }
+    }
+}
+
+fn shift_cursors(cursors: &mut Cursors, row: usize, col: usize, change: i32, inclusive: bool) {
+    for c in cursors.list.iter_mut() {
+        c.start.shift_for_edit(row, col, change, inclusive);
+        if let Some(e) = &mut c.end {
+            e.shift_for_edit(row, col, change, inclusive);
+        }
+    }
+}
+
+fn insert(
+    cursors: &mut Cursors,
+    rope: &mut Rope,
+    row: usize,
+    col: usize,
+    text: &str,
+    inclusive: bool,
+) {
+    rope.insert(rope.line_to_char(row) + col, text);
+    shift_cursors(cursors, row, col, text.chars().count() as i32, inclusive);
+}
+
+fn remove(cursors: &mut Cursors, rope: &mut Rope, row: usize, col: usize, len: usize) {
+    let idx = rope.line_to_char(row) + col;
+    rope.remove(idx..idx + len);
+    shift_cursors(cursors, row, col, -(len as i32), false);
+}
+
+fn indent(rope: &Rope, row: usize) -> usize {
+    rope.line(row)
+        .chars()
+        .take_while(|c| c.is_whitespace() && *c != '\n')
+        .count()
+}
+
+fn is_blank(rope: &Rope, row: usize) -> bool {
+    rope.line(row).chars().all(|c| c.is_whitespace())
+}
+
+fn starts_with(rope: &Rope, idx: usize, token: &str) -> bool {
+    let mut chars = rope.chars_at(idx.min(rope.len_chars()));
+    token.chars().all(|t| chars.next() == Some(t))
+}
+
+// the rows a cursor covers, a selection ending at the start of a row does not include that row
+fn cursor_rows(cursor: &Cursor, rope: &Rope) -> std::ops::RangeInclusive<usize> {
+    let first = cursor.first();
+    let last = cursor.last();
+    let mut end = last.row();
+    if end > first.row() && last.col(rope) == 0 {
+        end -= 1;
+    }
+    first.row()..=end
+}
+
+fn toggle_line(cursors: &mut Cursors, rope: &mut Rope, token: &str) {
+    realize_cursors(cursors, rope);
+
+    // group the rows by cursor so each group can be commented at its own indentation
+    let mut seen = std::collections::BTreeSet::new();
+    let mut groups: Vec<Vec<usize>> = Vec::new();
+    for c in &cursors.list {
+        let rows: Vec<usize> = cursor_rows(c, rope).filter(|r| seen.insert(*r)).collect();
+        let non_blank: Vec<usize> = rows
+            .iter()
+            .copied()
+            .filter(|r| !is_blank(rope, *r))
+            .collect();
+        // an empty line can only be commented if nothing else is selected
+        groups.push(if non_blank.is_empty() {
+            rows
+        } else {
+            non_blank
+        });
+    }
+
+    let commented =
+        |row: usize| starts_with(rope, rope.line_to_char(row) + indent(rope, row), token);
+    let all_commented = groups
+        .iter()
+        .flatten()
+        .all(|r| !is_blank(rope, *r) && commented(*r));
+
+    let token_len = token.chars().count();
+    for rows in groups {
+        if all_commented {
+            for row in rows {
+                let col = indent(rope, row);
+                let mut len = token_len;
+                if starts_with(rope, rope.line_to_char(row) + col + len, " ") {
+                    len += 1;
+                }
+                remove(cursors, rope, row, col, len);
+            }
+        } else {
+            let col = rows.iter().map(|r| indent(rope, *r)).min().unwrap_or(0);
+            let text = format!("{} ", token);
+            for row in rows {
+                insert(cursors, rope, row, col, &text, true);
+            }
+        }
+    }
+}
+
+fn toggle_block(cursors: &mut Cursors, rope: &mut Rope, start: &str, end: &str) {
+    realize_cursors(cursors, rope);
+
+    // the char range to wrap for each cursor, an empty cursor wraps the contents of its line
+    let mut ranges: Vec<(usize, usize)> = cursors
+        .list
+        .iter()
+        .map(|c| {
+            if c.end.is_some() {
+                (c.first().idx(rope), c.last().idx(rope))
+            } else {
+                let row = c.start.row();
+                let line_start = rope.line_to_char(row);
+                let content = rope.line(row).to_string();
+                let trimmed = content.trim_end().chars().count();
+                (
+                    line_start + indent(rope, row).min(trimmed),
+                    line_start + trimmed,
+                )
+            }
+        })
+        .collect();
+    let start_len = start.chars().count();
+    let end_len = end.chars().count();
+    // a selection of the text inside of a comment toggles the surrounding comment
+    for (s, e) in ranges.iter_mut() {
+        let before = *s - (*s > 0 && rope.char(*s - 1) == ' ') as usize;
+        let after = *e + starts_with(rope, *e, " ") as usize;
+        if before >= start_len
+            && starts_with(rope, before - start_len, start)
+            && starts_with(rope, after, end)
+        {
+            *s = before - start_len;
+            *e = after + end_len;
+        }
+    }
+    ranges.sort_unstable();
+    ranges.dedup();
+
+    // the range with surrounding whitespace trimmed
+    let trim = |rope: &Rope, (mut s, mut e): (usize, usize)| {
+        while s < e && rope.char(s).is_whitespace() {
+            s += 1;
+        }
+        while e > s && rope.char(e - 1).is_whitespace() {
+            e -= 1;
+        }
+        (s, e)
+    };
+    let commented = |rope: &Rope, range: (usize, usize)| {
+        let (s, e) = trim(rope, range);
+        e - s >= start_len + end_len
+            && starts_with(rope, s, start)
+            && starts_with(rope, e - end_len, end)
+    };
+    let all_commented = ranges.iter().all(|r| commented(rope, *r));
+
+    let to_pos = |rope: &Rope, idx: usize| {
+        let row = rope.char_to_line(idx);
+        (row, idx - rope.line_to_char(row))
+    };
+    // edit from the back so the earlier ranges stay valid
+    for range in ranges.into_iter().rev() {
+        if all_commented {
+            let (s, e) = trim(rope, range);
+            let mut remove_start = start_len;
+            if starts_with(rope, s + start_len, " ") {
+                remove_start += 1;
+            }
+            let mut end_idx = e - end_len;
+            let mut remove_end = end_len;
+            if end_idx > s + remove_start && rope.char(end_idx - 1) == ' ' {
+                end_idx -= 1;
+                remove_end += 1;
+            }
+            let (row, col) = to_pos(rope, end_idx);
+            remove(cursors, rope, row, col, remove_end);
+            let (row, col) = to_pos(rope, s);
+            remove(cursors, rope, row, col, remove_start);
+        } else if !commented(rope, range) {
+            let (s, e) = range;
+            let (row, col) = to_pos(rope, e);
+            insert(cursors, rope, row, col, &format!(" {}", end), false);
+            let (row, col) = to_pos(rope, s);
+            insert(cursors, rope, row, col, &format!("{} ", start), true);
+        }
+    }
+}
+
+#[test]
+fn toggle_line_comments() {
+    let tokens = CommentTokens::new(Some("//"), Some(("/*", "*/")));
+    let mut rope = Rope::from_str("fn main() {\n    let a = 1;\n\n    let b = 2;\n}");
+    let mut cursors = Cursors::new(vec![Cursor::new(Pos::new(0, 1), Pos::new(5, 3))]);
+
+    tokens.toggle(&mut cursors, &mut rope, false);
+    assert_eq!(
+        rope.to_string(),
+        "fn main() {\n    // let a = 1;\n\n    // let b = 2;\n}"
+    );
+    assert_eq!(
+        cursors,
+        Cursors::new(vec![Cursor::new(Pos::new(0, 1), Pos::new(8, 3))])
+    );
+
+    tokens.toggle(&mut cursors, &mut rope, false);
+    assert_eq!(
+        rope.to_string(),
+        "fn main() {\n    let a = 1;\n\n    let b = 2;\n}"
+    );
+    assert_eq!(
+        cursors,
+        Cursors::new(vec![Cursor::new(Pos::new(0, 1), Pos::new(5, 3))])
+    );
+}
+
+#[test]
+fn toggle_mixed_line_comments() {
+    let tokens = CommentTokens::new(Some("#"), None);
+    // some lines are commented, so every line gets commented
+    let mut rope = Rope::from_str("# a\nb\n# c");
+    let mut cursors = Cursors::new(vec![
+        Cursor::from_start(Pos::new(0, 0)),
+        Cursor::from_start(Pos::new(1, 1)),
+        Cursor::from_start(Pos::new(0, 2)),
+    ]);
+
+    tokens.toggle(&mut cursors, &mut rope, false);
+    assert_eq!(rope.to_string(), "# # a\n# b\n# # c");
+    assert_eq!(
+        cursors,
+        Cursors::new(vec![
+            Cursor::from_start(Pos::new(2, 0)),
+            Cursor::from_start(Pos::new(3, 1)),
+            Cursor::from_start(Pos::new(2, 2)),
+        ])
+    );
+
+    tokens.toggle(&mut cursors, &mut rope, false);
+    assert_eq!(rope.to_string(), "# a\nb\n# c");
+
+    // block comments fall back to line comments when the language has none
+    tokens.toggle(&mut cursors, &mut rope, true);
+    assert_eq!(rope.to_string(), "# # a\n# b\n# # c");
+}
+
+#[test]
+fn toggle_block_comments() {
+    let tokens = CommentTokens::new(Some("//"), Some(("/*", "*/")));
+    let mut rope = Rope::from_str("let a = 1 + 2;\n  let b;");
+    let mut cursors = Cursors::new(vec![
+        Cursor::new(Pos::new(8, 0), Pos::new(13, 0)),
+        Cursor::from_start(Pos::new(3, 1)),
+    ]);
+
+    tokens.toggle(&mut cursors, &mut rope, true);
+    assert_eq!(rope.to_string(), "let a = /* 1 + 2 */;\n  /* let b; */");
+    assert_eq!(
+        cursors,
+        Cursors::new(vec![
+            Cursor::new(Pos::new(11, 0), Pos::new(16, 0)),
+            Cursor::from_start(Pos::new(6, 1)),
+        ])
+    );
+
+    tokens.toggle(&mut cursors, &mut rope, true);
+    assert_eq!(rope.to_string(), "let a = 1 + 2;\n  let b;");
+    assert_eq!(
+        cursors,
+        Cursors::new(vec![
+            Cursor::new(Pos::new(8, 0), Pos::new(13, 0)),
+            Cursor::from_start(Pos::new(3, 1)),
+        ])
+    );
+}
+
+#[test]
+fn comment_tokens_for_syntax() {
+    let rust = PS.find_syntax_by_extension("rs").unwrap();
+    assert_eq!(CommentTokens::for_syntax(rust).line, Some("//".to_string()));
+    let python = PS.find_syntax_by_extension("py").unwrap();
+    assert_eq!(
+        CommentTokens::for_syntax(python).line,
+        Some("#".to_string())
+    );
+}
@@ -0,0 +1,510 @@
+use std::collections::{BTreeMap, BTreeSet, HashSet};
+use std::fmt::{self, Display};
+use std::sync::{Arc, Mutex};
+
+use dioxus::events::KeyboardData;
+use dioxus_html::KeyCode;
+use lsp_types::{CompletionItem, CompletionTextEdit, InsertTextFormat, Position, Range, Url};
+use ropey::Rope;
+
+use crate::commands::{document, Context};
+use crate::cursor::{Cursor, Pos};
+use crate::cursors::Cursors;
+use crate::fuzzy::{fuzzy_match, FuzzyMatch};
+use crate::lsp::{to_idx, LspClient};
+use crate::snippet::{insert_snippet, snippets};
+use crate::word::{is_word_char, word_start};
+use crate::workspace::Workspace;
+
+// the most candidates kept after ranking
+const MAX_CANDIDATES: usize = 100;
+
+/// Where a candidate came from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Kind {
+    Word,
+    Keyword,
+    Snippet,
+    Lsp,
+}
+
+impl Display for Kind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Kind::Word => write!(f, "word"),
+            Kind::Keyword => write!(f, "keyword"),
+            Kind::Snippet => write!(f, "snippet"),
+            Kind::Lsp => write!(f, "language server"),
+        }
+    }
+}
+
+/// A completion shown in the popup.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Candidate {
+    pub label: String,
+    // the text matched against the typed word
+    pub filter: String,
+    // the text that replaces the typed word
+    pub insert: String,
+    // the chars replaced before the typed word and after the cursor, as well as the typed word
+    pub around: (usize, usize),
+    pub detail: String,
+    pub kind: Kind,
+    // the insert text is a snippet body with tab stops
+    pub snippet: bool,
+}
+
+impl Candidate {
+    pub fn new(label: &str, kind: Kind) -> Self {
+        Self {
+            label: label.to_string(),
+            filter: label.to_string(),
+            insert: label.to_string(),
+            around: (0, 0),
+            detail: kind.to_string(),
+            kind,
+            snippet: false,
+        }
+    }
+}
+
+/// Everything a provider can complete from.
+pub struct Request<'a> {
+    // the part of the word before the primary cursor that is already typed
+    pub prefix: String,
+    pub syntax: &'a str,
+    // the words of every open buffer
+    pub words: &'a WordCache,
+    // the words of the buffer highlighted as part of the language
+    pub keywords: &'a BTreeSet<String>,
+}
+
+/// A source of completions, candidates are ranked against the typed word after they are collected.
+pub trait Provider: Send + Sync {
+    fn candidates(&self, request: &Request) -> Vec<Candidate>;
+}
+
+// the chars a range of the rope replaces before the start of the word at idx and after idx
+fn around(rope: &Rope, idx: usize, range: Range) -> (usize, usize) {
+    let start = to_idx(rope, range.start);
+    let end = to_idx(rope, range.end);
+    (
+        word_start(idx, rope).saturating_sub(start),
+        end.saturating_sub(idx),
+    )
+}
+
+// a completion item of a language server, asked for with the cursor at idx in the rope
+fn server_candidate(item: CompletionItem, rope: &Rope, idx: usize) -> Candidate {
+    let (insert, around) = match item.text_edit {
+        Some(CompletionTextEdit::Edit(edit)) => (edit.new_text, around(rope, idx, edit.range)),
+        Some(CompletionTextEdit::InsertAndReplace(edit)) => {
+            (edit.new_text, around(rope, idx, edit.insert))
+        }
+        None => (
+            item.insert_text.unwrap_or_else(|| item.label.clone()),
+            (0, 0),
+        ),
+    };
+    Candidate {
+        filter: item.filter_text.unwrap_or_else(|| item.label.clone()),
+        insert,
+        around,
+        snippet: item.insert_text_format == Some(InsertTextFormat::SNIPPET),
+        detail: item.detail.unwrap_or_else(|| Kind::Lsp.to_string()),
+        label: item.label,
+        kind: Kind::Lsp,
+    }
+}
+
+/// Completions asked from the language server of a buffer, they are added to the popup when they are received.
+#[derive(Debug, Clone)]
+pub struct ServerCompletion {
+    // the typed word the completions were asked for
+    prefix: String,
+    explicit: bool,
+    result: Arc<Mutex<Option<Vec<Candidate>>>>,
+}
+
+impl ServerCompletion {
+    /// Asks the server on a background thread, idx is the char of the rope at the position.
+    pub fn start(
+        (client, uri, position): (LspClient, Url, Position),
+        rope: &Rope,
+        idx: usize,
+        prefix: String,
+        explicit: bool,
+    ) -> Self {
+        let completion = Self {
+            prefix,
+            explicit,
+            result: Arc::new(Mutex::new(None)),
+        };
+        let result = completion.result.clone();
+        let rope = rope.clone();
+        let ask = move || {
+            let items = client.completion(&uri, position).unwrap_or_default();
+            let candidates = items
+                .into_iter()
+                .map(|item| server_candidate(item, &rope, idx))
+                .collect();
+            *result.lock().unwrap() = Some(candidates);
+        };
+        #[cfg(not(target_arch = "wasm32"))]
+        std::thread::spawn(ask);
+        #[cfg(target_arch = "wasm32")]
+        ask();
+        completion
+    }
+
+    pub fn is_received(&self) -> bool {
+        self.result.lock().unwrap().is_some()
+    }
+}
+
+/// The snippets of the syntax, one candidate for each prefix.
+struct Snippets;
+
+impl Provider for Snippets {
+    fn candidates(&self, request: &Request) -> Vec<Candidate> {
+        snippets(request.syntax)
+            .iter()
+            .flat_map(|snippet| {
+                snippet.prefixes.iter().map(move |prefix| Candidate {
+                    insert: snippet.body.clone(),
+                    detail: snippet
+                        .description
+                        .clone()
+                        .unwrap_or_else(|| snippet.name.clone()),
+                    snippet: true,
+                    ..Candidate::new(prefix, Kind::Snippet)
+                })
+            })
+            .collect()
+    }
+}
+
+/// Keywords and built in names of the language used in the buffer.
+struct Keywords;
+
+impl Provider for Keywords {
+    fn candidates(&self, request: &Request) -> Vec<Candidate> {
+        request
+            .keywords
+            .iter()
+            .map(|k| Candidate::new(k, Kind::Keyword))
+            .collect()
+    }
+}
+
+// the words of a text, without numbers
+fn words(rope: &Rope) -> BTreeSet<String> {
+    let mut words = BTreeSet::new();
+    let mut word = String::new();
+    for c in rope.chars().chain(std::iter::once(' ')) {
+        if is_word_char(c) {
+            word.push(c);
+        } else if !word.is_empty() {
+            let word = std::mem::take(&mut word);
+            if !word.starts_with(char::is_numeric) {
+                words.insert(word);
+            }
+        }
+    }
+    words
+}
+
+/// The words of each open buffer by tab, the words of a buffer are found again only when its text changes.
+#[derive(Debug, Clone, Default)]
+pub struct WordCache(BTreeMap<usize, (Rope, BTreeSet<String>)>);
+
+impl WordCache {
+    pub fn update(&mut self, tab: usize, rope: &Rope) {
+        match self.0.get(&tab) {
+            Some((cached, _)) if cached == rope => {}
+            _ => {
+                self.0.insert(tab, (rope.clone(), words(rope)));
+            }
+        }
+    }
+
+    pub fn remove(&mut self, tab: usize) {
+        self.0.remove(&tab);
+    }
+
+    fn words(&self) -> BTreeSet<&String> {
+        self.0.values().flat_map(|(_, words)| words).collect()
+    }
+}
+
+/// Words in every open buffer.
+struct BufferWords;
+
+impl Provider for BufferWords {
+    fn candidates(&self, request: &Request) -> Vec<Candidate> {
+        request
+            .words
+            .words()
+            .into_iter()
+            // the word being typed is not completed
+            .filter(|w| **w != request.prefix)
+            .map(|w| Candidate::new(w, Kind::Word))
+            .collect()
+    }
+}
+
+lazy_static! {
+    // the first provider of a label is the one shown, the language server comes before all of them
+    static ref PROVIDERS: Vec<Box<dyn Provider>> = vec![
+        Box::new(Snippets),
+        Box::new(Keywords),
+        Box::new(BufferWords),
+    ];
+}
+
+/// Fuzzy matches candidates against the typed word, the best matches first.
+fn rank(prefix: &str, candidates: Vec<Candidate>) -> Vec<(Candidate, FuzzyMatch)> {
+    let mut seen = HashSet::new();
+    let mut ranked: Vec<_> = candidates
+        .into_iter()
+        .filter(|c| seen.insert(c.label.clone()))
+        .filter_map(|c| {
+            let mut m = fuzzy_match(prefix, &c.filter)?;
+            // the matched chars are shown in the label
+            if c.filter != c.label {
+                m.indexes = fuzzy_match(prefix, &c.label).map_or(Vec::new(), |m| m.indexes);
+            }
+            Some((c, m))
+        })
+        .collect();
+    // the sort is stable so candidates that match as well stay in the order of their providers
+    ranked.sort_by_key(|(_, m)| -m.score);
+    ranked.truncate(MAX_CANDIDATES);
+    ranked
+}
+
+/// The candidates of every provider ranked against the typed word.
+pub fn complete(request: &Request) -> Vec<(Candidate, FuzzyMatch)> {
+    let candidates = PROVIDERS
+        .iter()
+        .flat_map(|p| p.candidates(request))
+        .collect();
+    rank(&request.prefix, candidates)
+}
+
+/// The open completion popup.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Completion {
+    pub candidates: Vec<(Candidate, FuzzyMatch)>,
+    pub selected: usize,
+}
+
+/// Opens the completion popup for the word before the primary cursor, or closes it if nothing matches.
+/// Typing only opens it after the start of a word, explicit requests open it anywhere.
+/// The completions of the language server are added once it answers.
+pub fn open_completion(ctx: &mut Context, explicit: bool) {
+    let rope = &ctx.buffer.rope;
+    let idx = ctx.cursors.primary().caret().idx(rope);
+    let prefix = rope.slice(word_start(idx, rope)..idx).to_string();
+    if prefix.is_empty() && !explicit {
+        ctx.workspace.write().close_completion();
+        return;
+    }
+    let server = document(ctx)
+        .ok()
+        .map(|lsp| ServerCompletion::start(lsp, rope, idx, prefix.clone(), explicit));
+    let mut workspace = ctx.workspace.write();
+    workspace.words.update(ctx.tab, rope);
+    let request = Request {
+        prefix,
+        syntax: &ctx.buffer.syntax.name,
+        words: &workspace.words,
+        keywords: ctx.keywords,
+    };
+    let candidates = complete(&request);
+    if candidates.is_empty() && explicit && server.is_none() {
+        workspace.message = Some("No completions".to_string());
+    }
+    workspace.completion = (!candidates.is_empty()).then(|| Completion {
+        candidates,
+        selected: 0,
+    });
+    workspace.completion_request = server;
+}
+
+/// Adds the completions of the language server to the popup once they are received.
+pub fn receive_completion(workspace: &mut Workspace) {
+    let server = match &workspace.completion_request {
+        Some(server) => server.clone(),
+        None => return,
+    };
+    let received = match server.result.lock().unwrap().take() {
+        Some(received) => received,
+        None => return,
+    };
+    workspace.completion_request = None;
+    let (shown, selected) = match workspace.completion.take() {
+        Some(c) => {
+            let selected = c.candidates[c.selected].0.label.clone();
+            (
+                c.candidates.into_iter().map(|(c, _)| c).collect(),
+                Some(selected),
+            )
+        }
+        None => (Vec::new(), None),
+    };
+    let candidates = rank(&server.prefix, received.into_iter().chain(shown).collect());
+    if candidates.is_empty() && server.explicit {
+        workspace.message = Some("No completions".to_string());
+    }
+    // the candidate that was selected stays selected
+    let selected = candidates
+        .iter()
+        .position(|(c, _)| Some(&c.label) == selected.as_ref())
+        .unwrap_or(0);
+    workspace.completion = (!candidates.is_empty()).then(|| Completion {
+        candidates,
+        selected,
+    });
+}
+
+// selects the chars a candidate replaces around the typed word at each cursor without a selection
+fn select_around(cursors: &mut Cursors, (before, after): (usize, usize), rope: &Rope) {
+    if (before, after) == (0, 0) {
+        return;
+    }
+    for c in cursors.list.iter_mut().filter(|c| c.end.is_none()) {
+        let idx = c.caret().idx(rope);
+        let start = word_start(idx, rope).saturating_sub(before);
+        let end = (idx + after).min(rope.len_chars());
+        *c = Cursor::new(Pos::from_idx(start, rope), Pos::from_idx(end, rope));
+    }
+}
+
+/// Handles a key press while the popup is open, returns false for keys that are not used by it.
+pub fn completion_input(k: &KeyboardData, ctx: &mut Context) -> bool {
+    let mut workspace = ctx.workspace.write();
+    let completion = match &mut workspace.completion {
+        Some(completion) => completion,
+        None => return false,
+    };
+    let len = completion.candidates.len();
+    match k.key_code {
+        KeyCode::UpArrow => completion.selected = (completion.selected + len - 1) % len,
+        KeyCode::DownArrow => completion.selected = (completion.selected + 1) % len,
+        KeyCode::Escape => workspace.close_completion(),
+        KeyCode::Tab | KeyCode::Enter if !k.shift_key && !k.ctrl_key => {
+            let (candidate, _) = completion.candidates[completion.selected].clone();
+            workspace.close_completion();
+            drop(workspace);
+            select_around(ctx.cursors, candidate.around, &ctx.buffer.rope);
+            if candidate.snippet {
+                insert_snippet(ctx, &candidate.insert);
+            } else {
+                ctx.cursors
+                    .replace_word_before(&candidate.insert, &mut ctx.buffer.rope);
+            }
+        }
+        _ => return false,
+    }
+    true
+}
+
+#[test]
+fn rank_candidates_from_providers() {
+    let rope = Rope::from_str("let value = va");
+    let other = Rope::from_str("fn validate() {}");
+    let keywords: BTreeSet<String> = ["let".to_string(), "fn".to_string()].into();
+    let mut words = WordCache::default();
+    words.update(0, &rope);
+    words.update(1, &other);
+    let request = Request {
+        prefix: "va".to_string(),
+        syntax: "Rust",
+        words: &words,
+        keywords: &keywords,
+    };
+    let labels: Vec<_> = complete(&request)
+        .into_iter()
+        .map(|(c, _)| (c.label, c.kind))
+        .collect();
+    assert_eq!(
+        labels,
+        vec![
+            ("validate".to_string(), Kind::Word),
+            ("value".to_string(), Kind::Word),
+        ]
+    );
+
+    // an empty prefix matches everything, snippets come before the keyword with the same label
+    let request = Request {
+        prefix: String::new(),
+        ..request
+    };
+    let candidates = complete(&request);
+    let fn_candidates: Vec<_> = candidates.iter().filter(|(c, _)| c.label == "fn").collect();
+    assert_eq!(fn_candidates.len(), 1);
+    assert_eq!(fn_candidates[0].0.kind, Kind::Snippet);
+    assert!(candidates
+        .iter()
+        .any(|(c, _)| c.label == "let" && c.kind == Kind::Keyword));
+}
+
+#[test]
+fn candidates_from_server_items() {
+    let rope = Rope::from_str("self.va");
+    let item = |json| serde_json::from_value::<CompletionItem>(json).unwrap();
+
+    // the edit replaces the dot and the typed word, the filter text is matched
+    let edited = server_candidate(
+        item(serde_json::json!({
+            "label": "value()",
+            "filterText": ".value",
+            "textEdit": {
+                "range": {"start": {"line": 0, "character": 4}, "end": {"line": 0, "character": 7}},
+                "newText": "?.value()",
+            },
+        })),
+        &rope,
+        7,
+    );
+    assert_eq!(edited.insert, "?.value()");
+    assert_eq!(edited.around, (1, 0));
+    let ranked = rank("va", vec![edited.clone()]);
+    assert_eq!(ranked.len(), 1);
+    assert_eq!(ranked[0].1.indexes, vec![0, 1]);
+
+    let inserted = server_candidate(
+        item(serde_json::json!({"label": "validate", "insertText": "validate()"})),
+        &rope,
+        7,
+    );
+    assert_eq!(inserted.insert, "validate()");
+    assert_eq!(inserted.around, (0, 0));
+    let labelled = server_candidate(item(serde_json::json!({"label": "vec"})), &rope, 7);
+    assert_eq!(labelled.insert, "vec");
+
+    let mut rope = rope;
+    let mut cursors = Cursors::new(vec![Cursor::from_start(Pos::new(7, 0))]);
+    select_around(&mut cursors, edited.around, &rope);
+    cursors.replace_word_before(&edited.insert, &mut rope);
+    assert_eq!(rope.to_string(), "self?.value()");
+}
+
+#[test]
+fn words_are_found_again_only_for_changed_buffers() {
+    let mut words = WordCache::default();
+    let first = Rope::from_str("alpha 12 beta");
+    words.update(0, &first);
+    words.update(1, &Rope::from_str("gamma"));
+    let found: Vec<_> = words.words().into_iter().cloned().collect();
+    assert_eq!(found, vec!["alpha", "beta", "gamma"]);
+
+    // the same text keeps its words, a changed text replaces them
+    words.update(0, &first.clone());
+    words.update(1, &Rope::from_str("delta"));
+    words.remove(0);
+    let found: Vec<_> = words.words().into_iter().cloned().collect();
+    assert_eq!(found, vec!["delta"]);
+}
@@ -0,0 +1,72 @@
+use crate::fuzzy::split_matched;
+use crate::utils::color_to_string;
+use crate::workspace::Workspace;
+use crate::{editor_colors, theme};
+use dioxus::prelude::*;
+
+// the most candidates shown at once
+const MAX_SHOWN: usize = 10;
+
+#[derive(Props)]
+pub struct CompletionPopupProps<'a> {
+    workspace: &'a UseRef<Workspace>,
+    // the column the popup starts at
+    left: usize,
+}
+pub fn CompletionPopup<'a>(cx: Scope<'a, CompletionPopupProps<'a>>) -> Element {
+    let completion = match cx.props.workspace.read().completion.clone() {
+        Some(completion) => completion,
+        None => return None,
+    };
+    let left = cx.props.left;
+
+    let colors = editor_colors();
+    let fg = color_to_string(colors.foreground);
+    let bg = color_to_string(theme().settings.gutter.unwrap_or(colors.background));
+    let selected_bg = color_to_string(colors.selection);
+    let matched_fg = color_to_string(theme().settings.accent.unwrap_or(colors.foreground));
+    // keep the selected candidate in view
+    let skip = (completion.selected + 1).saturating_sub(MAX_SHOWN);
+
+    cx.render(rsx! {
+        div{
+            position: "absolute",
+            top: "0px",
+            left: "{left}ch",
+            min_width: "20ch",
+            max_width: "60ch",
+            display: "flex",
+            flex_direction: "column",
+            z_index: "1",
+            color: "{fg}",
+            background_color: "{bg}",
+            completion.candidates.into_iter().enumerate().skip(skip).take(MAX_SHOWN).map(|(i, (candidate, m))| {
+                let row_bg = if i == completion.selected { selected_bg.clone() } else { bg.clone() };
+                let detail = candidate.detail;
+                let pieces = split_matched(&candidate.label, &m.indexes);
+                let matched_fg = matched_fg.clone();
+                rsx! {
+                    div{
+                        key: "{i}",
+                        display: "flex",
+                        flex_direction: "row",
+                        justify_content: "space-between",
+                        padding: "0px 6px",
+                        white_space: "pre",
+                        background_color: "{row_bg}",
+                        span{
+                            pieces.into_iter().map(|(matched, text)| {
+                                if matched {
+                                    rsx!(span{ color: "{matched_fg}", font_weight: "bold", "{text}" })
+                                } else {
+                                    rsx!(span{ "{text}" })
+                                }
+                            })
+                        }
+                        span{ padding_left: "12px", opacity: "0.6", "{detail}" }
+                    }
+                }
+            })
+        }
+    })
+}
@@ -0,0 +1,65 @@
+use crate::utils::color_to_string;
+use crate::watch::{keep_changes, reload, show_diff};
+use crate::workspace::Workspace;
+use crate::{editor_colors, theme};
+use dioxus::prelude::*;
+
+#[derive(Props)]
+pub struct ConflictPanelProps<'a> {
+    workspace: &'a UseRef<Workspace>,
+}
+/// Asks what to do with buffers with unsaved changes whose file changed on disk.
+pub fn ConflictPanel<'a>(cx: Scope<'a, ConflictPanelProps<'a>>) -> Element {
+    let workspace = cx.props.workspace;
+    let files: Vec<_> = {
+        let workspace = workspace.read();
+        workspace
+            .conflicts
+            .keys()
+            .filter_map(|tab| Some((*tab, workspace.tabs.get(*tab)?.name.clone())))
+            .collect()
+    };
+
+    let colors = editor_colors();
+    let fg = color_to_string(colors.foreground);
+    let bg = color_to_string(theme().settings.gutter.unwrap_or(colors.background));
+
+    cx.render(rsx! {
+        div{
+            width: "100%",
+            max_height: "30%",
+            overflow: "auto",
+            color: "{fg}",
+            background_color: "{bg}",
+            files.into_iter().map(|(tab, name)| rsx! {
+                div{
+                    key: "{tab}",
+                    padding: "2px 6px",
+                    display: "flex",
+                    flex_direction: "row",
+                    span{
+                        flex_grow: "1",
+                        "{name} changed on disk and has unsaved changes"
+                    }
+                    span{
+                        cursor: "pointer",
+                        padding_right: "12px",
+                        onclick: move |_| reload(&mut workspace.write(), tab),
+                        "Reload"
+                    }
+                    span{
+                        cursor: "pointer",
+                        padding_right: "12px",
+                        onclick: move |_| keep_changes(&mut workspace.write(), tab),
+                        "Keep mine"
+                    }
+                    span{
+                        cursor: "pointer",
+                        onclick: move |_| show_diff(&mut workspace.write(), tab),
+                        "Show diff"
+                    }
+                }
+            })
+        }
+    })
+}
@@ -2,8 +2,14 @@ use std::cmp::Ordering;
 
 use dioxus::events::KeyboardData;
 use ropey::Rope;
+use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg(test)]
+use crate::fold::FoldRange;
+use crate::fold::Folds;
+use crate::layout::Layout;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct Pos {
     col: usize,
     row: usize,
@@ -14,30 +20,84 @@ impl Pos {
         Self { row, col }
     }
 
-    pub fn up(&mut self, rope: &Rope) {
-        self.move_row(-1, rope);
+    pub fn from_idx(idx: usize, rope: &Rope) -> Self {
+        let row = rope.char_to_line(idx);
+        Self::new(idx - rope.line_to_char(row), row)
+    }
+
+    pub fn line_end(row: usize, rope: &Rope) -> Self {
+        let mut pos = Self::new(0, row);
+        pos.col = pos.len_line(rope);
+        pos
     }
 
-    pub fn down(&mut self, rope: &Rope) {
-        self.move_row(1, rope);
+    pub fn up(&mut self, rope: &Rope, folds: &Folds) {
+        self.move_row(-1, rope, folds);
     }
 
-    pub fn right(&mut self, rope: &Rope) {
-        self.move_col(1, rope);
+    pub fn down(&mut self, rope: &Rope, folds: &Folds) {
+        self.move_row(1, rope, folds);
     }
 
-    pub fn left(&mut self, rope: &Rope) {
-        self.move_col(-1, rope);
+    pub fn right(&mut self, rope: &Rope, folds: &Folds) {
+        self.move_col(1, rope, folds);
     }
 
-    pub fn move_row(&mut self, change: i32, rope: &Rope) {
+    pub fn left(&mut self, rope: &Rope, folds: &Folds) {
+        self.move_col(-1, rope, folds);
+    }
+
+    // moving onto a folded row skips to the nearest visible row
+    pub fn move_row(&mut self, change: i32, rope: &Rope, folds: &Folds) {
         let new = self.row as i32 + change;
         if new >= 0 && new < rope.len_lines() as i32 {
-            self.row = new as usize;
+            if let Some(row) = folds.visible_row(new as usize, change > 0, rope.len_lines()) {
+                self.row = row;
+            }
+        }
+    }
+
+    // moves between the visual rows of wrapped lines, the column is kept where the text is displayed
+    pub fn move_visual_row(&mut self, change: i32, rope: &Rope, layout: &Layout) {
+        if layout.wrap_width.is_none() {
+            self.move_row(change, rope, layout.folds);
+            return;
+        }
+        self.realize_col(rope);
+        let line = layout.wrapped(rope, self.row);
+        let x = line.x(self.col);
+        let target = line.row_of(self.col) as i32 + change;
+        if target >= 0 && (target as usize) < line.starts.len() {
+            self.col = line.col_at(target as usize, x, self.len_line(rope));
+            return;
+        }
+        let old_row = self.row;
+        self.move_row(change, rope, layout.folds);
+        if self.row != old_row {
+            let line = layout.wrapped(rope, self.row);
+            let target = if change > 0 { 0 } else { line.starts.len() - 1 };
+            self.col = line.col_at(target, x, self.len_line(rope));
         }
     }
 
-    pub fn move_col(&mut self, change: i32, rope: &Rope) {
+    // moves to the start of the visual row, or the start of the line if already there
+    pub fn move_home(&mut self, rope: &Rope, layout: &Layout) {
+        self.realize_col(rope);
+        let line = layout.wrapped(rope, self.row);
+        let start = line.starts[line.row_of(self.col)];
+        self.col = if self.col == start { 0 } else { start };
+    }
+
+    // moves to the end of the visual row, or the end of the line if already there
+    pub fn move_end(&mut self, rope: &Rope, layout: &Layout) {
+        self.realize_col(rope);
+        let line = layout.wrapped(rope, self.row);
+        let len = self.len_line(rope);
+        let end = line.row_end(line.row_of(self.col), len);
+        self.col = if self.col == end { len } else { end };
+    }
+
+    pub fn move_col(&mut self, change: i32, rope: &Rope, folds: &Folds) {
         self.realize_col(rope);
         let idx = self.idx(rope) as i32;
         if idx + change >= 0 && idx + change <= rope.len_chars() as i32 {
@@ -45,13 +105,13 @@ impl Pos {
             let new_col = self.col as i32 + change;
             let diff = new_col - len_line;
             if diff > 0 {
-                self.down(rope);
+                self.down(rope, folds);
                 self.col = 0;
-                self.move_col(diff - 1, rope);
+                self.move_col(diff - 1, rope, folds);
             } else if new_col < 0 {
-                self.up(rope);
+                self.up(rope, folds);
                 self.col = self.len_line(rope);
-                self.move_col(new_col + 1, rope);
+                self.move_col(new_col + 1, rope, folds);
             } else {
                 self.col = new_col as usize;
             }
@@ -73,10 +133,14 @@ impl Pos {
     fn len_line(&self, rope: &Rope) -> usize {
         let line = rope.line(self.row);
         let len = line.len_chars();
-        if len > 0 && line.get_char(len - 1) == Some('\n') {
-            len - 1
-        } else {
-            len
+        // a CRLF is a single line break, the cursor is never placed between its chars
+        match (
+            len.checked_sub(2).map(|i| line.char(i)),
+            len.checked_sub(1).map(|i| line.char(i)),
+        ) {
+            (Some('\r'), Some('\n')) => len - 2,
+            (_, Some('\n' | '\r')) => len - 1,
+            _ => len,
         }
     }
 
@@ -85,9 +149,20 @@ impl Pos {
     }
 
     // the column can be more than the line length, cap it
-    fn realize_col(&mut self, rope: &Rope) {
+    pub fn realize_col(&mut self, rope: &Rope) {
         self.col = self.col(rope);
     }
+
+    // keep the position on the same character after `change` chars are inserted (or removed if negative) at col on row
+    // the column should be realized before the rope is edited
+    pub fn shift_for_edit(&mut self, row: usize, col: usize, change: i32, inclusive: bool) {
+        if self.row != row {
+            return;
+        }
+        if self.col > col || (inclusive && self.col == col) {
+            self.col = (self.col as i32 + change).max(col as i32) as usize;
+        }
+    }
 }
 
 impl Ord for Pos {
@@ -102,7 +177,24 @@ impl PartialOrd for Pos {
     }
 }
 
-#[derive(Debug, Clone, PartialEq, Eq)]
+/// A way to move the caret.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Motion {
+    Up,
+    Down,
+    Left,
+    Right,
+    // to the next blank line
+    BlockUp,
+    BlockDown,
+    // to the next space
+    WordLeft,
+    WordRight,
+    Home,
+    End,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct Cursor {
     pub start: Pos,
     pub end: Option<Pos>,
@@ -123,6 +215,22 @@ impl Cursor {
         }
     }
 
+    /// Selects every line between the anchor row and row, with the caret on row.
+    pub fn select_lines(anchor: usize, row: usize, rope: &Rope) -> Self {
+        let line_end = |row: usize| {
+            if row + 1 < rope.len_lines() {
+                Pos::new(0, row + 1)
+            } else {
+                Pos::line_end(row, rope)
+            }
+        };
+        if anchor <= row {
+            Self::new(Pos::new(0, anchor), line_end(row))
+        } else {
+            Self::new(line_end(anchor), Pos::new(0, row))
+        }
+    }
+
     fn move_cursor(&mut self, f: impl FnOnce(&mut Pos), shift: bool) {
         if shift {
             self.with_end(f);
@@ -150,169 +258,144 @@ impl Cursor {
         [dc, dr]
     }
 
-    pub fn handle_input(
-        &mut self,
-        data: &dioxus_html::on::KeyboardData,
-        rope: &mut Rope,
-    ) -> [i32; 2] {
-        use dioxus_html::KeyCode::*;
-        match data.key_code {
-            UpArrow => {
-                if data.ctrl_key {
-                    self.move_cursor(
-                        |c| {
-                            let mut change = -1;
-                            let row = c.row() as i32;
-                            while row + change > 0 {
-                                let chr = rope.line((row + change) as usize);
-                                if chr.chars().all(|chr| chr.is_whitespace()) {
-                                    break;
-                                }
-                                change -= 1;
-                            }
-                            c.move_row(change as i32, rope);
-                        },
-                        data.shift_key,
-                    );
-                } else {
-                    self.move_cursor(|c| c.up(rope), data.shift_key);
-                }
-                [0, 0]
-            }
-            DownArrow => {
-                if data.ctrl_key {
-                    self.move_cursor(
-                        |c| {
-                            let mut change = 1;
-                            let row = c.row();
-                            let length = rope.len_lines();
-                            while row + change + 1 < length {
-                                let chr = rope.line(row + change);
-                                if chr.chars().all(|chr| chr.is_whitespace()) {
-                                    break;
-                                }
-                                change += 1;
-                            }
-                            c.move_row(change as i32, rope);
-                        },
-                        data.shift_key,
-                    );
-                } else {
-                    self.move_cursor(|c| c.down(rope), data.shift_key);
-                }
-                [0, 0]
-            }
-            RightArrow => {
-                if data.ctrl_key {
-                    self.move_cursor(
-                        |c| {
-                            let mut change = 1;
-                            let idx = c.idx(rope);
-                            let length = rope.len_chars();
-                            while idx + change + 1 < length {
-                                let chr = rope.char(idx + change);
-                                if chr.is_whitespace() {
-                                    break;
-                                }
-                                change += 1;
-                            }
-                            c.move_col(change as i32, rope);
-                        },
-                        data.shift_key,
-                    );
-                } else {
-                    self.move_cursor(|c| c.right(rope), data.shift_key);
+    /// Moves the caret, extending the selection if select is true.
+    pub fn move_by(&mut self, motion: Motion, select: bool, rope: &Rope, layout: &Layout) {
+        self.move_cursor(
+            |c| match motion {
+                Motion::Up => c.move_visual_row(-1, rope, layout),
+                Motion::Down => c.move_visual_row(1, rope, layout),
+                Motion::Left => c.left(rope, layout.folds),
+                Motion::Right => c.right(rope, layout.folds),
+                Motion::BlockUp => {
+                    let mut change = -1;
+                    let row = c.row() as i32;
+                    while row + change > 0 {
+                        let chr = rope.line((row + change) as usize);
+                        if chr.chars().all(|chr| chr.is_whitespace()) {
+                            break;
+                        }
+                        change -= 1;
+                    }
+                    c.move_row(change as i32, rope, layout.folds);
                 }
-                [0, 0]
-            }
-            LeftArrow => {
-                if data.ctrl_key {
-                    self.move_cursor(
-                        |c| {
-                            let mut change = -1;
-                            let idx = c.idx(rope) as i32;
-                            while idx + change > 0 {
-                                let chr = rope.char((idx + change) as usize);
-                                if chr == ' ' {
-                                    break;
-                                }
-                                change -= 1;
-                            }
-                            c.move_col(change as i32, rope);
-                        },
-                        data.shift_key,
-                    );
-                } else {
-                    self.move_cursor(|c| c.left(rope), data.shift_key);
+                Motion::BlockDown => {
+                    let mut change = 1;
+                    let row = c.row();
+                    let length = rope.len_lines();
+                    while row + change + 1 < length {
+                        let chr = rope.line(row + change);
+                        if chr.chars().all(|chr| chr.is_whitespace()) {
+                            break;
+                        }
+                        change += 1;
+                    }
+                    c.move_row(change as i32, rope, layout.folds);
                 }
-                [0, 0]
-            }
-            End => {
-                self.move_cursor(|c| c.col = c.len_line(rope), data.shift_key);
-                [0, 0]
-            }
-            Home => {
-                self.move_cursor(|c| c.col = 0, data.shift_key);
-                [0, 0]
-            }
-            Backspace => {
-                self.start.realize_col(rope);
-                let start_idx = self.start.idx(rope);
-                if self.end.is_some() {
-                    self.delete_selection(rope)
-                } else {
-                    if start_idx > 0 {
-                        let old_row = self.start.row;
-                        self.start.left(rope);
-                        rope.remove(start_idx - 1..start_idx);
-                        if old_row == self.start.row {
-                            [-1, 0]
-                        } else {
-                            [0, -1]
+                Motion::WordLeft => {
+                    let mut change = -1;
+                    let idx = c.idx(rope) as i32;
+                    while idx + change > 0 {
+                        let chr = rope.char((idx + change) as usize);
+                        if chr == ' ' {
+                            break;
                         }
-                    } else {
-                        [0, 0]
+                        change -= 1;
                     }
+                    c.move_col(change as i32, rope, layout.folds);
                 }
-            }
-            Enter => {
-                let old_col = self.start.col(&rope);
-                rope.insert_char(self.start.idx(rope), '\n');
-                self.start.col = 0;
-                self.start.down(rope);
-                [-(old_col as i32), 1]
-            }
-            Tab => {
-                self.start.realize_col(rope);
-                let mut change = self.delete_selection(rope);
-                rope.insert_char(self.start.idx(rope), '\t');
-                self.start.right(rope);
-                change[0] += 1;
-                change
-            }
-            _ => {
-                self.start.realize_col(rope);
-                if data.key.len() == 1 {
-                    let mut change = self.delete_selection(rope);
-                    let c = data.key.chars().next().unwrap();
-                    rope.insert_char(self.start.idx(rope), c);
-                    self.start.right(rope);
-                    change[0] += 1;
-                    change
-                } else {
-                    [0, 0]
+                Motion::WordRight => {
+                    let mut change = 1;
+                    let idx = c.idx(rope);
+                    let length = rope.len_chars();
+                    while idx + change + 1 < length {
+                        let chr = rope.char(idx + change);
+                        if chr.is_whitespace() {
+                            break;
+                        }
+                        change += 1;
+                    }
+                    c.move_col(change as i32, rope, layout.folds);
                 }
+                Motion::Home => c.move_home(rope, layout),
+                Motion::End => c.move_end(rope, layout),
+            },
+            select,
+        );
+    }
+
+    /// Deletes the selection, or the char before the caret if nothing is selected.
+    pub fn backspace(&mut self, rope: &mut Rope) -> [i32; 2] {
+        self.start.realize_col(rope);
+        let start_idx = self.start.idx(rope);
+        if self.end.is_some() {
+            self.delete_selection(rope)
+        } else if start_idx > 0 {
+            let old_row = self.start.row;
+            // the edit opens any fold, so move through it
+            self.start.left(rope, &Folds::default());
+            // at the start of a line this removes the whole line break
+            rope.remove(self.start.idx(rope)..start_idx);
+            if old_row == self.start.row {
+                [-1, 0]
+            } else {
+                [0, -1]
             }
+        } else {
+            [0, 0]
         }
     }
 
+    /// Breaks the line at the caret with the line ending of the buffer.
+    pub fn newline(&mut self, line_ending: &str, rope: &mut Rope) -> [i32; 2] {
+        let old_col = self.start.col(rope);
+        rope.insert(self.start.idx(rope), line_ending);
+        self.start.col = 0;
+        self.start.down(rope, &Folds::default());
+        [-(old_col as i32), 1]
+    }
+
+    /// Types the character of a key press, keys that are not characters are ignored.
+    pub fn handle_input(&mut self, data: &KeyboardData, rope: &mut Rope) -> [i32; 2] {
+        self.start.realize_col(rope);
+        if data.key.chars().count() == 1 {
+            let mut change = self.delete_selection(rope);
+            let c = data.key.chars().next().unwrap();
+            rope.insert_char(self.start.idx(rope), c);
+            self.start.right(rope, &Folds::default());
+            change[0] += 1;
+            change
+        } else {
+            [0, 0]
+        }
+    }
+
+    // replace the selection with text, returns the change in position for the cursors after this one
+    pub fn insert_text(&mut self, text: &str, rope: &mut Rope) -> [i32; 2] {
+        self.start.realize_col(rope);
+        let mut change = self.delete_selection(rope);
+        let old_col = self.start.col;
+        let idx = self.start.idx(rope);
+        rope.insert(idx, text);
+        // the rope knows every kind of line break in the text
+        let end = Pos::from_idx(idx + text.chars().count(), rope);
+        change[0] += end.col as i32 - old_col as i32;
+        change[1] += (end.row - self.start.row) as i32;
+        self.start = end;
+        change
+    }
+
     pub fn with_end(&mut self, f: impl FnOnce(&mut Pos)) {
         let mut new = self.end.take().unwrap_or(self.start.clone());
         f(&mut new);
         self.end.replace(new);
     }
 
-    fn first(&self) -> &Pos {
+    // the end of the selection that moves
+    pub fn caret(&self) -> &Pos {
+        self.end.as_ref().unwrap_or(&self.start)
+    }
+
+    pub fn first(&self) -> &Pos {
         if let Some(e) = &self.end {
             e.min(&self.start)
         } else {
@@ -320,7 +403,7 @@ impl Cursor {
         }
     }
 
-    fn last(&self) -> &Pos {
+    pub fn last(&self) -> &Pos {
         if let Some(e) = &self.end {
             e.max(&self.start)
         } else {
@@ -361,13 +444,13 @@ fn pos_direction_movement() {
     let rope = Rope::from_str(text);
 
     assert_eq!(pos.col(&rope), text.lines().nth(0).unwrap().len());
-    pos.down(&rope);
+    pos.down(&rope, &Folds::default());
     assert_eq!(pos.col(&rope), text.lines().nth(1).unwrap().len());
-    pos.up(&rope);
+    pos.up(&rope, &Folds::default());
     assert_eq!(pos.col(&rope), text.lines().nth(0).unwrap().len());
-    pos.left(&rope);
+    pos.left(&rope, &Folds::default());
     assert_eq!(pos.col(&rope), text.lines().nth(0).unwrap().len() - 1);
-    pos.right(&rope);
+    pos.right(&rope, &Folds::default());
     assert_eq!(pos.col(&rope), text.lines().nth(0).unwrap().len());
 }
 
@@ -378,20 +461,20 @@ fn pos_col_movement() {
     let rope = Rope::from_str(text);
 
     // move inside a row
-    pos.move_col(-5, &rope);
+    pos.move_col(-5, &rope, &Folds::default());
     assert_eq!(pos.col(&rope), text.lines().nth(0).unwrap().len() - 5);
-    pos.move_col(5, &rope);
+    pos.move_col(5, &rope, &Folds::default());
     assert_eq!(pos.col(&rope), text.lines().nth(0).unwrap().len());
 
     // move between rows
-    pos.move_col(3, &rope);
+    pos.move_col(3, &rope, &Folds::default());
     assert_eq!(pos.col(&rope), 2);
-    pos.move_col(-3, &rope);
+    pos.move_col(-3, &rope, &Folds::default());
     assert_eq!(pos.col(&rope), text.lines().nth(0).unwrap().len());
 
     // don't panic if moving out of range
-    pos.move_col(-100, &rope);
-    pos.move_col(1000, &rope);
+    pos.move_col(-100, &rope, &Folds::default());
+    pos.move_col(1000, &rope, &Folds::default());
 }
 
 #[test]
@@ -400,14 +483,74 @@ fn cursor_row_movement() {
     let text = "hello world\nhi";
     let rope = Rope::from_str(text);
 
-    pos.move_row(1, &rope);
+    pos.move_row(1, &rope, &Folds::default());
     assert_eq!(pos.row(), 1);
-    pos.move_row(-1, &rope);
+    pos.move_row(-1, &rope, &Folds::default());
     assert_eq!(pos.row(), 0);
 
     // don't panic if moving out of range
-    pos.move_row(-100, &rope);
-    pos.move_row(1000, &rope);
+    pos.move_row(-100, &rope, &Folds::default());
+    pos.move_row(1000, &rope, &Folds::default());
+}
+
+#[test]
+fn cursor_row_movement_skips_folds() {
+    let mut pos = Pos::new(0, 0);
+    let text = "a {\n  b\n  c\n}";
+    let rope = Rope::from_str(text);
+    let folds = Folds(vec![FoldRange::new(0, 2)]);
+
+    pos.down(&rope, &folds);
+    assert_eq!(pos.row(), 3);
+    pos.up(&rope, &folds);
+    assert_eq!(pos.row(), 0);
+
+    // moving past the end of the first line skips the folded lines
+    pos.move_col(4, &rope, &folds);
+    assert_eq!(pos, Pos::new(0, 3));
+}
+
+#[test]
+fn wrapped_movement() {
+    let mut pos = Pos::new(2, 0);
+    let text = "hello world\nhi there";
+    let rope = Rope::from_str(text);
+    let folds = Folds::default();
+    let layout = Layout::new(&folds, Some(8));
+
+    pos.move_visual_row(1, &rope, &layout);
+    assert_eq!(pos, Pos::new(8, 0));
+    pos.move_visual_row(1, &rope, &layout);
+    assert_eq!(pos, Pos::new(2, 1));
+    pos.move_visual_row(-1, &rope, &layout);
+    assert_eq!(pos, Pos::new(8, 0));
+
+    pos.move_home(&rope, &layout);
+    assert_eq!(pos, Pos::new(6, 0));
+    pos.move_home(&rope, &layout);
+    assert_eq!(pos, Pos::new(0, 0));
+    pos.move_end(&rope, &layout);
+    assert_eq!(pos, Pos::new(5, 0));
+    pos.move_end(&rope, &layout);
+    assert_eq!(pos, Pos::new(11, 0));
+}
+
+#[test]
+fn select_lines() {
+    let rope = Rope::from_str("hello world\nhi\nthere");
+
+    assert_eq!(
+        Cursor::select_lines(0, 1, &rope),
+        Cursor::new(Pos::new(0, 0), Pos::new(0, 2))
+    );
+    assert_eq!(
+        Cursor::select_lines(2, 1, &rope),
+        Cursor::new(Pos::new(5, 2), Pos::new(0, 1))
+    );
+    assert_eq!(
+        Cursor::select_lines(2, 2, &rope),
+        Cursor::new(Pos::new(0, 2), Pos::new(5, 2))
+    );
 }
 
 #[test]
@@ -417,41 +560,11 @@ fn cursor_input() {
     let mut rope = Rope::from_str(text);
 
     for _ in 0..5 {
-        cursor.handle_input(
-            &dioxus_html::on::KeyboardData {
-                char_code: 0,
-                key: "".to_string(),
-                key_code: dioxus_html::KeyCode::RightArrow,
-                alt_key: false,
-                ctrl_key: false,
-                meta_key: false,
-                shift_key: false,
-                locale: "".to_string(),
-                location: 0,
-                repeat: false,
-                which: 0,
-            },
-            &mut rope,
-        );
+        cursor.move_by(Motion::Right, false, &rope, &Layout::default());
     }
 
     for _ in 0..5 {
-        cursor.handle_input(
-            &dioxus_html::on::KeyboardData {
-                char_code: 0,
-                key: "".to_string(),
-                key_code: dioxus_html::KeyCode::Backspace,
-                alt_key: false,
-                ctrl_key: false,
-                meta_key: false,
-                shift_key: false,
-                locale: "".to_string(),
-                location: 0,
-                repeat: false,
-                which: 0,
-            },
-            &mut rope,
-        );
+        cursor.backspace(&mut rope);
     }
 
     assert_eq!(rope.to_string(), " world\nhi");
@@ -543,3 +656,30 @@ fn cursor_input() {
 
     assert_eq!(rope.to_string(), "hello world\nhi");
 }
+
+#[test]
+fn crlf_line_breaks() {
+    let mut rope = Rope::from_str("ab\r\ncd\re");
+    let folds = Folds::default();
+    let mut pos = Pos::line_end(0, &rope);
+    assert_eq!(pos.col(&rope), 2);
+    // the line break is a single step
+    pos.right(&rope, &folds);
+    assert_eq!(pos, Pos::new(0, 1));
+    pos.left(&rope, &folds);
+    assert_eq!(pos, Pos::new(2, 0));
+    assert_eq!(Pos::line_end(1, &rope).col(&rope), 2);
+
+    // deleting at the start of a line removes the whole line break
+    let mut cursor = Cursor::from_start(Pos::new(0, 1));
+    cursor.backspace(&mut rope);
+    assert_eq!(rope.to_string(), "abcd\re");
+    assert_eq!(cursor.start, Pos::new(2, 0));
+
+    cursor.newline("\r\n", &mut rope);
+    assert_eq!(rope.to_string(), "ab\r\ncd\re");
+    assert_eq!(cursor.start, Pos::new(0, 1));
+    cursor.insert_text("x\r\ny", &mut rope);
+    assert_eq!(rope.to_string(), "ab\r\nx\r\nycd\re");
+    assert_eq!(cursor.start, Pos::new(1, 2));
+}
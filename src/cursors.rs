@@ -1,8 +1,13 @@
 use dioxus::events::KeyboardData;
 use dioxus_html::KeyCode;
 use ropey::Rope;
+use serde::{Deserialize, Serialize};
 
-use crate::cursor::{Cursor, Pos};
+use crate::cursor::{Cursor, Motion, Pos};
+use crate::diff::follow_edits;
+use crate::fold::Folds;
+use crate::layout::Layout;
+use crate::word::word_start;
 use std::cmp::Ordering;
 
 #[derive(Debug, PartialEq, Eq, Clone, PartialOrd, Ord)]
@@ -16,17 +21,24 @@ pub struct SelectionMarker<'a> {
     pub pos: &'a Pos,
     pub marker_type: SelectionMarkerType,
     id: usize,
+    primary: bool,
 }
 
 impl<'a> SelectionMarker<'a> {
-    fn new(pos: &'a Pos, id: usize, selection_type: SelectionMarkerType) -> Self {
+    fn new(pos: &'a Pos, id: usize, primary: bool, selection_type: SelectionMarkerType) -> Self {
         Self {
             pos,
             id,
+            primary,
             marker_type: selection_type,
         }
     }
 
+    /// If the marker belongs to the primary cursor.
+    pub fn is_primary(&self) -> bool {
+        self.primary
+    }
+
     fn is_matching(&self, other: &'a SelectionMarker) -> bool {
         self.marker_type != other.marker_type && self.id == other.id
     }
@@ -46,26 +58,110 @@ impl<'a> PartialOrd for SelectionMarker<'a> {
     }
 }
 
-#[derive(Debug, Clone, PartialEq, Eq)]
-pub struct Cursors(pub Vec<Cursor>);
+/// The cursors of a view in the order of their position, there is always at least one.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(from = "SavedCursors")]
+pub struct Cursors {
+    pub list: Vec<Cursor>,
+    // the index of the cursor that was placed last
+    pub primary: usize,
+}
+
+// the cursors as they are saved, a saved list can be empty
+#[derive(Deserialize)]
+struct SavedCursors {
+    list: Vec<Cursor>,
+    primary: usize,
+}
+
+impl From<SavedCursors> for Cursors {
+    fn from(saved: SavedCursors) -> Self {
+        Self {
+            primary: saved.primary,
+            ..Cursors::new(saved.list)
+        }
+    }
+}
 
 impl Cursors {
-    pub fn process_input(&mut self, keyboard_data: &KeyboardData, rope: &mut Rope) {
-        let mut old = if keyboard_data.alt_key {
-            self.0.clone()
+    /// Cursors whose first cursor is the primary one, a cursor at the start of the text if the list is empty.
+    pub fn new(list: Vec<Cursor>) -> Self {
+        let list = if list.is_empty() {
+            vec![Cursor::default()]
         } else {
-            Vec::new()
+            list
         };
+        Self { list, primary: 0 }
+    }
+
+    /// Types the character of a key press at every cursor.
+    pub fn process_input(&mut self, keyboard_data: &KeyboardData, rope: &mut Rope) {
+        self.apply(|c, rope| c.handle_input(keyboard_data, rope), rope);
+
+        self.remove_overlaping();
+    }
+
+    /// Moves every cursor, spawn keeps the old cursors in place to add a cursor.
+    pub fn move_by(
+        &mut self,
+        motion: Motion,
+        select: bool,
+        spawn: bool,
+        rope: &Rope,
+        layout: &Layout,
+    ) {
+        let mut old = if spawn { self.list.clone() } else { Vec::new() };
+
+        for c in self.list.iter_mut() {
+            c.move_by(motion, select, rope, layout);
+        }
+
+        self.list.append(&mut old);
+
+        self.remove_overlaping();
+    }
+
+    pub fn backspace(&mut self, rope: &mut Rope) {
+        self.apply(|c, rope| c.backspace(rope), rope);
+
+        self.remove_overlaping();
+    }
+
+    pub fn newline(&mut self, line_ending: &str, rope: &mut Rope) {
+        self.apply(|c, rope| c.newline(line_ending, rope), rope);
+
+        self.remove_overlaping();
+    }
+
+    /// Replaces the selection of every cursor with text.
+    pub fn insert_text(&mut self, text: &str, rope: &mut Rope) {
+        self.apply(|c, rope| c.insert_text(text, rope), rope);
 
+        self.remove_overlaping();
+    }
+
+    /// Replaces the word chars before every cursor with text, like the typed part of a completion.
+    /// Cursors with a selection replace the selection.
+    pub fn replace_word_before(&mut self, text: &str, rope: &mut Rope) {
+        for c in self.list.iter_mut().filter(|c| c.end.is_none()) {
+            let caret = c.caret().clone();
+            let start = word_start(caret.idx(rope), rope);
+            *c = Cursor::new(Pos::from_idx(start, rope), caret);
+        }
+        self.insert_text(text, rope);
+    }
+
+    // run an edit on each cursor, moving the cursors after it to account for the change
+    fn apply(&mut self, mut f: impl FnMut(&mut Cursor, &mut Rope) -> [i32; 2], rope: &mut Rope) {
         let mut row = 0;
         let mut new_rows = 0;
         let mut new_chars = 0;
-        for c in self.0.iter_mut() {
+        for c in self.list.iter_mut() {
             let r = c.start.row();
             if new_rows != 0 {
-                c.start.move_row(new_rows, rope);
+                c.start.move_row(new_rows, rope, &Folds::default());
                 if let Some(e) = &mut c.end {
-                    e.move_row(new_rows, rope);
+                    e.move_row(new_rows, rope, &Folds::default());
                 }
             }
             if r <= row {
@@ -79,21 +175,21 @@ impl Cursors {
                 row = r;
                 new_chars = 0;
             }
-            let [dc, dr] = c.handle_input(&keyboard_data, rope);
+            let [dc, dr] = f(c, rope);
             new_rows += dr;
             new_chars += dc;
         }
-
-        self.0.append(&mut old);
-
-        self.remove_overlaping();
     }
 
     fn remove_overlaping(&mut self) {
         let mut new: Vec<Cursor> = Vec::new();
         let mut open = Vec::new();
         let mut cursor_first = None;
+        // the merged cursor that the primary cursor is part of stays primary
+        let mut primary = 0;
+        let mut has_primary = false;
         for s in self.sorted() {
+            has_primary |= s.is_primary();
             match &cursor_first {
                 None => {
                     open = vec![s.clone()];
@@ -110,6 +206,10 @@ impl Cursors {
                         open.push(s)
                     } else {
                         if open.is_empty() {
+                            if has_primary {
+                                primary = new.len();
+                                has_primary = false;
+                            }
                             match first.marker_type {
                                 SelectionMarkerType::Start => new.push(Cursor {
                                     start: first.pos.clone(),
@@ -133,25 +233,72 @@ impl Cursors {
                 }
             }
         }
-        self.0 = new;
+        self.list = new;
+        self.primary = primary;
+    }
+
+    /// Moves any cursor inside of a fold to the end of the fold's first line.
+    pub fn move_out_of_folds(&mut self, folds: &Folds, rope: &Rope) {
+        let num_lines = rope.len_lines();
+        for c in self.list.iter_mut() {
+            let first = c.first().row();
+            let last = c.last().row();
+            if folds.is_hidden(first) || folds.is_hidden(last) {
+                let header = folds.visible_row(first, false, num_lines).unwrap_or(first);
+                *c = Cursor::from_start(Pos::line_end(header, rope));
+            }
+        }
+        self.remove_overlaping();
+    }
+
+    /// Moves the cursors to follow edits made somewhere else, like another view of the same buffer, a reload or formatting.
+    /// Cursors inside of the changed text are moved to its end.
+    pub fn update(&mut self, old: &Rope, new: &Rope) {
+        let f = follow_edits(old, new);
+        for c in self.list.iter_mut() {
+            c.start = Pos::from_idx(f(c.start.idx(old)), new);
+            if let Some(end) = &mut c.end {
+                *end = Pos::from_idx(f(end.idx(old)), new);
+            }
+        }
+        self.remove_overlaping();
+    }
+
+    pub fn primary(&self) -> &Cursor {
+        &self.list[self.primary.min(self.list.len() - 1)]
+    }
+
+    /// The number of selected chars and lines across every cursor.
+    pub fn selection_len(&self, rope: &Rope) -> (usize, usize) {
+        self.list
+            .iter()
+            .filter(|c| c.end.is_some())
+            .fold((0, 0), |(chars, lines), c| {
+                let (first, last) = (c.first(), c.last());
+                (
+                    chars + last.idx(rope) - first.idx(rope),
+                    lines + last.row() - first.row() + 1,
+                )
+            })
     }
 
     pub fn sorted(&self) -> Vec<SelectionMarker> {
         let mut v: Vec<_> = self
-            .0
+            .list
             .iter()
             .enumerate()
             .flat_map(|(i, c)| {
+                let primary = i == self.primary;
                 if let Some(e) = &c.end {
                     vec![
-                        SelectionMarker::new(&c.start, i, SelectionMarkerType::Start),
-                        SelectionMarker::new(e, i, SelectionMarkerType::End),
+                        SelectionMarker::new(&c.start, i, primary, SelectionMarkerType::Start),
+                        SelectionMarker::new(e, i, primary, SelectionMarkerType::End),
                     ]
                     .into_iter()
                 } else {
                     vec![
-                        SelectionMarker::new(&c.start, i, SelectionMarkerType::Start),
-                        SelectionMarker::new(&c.start, i, SelectionMarkerType::End),
+                        SelectionMarker::new(&c.start, i, primary, SelectionMarkerType::Start),
+                        SelectionMarker::new(&c.start, i, primary, SelectionMarkerType::End),
                     ]
                     .into_iter()
                 }
@@ -164,13 +311,13 @@ impl Cursors {
 
 impl Default for Cursors {
     fn default() -> Self {
-        Cursors(vec![Cursor::default()])
+        Cursors::new(Vec::new())
     }
 }
 
 #[test]
 fn remove_overlaping_cursors() {
-    let mut cursors = Cursors(vec![
+    let mut cursors = Cursors::new(vec![
         Cursor::new(Pos::new(0, 0), Pos::new(0, 1)),
         Cursor::from_start(Pos::new(1, 0)),
     ]);
@@ -179,10 +326,10 @@ fn remove_overlaping_cursors() {
 
     assert_eq!(
         cursors,
-        Cursors(vec![Cursor::new(Pos::new(0, 0), Pos::new(0, 1))])
+        Cursors::new(vec![Cursor::new(Pos::new(0, 0), Pos::new(0, 1))])
     );
 
-    let mut cursors = Cursors(vec![
+    let mut cursors = Cursors::new(vec![
         Cursor::new(Pos::new(0, 1), Pos::new(0, 0)),
         Cursor::from_start(Pos::new(1, 0)),
     ]);
@@ -191,10 +338,10 @@ fn remove_overlaping_cursors() {
 
     assert_eq!(
         cursors,
-        Cursors(vec![Cursor::new(Pos::new(0, 1), Pos::new(0, 0))])
+        Cursors::new(vec![Cursor::new(Pos::new(0, 1), Pos::new(0, 0))])
     );
 
-    let mut cursors = Cursors(vec![
+    let mut cursors = Cursors::new(vec![
         Cursor::new(Pos::new(0, 0), Pos::new(0, 1)),
         Cursor::new(Pos::new(1, 0), Pos::new(0, 2)),
     ]);
@@ -203,19 +350,22 @@ fn remove_overlaping_cursors() {
 
     assert_eq!(
         cursors,
-        Cursors(vec![Cursor::new(Pos::new(0, 0), Pos::new(0, 2)),])
+        Cursors::new(vec![Cursor::new(Pos::new(0, 0), Pos::new(0, 2)),])
     );
 
-    let mut cursors = Cursors(vec![
+    let mut cursors = Cursors::new(vec![
         Cursor::from_start(Pos::new(0, 0)),
         Cursor::from_start(Pos::new(0, 0)),
     ]);
 
     cursors.remove_overlaping();
 
-    assert_eq!(cursors, Cursors(vec![Cursor::from_start(Pos::new(0, 0))]));
+    assert_eq!(
+        cursors,
+        Cursors::new(vec![Cursor::from_start(Pos::new(0, 0))])
+    );
 
-    let mut cursors = Cursors(vec![
+    let mut cursors = Cursors::new(vec![
         Cursor::new(Pos::new(0, 0), Pos::new(0, 1)),
         Cursor::new(Pos::new(1, 1), Pos::new(0, 2)),
     ]);
@@ -224,16 +374,59 @@ fn remove_overlaping_cursors() {
 
     assert_eq!(
         cursors,
-        Cursors(vec![
+        Cursors::new(vec![
             Cursor::new(Pos::new(0, 0), Pos::new(0, 1)),
             Cursor::new(Pos::new(1, 1), Pos::new(0, 2)),
         ])
     );
 }
 
+#[test]
+fn multicursor_insert_text() {
+    let mut cursors = Cursors::new(vec![
+        Cursor::from_start(Pos::new(1, 0)),
+        Cursor::new(Pos::new(3, 0), Pos::new(5, 0)),
+    ]);
+    // h|el[lo] world
+    let mut rope = Rope::from_str("hello world");
+
+    cursors.insert_text("  ", &mut rope);
+
+    assert_eq!(rope.to_string(), "h  el   world");
+    assert_eq!(
+        cursors,
+        Cursors::new(vec![
+            Cursor::from_start(Pos::new(3, 0)),
+            Cursor::from_start(Pos::new(7, 0))
+        ])
+    );
+
+    cursors.insert_text("a\nb", &mut rope);
+
+    assert_eq!(rope.to_string(), "h  a\nbel  a\nb world");
+    assert_eq!(
+        cursors,
+        Cursors::new(vec![
+            Cursor::from_start(Pos::new(1, 1)),
+            Cursor::from_start(Pos::new(1, 2))
+        ])
+    );
+}
+
+#[test]
+fn selection_len() {
+    let rope = Rope::from_str("hello\nworld\n!");
+    let cursors = Cursors::new(vec![
+        Cursor::new(Pos::new(1, 0), Pos::new(2, 1)),
+        Cursor::from_start(Pos::new(0, 2)),
+    ]);
+
+    assert_eq!(cursors.selection_len(&rope), (7, 2));
+}
+
 #[test]
 fn multicursor_input() {
-    let mut cursors = Cursors(vec![
+    let mut cursors = Cursors::new(vec![
         Cursor::from_start(Pos::new(1, 0)),
         Cursor::from_start(Pos::new(3, 0)),
     ]);
@@ -258,75 +451,47 @@ fn multicursor_input() {
     assert_eq!(rope.to_string(), "hoelolo world");
     assert_eq!(
         cursors,
-        Cursors(vec![
+        Cursors::new(vec![
             Cursor::from_start(Pos::new(2, 0)),
             Cursor::from_start(Pos::new(5, 0))
         ])
     );
 
-    let mut cursors = Cursors(vec![
+    let mut cursors = Cursors::new(vec![
         Cursor::from_start(Pos::new(1, 0)),
         Cursor::from_start(Pos::new(3, 0)),
     ]);
     // h|el|lo world
     let mut rope = Rope::from_str("hello world");
-    let key = &KeyboardData {
-        char_code: 0,
-        key: "".to_string(),
-        key_code: dioxus_html::KeyCode::Backspace,
-        alt_key: false,
-        ctrl_key: false,
-        meta_key: false,
-        shift_key: false,
-        locale: "".to_string(),
-        location: 0,
-        repeat: false,
-        which: 0,
-    };
-
-    cursors.process_input(&key, &mut rope);
+    cursors.backspace(&mut rope);
 
     assert_eq!(rope.to_string(), "elo world");
     assert_eq!(
         cursors,
-        Cursors(vec![
+        Cursors::new(vec![
             Cursor::from_start(Pos::new(0, 0)),
             Cursor::from_start(Pos::new(1, 0))
         ])
     );
 
-    let mut cursors = Cursors(vec![
+    let mut cursors = Cursors::new(vec![
         Cursor::from_start(Pos::new(1, 0)),
         Cursor::from_start(Pos::new(3, 0)),
     ]);
     // h|el|lo world
     let mut rope = Rope::from_str("hello world");
-    let key = &KeyboardData {
-        char_code: 0,
-        key: "".to_string(),
-        key_code: dioxus_html::KeyCode::Enter,
-        alt_key: false,
-        ctrl_key: false,
-        meta_key: false,
-        shift_key: false,
-        locale: "".to_string(),
-        location: 0,
-        repeat: false,
-        which: 0,
-    };
-
-    cursors.process_input(&key, &mut rope);
+    cursors.newline("\n", &mut rope);
 
     assert_eq!(rope.to_string(), "h\nel\nlo world");
     assert_eq!(
         cursors,
-        Cursors(vec![
+        Cursors::new(vec![
             Cursor::from_start(Pos::new(0, 1)),
             Cursor::from_start(Pos::new(0, 2))
         ])
     );
 
-    let mut cursors = Cursors(vec![
+    let mut cursors = Cursors::new(vec![
         Cursor::from_start(Pos::new(5, 0)),
         Cursor::from_start(Pos::new(5, 1)),
     ]);
@@ -352,73 +517,155 @@ fn multicursor_input() {
     assert_eq!(rope.to_string(), "helloo\nworldo");
     assert_eq!(
         cursors,
-        Cursors(vec![
+        Cursors::new(vec![
             Cursor::from_start(Pos::new(6, 0)),
             Cursor::from_start(Pos::new(6, 1))
         ])
     );
 
-    let mut cursors = Cursors(vec![
+    let mut cursors = Cursors::new(vec![
         Cursor::from_start(Pos::new(5, 0)),
         Cursor::from_start(Pos::new(5, 1)),
     ]);
     // hello|
     // world|
     let mut rope = Rope::from_str("hello\nworld");
-    let key = &KeyboardData {
-        char_code: 0,
-        key: "".to_string(),
-        key_code: dioxus_html::KeyCode::Backspace,
-        alt_key: false,
-        ctrl_key: false,
-        meta_key: false,
-        shift_key: false,
-        locale: "".to_string(),
-        location: 0,
-        repeat: false,
-        which: 0,
-    };
-
-    cursors.process_input(&key, &mut rope);
+    cursors.backspace(&mut rope);
 
     assert_eq!(rope.to_string(), "hell\nworl");
     assert_eq!(
         cursors,
-        Cursors(vec![
+        Cursors::new(vec![
             Cursor::from_start(Pos::new(4, 0)),
             Cursor::from_start(Pos::new(4, 1))
         ])
     );
 
-    let mut cursors = Cursors(vec![
+    let mut cursors = Cursors::new(vec![
         Cursor::from_start(Pos::new(0, 0)),
         Cursor::from_start(Pos::new(0, 1)),
     ]);
     // |hello
     // |world
     let mut rope = Rope::from_str("hello\nworld");
-    let key = &KeyboardData {
-        char_code: 0,
-        key: "".to_string(),
-        key_code: dioxus_html::KeyCode::Backspace,
-        alt_key: false,
-        ctrl_key: false,
-        meta_key: false,
-        shift_key: false,
-        locale: "".to_string(),
-        location: 0,
-        repeat: false,
-        which: 0,
-    };
-
-    cursors.process_input(&key, &mut rope);
+    cursors.backspace(&mut rope);
 
     assert_eq!(rope.to_string(), "helloworld");
     assert_eq!(
         cursors,
-        Cursors(vec![
+        Cursors::new(vec![
             Cursor::from_start(Pos::new(0, 0)),
             Cursor::from_start(Pos::new(5, 0))
         ])
     );
 }
+
+#[test]
+fn update_after_edit_elsewhere() {
+    let old = Rope::from_str("hello\nworld");
+    let mut cursors = Cursors::new(vec![
+        Cursor::from_start(Pos::new(1, 0)),
+        Cursor::new(Pos::new(4, 0), Pos::new(2, 1)),
+    ]);
+
+    // another view inserted a line at the start
+    let new = Rope::from_str("new\nhello\nworld");
+    cursors.update(&old, &new);
+    assert_eq!(
+        cursors,
+        Cursors::new(vec![
+            Cursor::from_start(Pos::new(1, 1)),
+            Cursor::new(Pos::new(4, 1), Pos::new(2, 2)),
+        ])
+    );
+
+    // deleting the text around a cursor moves it to the end of the change
+    let old = new;
+    let new = Rope::from_str("new\nhd\nworld");
+    cursors.update(&old, &new);
+    assert_eq!(
+        cursors,
+        Cursors::new(vec![
+            Cursor::from_start(Pos::new(1, 1)),
+            Cursor::new(Pos::new(2, 1), Pos::new(2, 2)),
+        ])
+    );
+
+    // a reload that changed the lines around the cursors keeps them in their line
+    let old = new;
+    let new = Rope::from_str("changed\nhd\nworld!\n");
+    cursors.update(&old, &new);
+    assert_eq!(
+        cursors,
+        Cursors::new(vec![
+            Cursor::from_start(Pos::new(1, 1)),
+            Cursor::new(Pos::new(2, 1), Pos::new(2, 2)),
+        ])
+    );
+
+    // typing at two cursors of another view leaves the selection between them as it is
+    let old = Rope::from_str(&(0..11).map(|i| format!("line {}\n", i)).collect::<String>());
+    let mut cursors = Cursors::new(vec![Cursor::new(Pos::new(1, 4), Pos::new(3, 6))]);
+    let mut typed = Cursors::new(vec![
+        Cursor::from_start(Pos::new(0, 0)),
+        Cursor::from_start(Pos::new(0, 10)),
+    ]);
+    let mut new = old.clone();
+    typed.insert_text("new ", &mut new);
+    cursors.update(&old, &new);
+    assert_eq!(
+        cursors,
+        Cursors::new(vec![Cursor::new(Pos::new(1, 4), Pos::new(3, 6))])
+    );
+}
+
+#[test]
+fn replace_word_before_cursors() {
+    let mut cursors = Cursors::new(vec![
+        Cursor::from_start(Pos::new(2, 0)),
+        Cursor::from_start(Pos::new(8, 0)),
+        Cursor::from_start(Pos::new(1, 1)),
+    ]);
+    let mut rope = Rope::from_str("pr x.pri\np");
+
+    cursors.replace_word_before("println", &mut rope);
+
+    assert_eq!(rope.to_string(), "println x.println\nprintln");
+    assert_eq!(
+        cursors,
+        Cursors::new(vec![
+            Cursor::from_start(Pos::new(7, 0)),
+            Cursor::from_start(Pos::new(17, 0)),
+            Cursor::from_start(Pos::new(7, 1)),
+        ])
+    );
+}
+
+#[test]
+fn primary_cursor_is_the_last_placed() {
+    let rope = Rope::from_str("one\ntwo\nthree");
+    let mut cursors = Cursors::new(vec![Cursor::from_start(Pos::new(1, 0))]);
+    let folds = Folds::default();
+    let layout = Layout::new(&folds, None);
+
+    // the added cursor sorts after the first one but is the primary cursor
+    cursors.move_by(Motion::Down, false, true, &rope, &layout);
+    assert_eq!(cursors.list.len(), 2);
+    assert_eq!(cursors.primary(), &Cursor::from_start(Pos::new(1, 1)));
+    assert!(cursors
+        .sorted()
+        .iter()
+        .any(|m| m.is_primary() && m.pos.row() == 1));
+
+    // it stays primary when it is merged with another cursor
+    cursors.move_by(Motion::Up, false, false, &rope, &layout);
+    assert_eq!(cursors.list.len(), 1);
+    assert_eq!(cursors.primary(), &Cursor::from_start(Pos::new(1, 0)));
+}
+
+#[test]
+fn never_empty() {
+    assert_eq!(Cursors::new(Vec::new()), Cursors::default());
+    let saved: Cursors = serde_json::from_str(r#"{"list": [], "primary": 3}"#).unwrap();
+    assert_eq!(saved.primary(), &Cursor::default());
+}
@@ -0,0 +1,255 @@
+use lsp_types::DiagnosticSeverity;
+use ropey::Rope;
+use syntect::highlighting::Color;
+
+use crate::cursor::Pos;
+use crate::diff::follow_edits;
+use crate::lsp::to_idx;
+
+/// How serious a diagnostic is, the most severe first.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    Error,
+    Warning,
+    Information,
+    Hint,
+}
+
+impl Severity {
+    fn from_lsp(severity: Option<DiagnosticSeverity>) -> Self {
+        match severity {
+            Some(DiagnosticSeverity::WARNING) => Severity::Warning,
+            Some(DiagnosticSeverity::INFORMATION) => Severity::Information,
+            Some(DiagnosticSeverity::HINT) => Severity::Hint,
+            // servers that leave the severity out are reporting errors
+            _ => Severity::Error,
+        }
+    }
+
+    /// The icon shown in the gutter.
+    pub fn icon(&self) -> char {
+        match self {
+            Severity::Error => '✖',
+            Severity::Warning => '▲',
+            Severity::Information => 'ℹ',
+            Severity::Hint => '·',
+        }
+    }
+
+    pub fn color(&self) -> Color {
+        let (r, g, b) = match self {
+            Severity::Error => (224, 108, 117),
+            Severity::Warning => (229, 192, 123),
+            Severity::Information => (97, 175, 239),
+            Severity::Hint => (150, 150, 150),
+        };
+        Color { r, g, b, a: 255 }
+    }
+}
+
+/// A problem in a range of a buffer, reported by a language server or another checker.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+    pub start: Pos,
+    pub end: Pos,
+    pub severity: Severity,
+    pub message: String,
+    // the tool that reported it, like rustc or clippy
+    pub source: Option<String>,
+}
+
+impl Diagnostic {
+    fn from_lsp(rope: &Rope, diagnostic: &lsp_types::Diagnostic) -> Self {
+        Self {
+            start: Pos::from_idx(to_idx(rope, diagnostic.range.start), rope),
+            end: Pos::from_idx(to_idx(rope, diagnostic.range.end), rope),
+            severity: Severity::from_lsp(diagnostic.severity),
+            message: diagnostic.message.clone(),
+            source: diagnostic.source.clone(),
+        }
+    }
+
+    /// The first line of the message with the source in front of it.
+    pub fn summary(&self) -> String {
+        let message = self.message.lines().next().unwrap_or_default();
+        match &self.source {
+            Some(source) => format!("{}: {}", source, message),
+            None => message.to_string(),
+        }
+    }
+}
+
+/// The diagnostics of a buffer sorted by position, they move with edits until they are reported again.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Diagnostics(pub Vec<Diagnostic>);
+
+impl Diagnostics {
+    pub fn new(mut diagnostics: Vec<Diagnostic>) -> Self {
+        diagnostics.sort_by(|a, b| (&a.start, a.severity).cmp(&(&b.start, b.severity)));
+        Self(diagnostics)
+    }
+
+    pub fn from_lsp(rope: &Rope, diagnostics: &[lsp_types::Diagnostic]) -> Self {
+        Self::new(
+            diagnostics
+                .iter()
+                .map(|d| Diagnostic::from_lsp(rope, d))
+                .collect(),
+        )
+    }
+
+    pub fn count(&self, severity: Severity) -> usize {
+        self.0.iter().filter(|d| d.severity == severity).count()
+    }
+
+    /// The most severe diagnostic starting on a row.
+    pub fn on_row(&self, row: usize) -> Option<&Diagnostic> {
+        self.0
+            .iter()
+            .filter(|d| d.start.row() == row)
+            .min_by_key(|d| d.severity)
+    }
+
+    /// The first diagnostic after a position, wrapping around to the first one.
+    pub fn next(&self, pos: &Pos) -> Option<&Diagnostic> {
+        self.0
+            .iter()
+            .find(|d| d.start > *pos)
+            .or_else(|| self.0.first())
+    }
+
+    /// The last diagnostic before a position, wrapping around to the last one.
+    pub fn previous(&self, pos: &Pos) -> Option<&Diagnostic> {
+        self.0
+            .iter()
+            .rev()
+            .find(|d| d.start < *pos)
+            .or_else(|| self.0.last())
+    }
+
+    /// The underlined chars of each diagnostic, empty ranges are widened to a char next to them on the same line.
+    pub fn ranges(&self, rope: &Rope) -> Vec<(usize, usize, Severity)> {
+        let len = rope.len_chars();
+        self.0
+            .iter()
+            .map(|d| {
+                let start = d.start.idx(rope).min(len);
+                let end = d.end.idx(rope).clamp(start, len);
+                if start < end {
+                    return (start, end, d.severity);
+                }
+                let line_end = Pos::line_end(d.start.row(), rope).idx(rope);
+                if start < line_end {
+                    (start, start + 1, d.severity)
+                } else {
+                    (
+                        start
+                            .saturating_sub(1)
+                            .max(rope.line_to_char(d.start.row())),
+                        start,
+                        d.severity,
+                    )
+                }
+            })
+            .collect()
+    }
+
+    /// Moves the diagnostics to follow edits, diagnostics inside of the changed text shrink to its end.
+    pub fn update(&mut self, old: &Rope, new: &Rope) {
+        let f = follow_edits(old, new);
+        let shift = |pos: &mut Pos| *pos = Pos::from_idx(f(pos.idx(old)), new);
+        for d in self.0.iter_mut() {
+            shift(&mut d.start);
+            shift(&mut d.end);
+        }
+    }
+}
+
+#[test]
+fn diagnostics_from_lsp() {
+    use lsp_types::{Position, Range};
+
+    let rope = Rope::from_str("let a = 1;\nlet b = a +;\n");
+    let lsp = |line, start, end, severity| lsp_types::Diagnostic {
+        range: Range::new(Position::new(line, start), Position::new(line, end)),
+        severity,
+        message: "message\nnote".to_string(),
+        ..Default::default()
+    };
+    let diagnostics = Diagnostics::from_lsp(
+        &rope,
+        &[
+            lsp(1, 10, 10, None),
+            lsp(0, 4, 5, Some(DiagnosticSeverity::WARNING)),
+            lsp(1, 4, 5, Some(DiagnosticSeverity::HINT)),
+        ],
+    );
+    assert_eq!(diagnostics.0[0].start, Pos::new(4, 0));
+    assert_eq!(diagnostics.0[0].severity, Severity::Warning);
+    assert_eq!(diagnostics.on_row(1).unwrap().severity, Severity::Error);
+    assert_eq!(diagnostics.count(Severity::Error), 1);
+    assert_eq!(diagnostics.0[0].summary(), "message");
+    // the empty range of the error is widened to the char after it
+    assert_eq!(
+        diagnostics.ranges(&rope),
+        vec![
+            (4, 5, Severity::Warning),
+            (15, 16, Severity::Hint),
+            (21, 22, Severity::Error),
+        ]
+    );
+
+    assert_eq!(
+        diagnostics.next(&Pos::new(4, 0)).unwrap().start,
+        Pos::new(4, 1)
+    );
+    assert_eq!(
+        diagnostics.next(&Pos::new(11, 1)).unwrap().start,
+        Pos::new(4, 0)
+    );
+    assert_eq!(
+        diagnostics.previous(&Pos::new(4, 0)).unwrap().start,
+        Pos::new(10, 1)
+    );
+}
+
+#[test]
+fn update_diagnostics_after_edit() {
+    let old = Rope::from_str("let a = 1;\nlet b = a +;\n");
+    let diagnostic = |start, end| Diagnostic {
+        start,
+        end,
+        severity: Severity::Error,
+        message: String::new(),
+        source: None,
+    };
+    let mut diagnostics = Diagnostics::new(vec![
+        diagnostic(Pos::new(4, 0), Pos::new(5, 0)),
+        diagnostic(Pos::new(8, 1), Pos::new(11, 1)),
+    ]);
+    // a line inserted above moves the second diagnostic down
+    let new = Rope::from_str("let a = 1;\n\nlet b = a +;\n");
+    diagnostics.update(&old, &new);
+    assert_eq!(diagnostics.0[0].start, Pos::new(4, 0));
+    assert_eq!(diagnostics.0[1].start, Pos::new(8, 2));
+    assert_eq!(diagnostics.0[1].end, Pos::new(11, 2));
+
+    // text typed at the start of a diagnostic is added to it
+    let old = new;
+    let new = Rope::from_str("let xya = 1;\n\nlet b = a +;\n");
+    diagnostics.update(&old, &new);
+    assert_eq!(diagnostics.0[0].start, Pos::new(4, 0));
+    assert_eq!(diagnostics.0[0].end, Pos::new(7, 0));
+
+    // typing at cursors above and below a diagnostic leaves it as it is
+    let old = new;
+    let new = Rope::from_str("// let xya = 1;\n\n// let b = a +;\n");
+    diagnostics.update(&old, &new);
+    assert_eq!(diagnostics.0[0].start, Pos::new(7, 0));
+    assert_eq!(diagnostics.0[0].end, Pos::new(10, 0));
+    let old = Rope::from_str("a\nlet b = a +;\nc\n");
+    let mut diagnostics = Diagnostics::new(vec![diagnostic(Pos::new(8, 1), Pos::new(11, 1))]);
+    diagnostics.update(&old, &Rope::from_str("ab\nlet b = a +;\ncb\n"));
+    assert_eq!(diagnostics.0[0].start, Pos::new(8, 1));
+    assert_eq!(diagnostics.0[0].end, Pos::new(11, 1));
+}
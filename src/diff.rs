@@ -0,0 +1,236 @@
+use std::ops::Range;
+
+use ropey::Rope;
+
+use crate::utils::changed_chars;
+
+// texts that need more edits than this, in lines removed or added, are compared as a single hunk
+const MAX_EDITS: usize = 2000;
+// the number of unchanged lines shown around each hunk of a unified diff
+const CONTEXT: usize = 3;
+
+/// Lines of the old text that were replaced by lines of the new text.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Hunk {
+    pub old: Range<usize>,
+    pub new: Range<usize>,
+}
+
+fn lines(rope: &Rope) -> Vec<String> {
+    rope.lines().map(|l| l.to_string()).collect()
+}
+
+// the hunks of the shortest edit between two sequences, or None if it takes more than MAX_EDITS
+fn shortest_edit<T: PartialEq>(a: &[T], b: &[T]) -> Option<Vec<Hunk>> {
+    let (n, m) = (a.len() as isize, b.len() as isize);
+    let max = MAX_EDITS as isize;
+    // the furthest x reached on each diagonal k = x - y, offset by max + 1
+    let mut v = vec![0isize; 2 * max as usize + 3];
+    let at = |k: isize| (k + max + 1) as usize;
+    // the diagonals before each round, used to walk back along the shortest edit
+    let mut trace = Vec::new();
+    let mut end = None;
+    'rounds: for d in 0..=max {
+        trace.push(v.clone());
+        for k in (-d..=d).step_by(2) {
+            let mut x = if k == -d || (k != d && v[at(k - 1)] < v[at(k + 1)]) {
+                v[at(k + 1)]
+            } else {
+                v[at(k - 1)] + 1
+            };
+            let mut y = x - k;
+            while x < n && y < m && a[x as usize] == b[y as usize] {
+                x += 1;
+                y += 1;
+            }
+            v[at(k)] = x;
+            if x >= n && y >= m {
+                end = Some(d);
+                break 'rounds;
+            }
+        }
+    }
+    let (mut x, mut y) = (n, m);
+    let mut hunks: Vec<Hunk> = Vec::new();
+    for d in (1..=end?).rev() {
+        let v = &trace[d as usize];
+        let k = x - y;
+        let prev_k = if k == -d || (k != d && v[at(k - 1)] < v[at(k + 1)]) {
+            k + 1
+        } else {
+            k - 1
+        };
+        let prev_x = v[at(prev_k)];
+        let prev_y = prev_x - prev_k;
+        while x > prev_x && y > prev_y {
+            x -= 1;
+            y -= 1;
+        }
+        // one line was removed or added before the unchanged lines
+        let (old, new) = (prev_x as usize..x as usize, prev_y as usize..y as usize);
+        match hunks.last_mut() {
+            Some(last) if last.old.start == old.end && last.new.start == new.end => {
+                last.old.start = old.start;
+                last.new.start = new.start;
+            }
+            _ => hunks.push(Hunk { old, new }),
+        }
+        x = prev_x;
+        y = prev_y;
+    }
+    hunks.reverse();
+    Some(hunks)
+}
+
+/// The lines that differ between two texts, in order.
+/// Only the lines between the first and the last changed char are compared, so small edits are quick to compare.
+pub fn diff_lines(old: &Rope, new: &Rope) -> Vec<Hunk> {
+    let (start, old_end, new_end) = changed_chars(old, new);
+    if start == old_end && start == new_end {
+        return Vec::new();
+    }
+    let first = old.char_to_line(start);
+    let old_last = (old.char_to_line(old_end) + 1).min(old.len_lines());
+    let new_last = (new.char_to_line(new_end) + 1).min(new.len_lines());
+    let a: Vec<_> = (first..old_last).map(|row| old.line(row)).collect();
+    let b: Vec<_> = (first..new_last).map(|row| new.line(row)).collect();
+    match shortest_edit(&a, &b) {
+        Some(hunks) => hunks
+            .into_iter()
+            .map(|h| Hunk {
+                old: first + h.old.start..first + h.old.end,
+                new: first + h.new.start..first + h.new.end,
+            })
+            .collect(),
+        None => vec![Hunk {
+            old: first..old_last,
+            new: first..new_last,
+        }],
+    }
+}
+
+/// Where a char of the old text is in the new text.
+/// Chars in unchanged lines keep their column, chars inside of a change move to the end of the change.
+pub fn follow(old: &Rope, new: &Rope, hunks: &[Hunk], idx: usize) -> usize {
+    let row = old.char_to_line(idx);
+    let mut new_row = row;
+    for hunk in hunks {
+        if row < hunk.old.start {
+            break;
+        }
+        if row < hunk.old.end {
+            let old_start = old.line_to_char(hunk.old.start);
+            let new_start = new.line_to_char(hunk.new.start);
+            let (prefix, changed_end, end) = changed_chars(
+                &old.slice(old_start..old.line_to_char(hunk.old.end)).into(),
+                &new.slice(new_start..new.line_to_char(hunk.new.end)).into(),
+            );
+            let offset = idx - old_start;
+            return new_start
+                + if offset <= prefix {
+                    offset
+                } else if offset >= changed_end {
+                    offset + end - changed_end
+                } else {
+                    end
+                };
+        }
+        new_row = row + hunk.new.end - hunk.old.end;
+    }
+    new.line_to_char(new_row) + idx - old.line_to_char(row)
+}
+
+/// Moves char indexes of the old text to the new text, after edits in one or more places like typing at several cursors.
+/// Chars in unchanged lines keep their column, chars inside of a change move to the end of the change.
+pub fn follow_edits<'a>(old: &'a Rope, new: &'a Rope) -> impl Fn(usize) -> usize + 'a {
+    let hunks = diff_lines(old, new);
+    move |idx| follow(old, new, &hunks, idx)
+}
+
+// a line of a unified diff, the empty line after a final line break is not shown
+fn push_line(text: &mut String, prefix: char, line: &str) {
+    if !line.is_empty() {
+        text.push(prefix);
+        text.push_str(line.trim_end_matches(&['\r', '\n'][..]));
+        text.push('\n');
+    }
+}
+
+/// A unified diff between two texts, with a few unchanged lines around each hunk.
+pub fn unified(old_name: &str, new_name: &str, old: &Rope, new: &Rope) -> String {
+    let hunks = diff_lines(old, new);
+    let (a, b) = (lines(old), lines(new));
+    let shown = a.len() - a.last().map_or(false, |l| l.is_empty()) as usize;
+    let mut text = format!("--- {}\n+++ {}\n", old_name, new_name);
+    let mut rest = &hunks[..];
+    while let Some(first) = rest.first() {
+        // hunks close to each other share their context
+        let count = 1 + rest
+            .windows(2)
+            .take_while(|w| w[1].old.start - w[0].old.end <= 2 * CONTEXT)
+            .count();
+        let (group, next) = rest.split_at(count);
+        rest = next;
+        let last = group.last().unwrap();
+        let old_start = first.old.start.saturating_sub(CONTEXT);
+        let old_end = (last.old.end + CONTEXT).min(shown).max(last.old.end);
+        let new_start = first.new.start - (first.old.start - old_start);
+        let new_end = last.new.end + (old_end - last.old.end);
+        text.push_str(&format!(
+            "@@ -{},{} +{},{} @@\n",
+            old_start + 1,
+            old_end - old_start,
+            new_start + 1,
+            new_end - new_start
+        ));
+        let mut row = old_start;
+        for hunk in group {
+            for line in &a[row..hunk.old.start] {
+                push_line(&mut text, ' ', line);
+            }
+            for line in &a[hunk.old.clone()] {
+                push_line(&mut text, '-', line);
+            }
+            for line in &b[hunk.new.clone()] {
+                push_line(&mut text, '+', line);
+            }
+            row = hunk.old.end;
+        }
+        for line in &a[row..old_end] {
+            push_line(&mut text, ' ', line);
+        }
+    }
+    text
+}
+
+#[test]
+fn diff_texts() {
+    let old = Rope::from_str("a\nb\nc\nd\ne\n");
+    let new = Rope::from_str("a\nB\nc\nd\nx\ne\n");
+    assert_eq!(
+        diff_lines(&old, &new),
+        vec![
+            Hunk {
+                old: 1..2,
+                new: 1..2
+            },
+            Hunk {
+                old: 4..4,
+                new: 4..5
+            },
+        ]
+    );
+    assert_eq!(diff_lines(&old, &old), Vec::new());
+    let hunks = diff_lines(&old, &new);
+    // the d after the first change keeps its column
+    assert_eq!(follow(&old, &new, &hunks, 6), 6);
+    // e moves down a line
+    assert_eq!(follow(&old, &new, &hunks, 8), 10);
+    // inside of the change of b
+    assert_eq!(follow(&old, &new, &hunks, 2), 2);
+    assert_eq!(follow(&old, &new, &hunks, 10), 12);
+    assert_eq!(
+        unified("disk", "buffer", &old, &new),
+        "--- disk\n+++ buffer\n@@ -1,5 +1,6 @@\n a\n-b\n+B\n c\n d\n+x\n e\n"
+    );
+}
@@ -0,0 +1,195 @@
+use std::fmt::{self, Display};
+
+use serde::{Deserialize, Serialize};
+
+// the number of bytes looked at to guess if a file without a byte order mark is UTF-16
+const SAMPLE: usize = 4096;
+
+/// The encoding of a file, its text is decoded when it is opened and encoded again when it is saved.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Encoding {
+    Utf8,
+    // UTF-8 that starts with a byte order mark
+    Utf8Bom,
+    Utf16Le,
+    Utf16LeBom,
+    Utf16Be,
+    Utf16BeBom,
+    Latin1,
+}
+
+impl Default for Encoding {
+    fn default() -> Self {
+        Encoding::Utf8
+    }
+}
+
+/// The text of a file, valid is false if the file has sequences that are invalid in its encoding.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Decoded {
+    pub text: String,
+    pub encoding: Encoding,
+    pub valid: bool,
+}
+
+impl Encoding {
+    pub const OPTIONS: [Encoding; 7] = [
+        Encoding::Utf8,
+        Encoding::Utf8Bom,
+        Encoding::Utf16Le,
+        Encoding::Utf16LeBom,
+        Encoding::Utf16Be,
+        Encoding::Utf16BeBom,
+        Encoding::Latin1,
+    ];
+
+    fn bom(&self) -> &'static [u8] {
+        match self {
+            Encoding::Utf8Bom => &[0xef, 0xbb, 0xbf],
+            Encoding::Utf16LeBom => &[0xff, 0xfe],
+            Encoding::Utf16BeBom => &[0xfe, 0xff],
+            Encoding::Utf8 | Encoding::Utf16Le | Encoding::Utf16Be | Encoding::Latin1 => &[],
+        }
+    }
+
+    /// Uses the byte order mark of the file, or guesses the encoding from its bytes, defaulting to UTF-8.
+    pub fn detect(bytes: &[u8]) -> Self {
+        for encoding in [
+            Encoding::Utf8Bom,
+            Encoding::Utf16LeBom,
+            Encoding::Utf16BeBom,
+        ] {
+            if bytes.starts_with(encoding.bom()) {
+                return encoding;
+            }
+        }
+        // most chars of text in UTF-16 have a zero byte, the first of the pair in big endian
+        let sample = &bytes[..bytes.len().min(SAMPLE)];
+        let pairs = sample.len() / 2;
+        let zeros = |i: usize| sample.chunks_exact(2).filter(|p| p[i] == 0).count();
+        if pairs > 0 && bytes.len() % 2 == 0 {
+            let (first, second) = (zeros(0), zeros(1));
+            if second * 2 > pairs && first * 10 < pairs {
+                return Encoding::Utf16Le;
+            }
+            if first * 2 > pairs && second * 10 < pairs {
+                return Encoding::Utf16Be;
+            }
+        }
+        if std::str::from_utf8(bytes).is_ok() {
+            return Encoding::Utf8;
+        }
+        // zero bytes and C1 control chars are not in text, the file is not Latin-1 either
+        if bytes.iter().all(|b| !matches!(b, 0 | 0x80..=0x9f)) {
+            Encoding::Latin1
+        } else {
+            Encoding::Utf8
+        }
+    }
+
+    /// Decodes the bytes of a file, invalid sequences are replaced.
+    pub fn decode(&self, bytes: &[u8]) -> Decoded {
+        let bytes = bytes.strip_prefix(self.bom()).unwrap_or(bytes);
+        let (text, valid) = match self {
+            Encoding::Utf8 | Encoding::Utf8Bom => match std::str::from_utf8(bytes) {
+                Ok(text) => (text.to_string(), true),
+                Err(_) => (String::from_utf8_lossy(bytes).into_owned(), false),
+            },
+            Encoding::Utf16Le | Encoding::Utf16LeBom | Encoding::Utf16Be | Encoding::Utf16BeBom => {
+                let units = bytes.chunks_exact(2).map(|p| match self {
+                    Encoding::Utf16Le | Encoding::Utf16LeBom => u16::from_le_bytes([p[0], p[1]]),
+                    _ => u16::from_be_bytes([p[0], p[1]]),
+                });
+                let mut valid = bytes.len() % 2 == 0;
+                let mut text: String = char::decode_utf16(units)
+                    .map(|c| {
+                        c.unwrap_or_else(|_| {
+                            valid = false;
+                            char::REPLACEMENT_CHARACTER
+                        })
+                    })
+                    .collect();
+                if bytes.len() % 2 == 1 {
+                    text.push(char::REPLACEMENT_CHARACTER);
+                }
+                (text, valid)
+            }
+            Encoding::Latin1 => (bytes.iter().map(|b| *b as char).collect(), true),
+        };
+        Decoded {
+            text,
+            encoding: *self,
+            valid,
+        }
+    }
+
+    /// The bytes of the text in this encoding with its byte order mark,
+    /// or the first char that can not be written in Latin-1.
+    pub fn encode(&self, text: &str) -> Result<Vec<u8>, char> {
+        let mut bytes = self.bom().to_vec();
+        match self {
+            Encoding::Utf8 | Encoding::Utf8Bom => bytes.extend_from_slice(text.as_bytes()),
+            Encoding::Utf16Le | Encoding::Utf16LeBom => {
+                bytes.extend(text.encode_utf16().flat_map(u16::to_le_bytes))
+            }
+            Encoding::Utf16Be | Encoding::Utf16BeBom => {
+                bytes.extend(text.encode_utf16().flat_map(u16::to_be_bytes))
+            }
+            Encoding::Latin1 => {
+                for c in text.chars() {
+                    bytes.push(u8::try_from(c as u32).map_err(|_| c)?);
+                }
+            }
+        }
+        Ok(bytes)
+    }
+}
+
+impl Display for Encoding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Encoding::Utf8 => write!(f, "UTF-8"),
+            Encoding::Utf8Bom => write!(f, "UTF-8 with BOM"),
+            Encoding::Utf16Le => write!(f, "UTF-16 LE"),
+            Encoding::Utf16LeBom => write!(f, "UTF-16 LE with BOM"),
+            Encoding::Utf16Be => write!(f, "UTF-16 BE"),
+            Encoding::Utf16BeBom => write!(f, "UTF-16 BE with BOM"),
+            Encoding::Latin1 => write!(f, "ISO-8859-1"),
+        }
+    }
+}
+
+/// Decodes a file in the encoding it is detected to have.
+pub fn decode_file(bytes: &[u8]) -> Decoded {
+    Encoding::detect(bytes).decode(bytes)
+}
+
+#[test]
+fn detect_encodings() {
+    let text = "héllo\r\n";
+    for encoding in Encoding::OPTIONS {
+        let bytes = encoding.encode(text).unwrap();
+        let decoded = decode_file(&bytes);
+        assert_eq!(decoded.encoding, encoding);
+        assert_eq!(decoded.text, text);
+        assert!(decoded.valid);
+        // the byte order mark is kept, or left out if there was none
+        assert_eq!(decoded.encoding.encode(&decoded.text).unwrap(), bytes);
+    }
+    let without_bom = [b'a', 0, b'\n', 0];
+    let decoded = decode_file(&without_bom);
+    assert_eq!(decoded.encoding, Encoding::Utf16Le);
+    assert_eq!(decoded.text, "a\n");
+    assert_eq!(decoded.encoding.encode(&decoded.text).unwrap(), without_bom);
+    assert_eq!(Encoding::Latin1.encode("a€"), Err('€'));
+
+    // invalid in UTF-8 and not text in Latin-1
+    let decoded = decode_file(&[b'a', 0x81, 0xff]);
+    assert_eq!(decoded.encoding, Encoding::Utf8);
+    assert_eq!(decoded.text, "a\u{fffd}\u{fffd}");
+    assert!(!decoded.valid);
+    // an unpaired surrogate
+    let decoded = Encoding::Utf16LeBom.decode(&[0xff, 0xfe, 0x00, 0xd8, b'a', 0]);
+    assert_eq!(decoded.text, "\u{fffd}a");
+    assert!(!decoded.valid);
+}
@@ -0,0 +1,578 @@
+use std::collections::{BTreeSet, HashMap};
+use std::fs;
+use std::io;
+use std::path::{Component, Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+
+use dioxus::events::KeyboardData;
+use dioxus_html::KeyCode;
+
+use crate::workspace::Workspace;
+
+/// The state of a file in git, directories show the highest state of the files in them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum GitStatus {
+    Untracked,
+    Added,
+    Modified,
+    Conflicted,
+}
+
+/// Parses the output of `git status --porcelain`, paths are relative to the root of the repository.
+pub fn parse_git_status(output: &str) -> HashMap<PathBuf, GitStatus> {
+    output
+        .lines()
+        .filter(|l| l.len() > 3)
+        .map(|line| {
+            let (code, path) = line.split_at(3);
+            let mut code = code.chars();
+            let (x, y) = (code.next().unwrap_or(' '), code.next().unwrap_or(' '));
+            let status = match (x, y) {
+                ('?', '?') => GitStatus::Untracked,
+                ('U', _) | (_, 'U') | ('A', 'A') | ('D', 'D') => GitStatus::Conflicted,
+                ('A', _) => GitStatus::Added,
+                _ => GitStatus::Modified,
+            };
+            // renamed files are shown as `from -> to`
+            let path = path.rsplit(" -> ").next().unwrap_or(path);
+            let path = path.trim_matches('"').trim_end_matches('/');
+            (PathBuf::from(path), status)
+        })
+        .collect()
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn git_status(root: &Path) -> HashMap<PathBuf, GitStatus> {
+    let git = |args: &[&str]| {
+        std::process::Command::new("git")
+            .arg("-C")
+            .arg(root)
+            .args(args)
+            .output()
+            .ok()
+            .filter(|o| o.status.success())
+            .map(|o| String::from_utf8_lossy(&o.stdout).into_owned())
+    };
+    // folders that are not in a repository have no status
+    let top = match git(&["rev-parse", "--show-toplevel"]) {
+        Some(top) => PathBuf::from(top.trim()),
+        None => return HashMap::new(),
+    };
+    git(&["status", "--porcelain"])
+        .map(|output| {
+            parse_git_status(&output)
+                .into_iter()
+                .map(|(path, status)| (top.join(path), status))
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// The git status of a project read in the background, so large repositories do not stall the editor.
+#[derive(Debug, Clone, Default)]
+struct GitReader {
+    // the status read last, taken once it is shown
+    result: Arc<Mutex<Option<HashMap<PathBuf, GitStatus>>>>,
+    running: Arc<AtomicBool>,
+}
+
+impl GitReader {
+    /// Starts reading the status unless the last read is still running.
+    #[cfg(not(target_arch = "wasm32"))]
+    fn start(&self, root: &Path) {
+        if self.running.swap(true, Ordering::SeqCst) {
+            return;
+        }
+        let root = root.to_path_buf();
+        let reader = self.clone();
+        std::thread::spawn(move || {
+            let status = git_status(&root);
+            *reader.result.lock().unwrap() = Some(status);
+            reader.running.store(false, Ordering::SeqCst);
+        });
+    }
+
+    // there is no git in the browser
+    #[cfg(target_arch = "wasm32")]
+    fn start(&self, _root: &Path) {}
+
+    /// The status if a read finished since it was last taken.
+    fn take(&self) -> Option<HashMap<PathBuf, GitStatus>> {
+        self.result.lock().unwrap().take()
+    }
+}
+
+// the path with `.` and `..` resolved without looking at the disk
+fn normalize(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                normalized.pop();
+            }
+            component => normalized.push(component),
+        }
+    }
+    normalized
+}
+
+/// A file or directory shown in the tree.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Entry {
+    pub path: PathBuf,
+    pub name: String,
+    pub dir: bool,
+    pub depth: usize,
+}
+
+/// Lists the entries of a directory, directories first, and the entries of expanded directories under them.
+fn list(dir: &Path, depth: usize, expanded: &BTreeSet<PathBuf>, entries: &mut Vec<Entry>) {
+    let mut children: Vec<Entry> = match fs::read_dir(dir) {
+        Ok(read) => read
+            .flatten()
+            .filter(|e| e.file_name() != ".git")
+            .map(|e| Entry {
+                path: e.path(),
+                name: e.file_name().to_string_lossy().into_owned(),
+                dir: e.file_type().map_or(false, |t| t.is_dir()),
+                depth,
+            })
+            .collect(),
+        Err(_) => return,
+    };
+    children.sort_by_key(|e| (!e.dir, e.name.to_lowercase()));
+    for child in children {
+        let path = child.path.clone();
+        let dir = child.dir;
+        entries.push(child);
+        if dir && expanded.contains(&path) {
+            list(&path, depth + 1, expanded, entries);
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Action {
+    NewFile,
+    NewFolder,
+    Rename,
+    Move,
+    Delete,
+}
+
+/// An action on a file waiting for a name and for confirmation.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Prompt {
+    pub action: Action,
+    // the directory new files are created in, or the file that is changed
+    pub target: PathBuf,
+    pub input: String,
+    // the input is done and the action is waiting to be confirmed
+    pub confirming: bool,
+}
+
+impl Prompt {
+    fn new(action: Action, target: PathBuf, input: String) -> Self {
+        Self {
+            confirming: action == Action::Delete,
+            action,
+            target,
+            input,
+        }
+    }
+
+    /// The question shown while waiting for input or confirmation.
+    pub fn question(&self, root: &Path) -> String {
+        let target = self.target.strip_prefix(root).unwrap_or(&self.target);
+        let target = target.to_string_lossy();
+        let target = if target.is_empty() { "/" } else { &target };
+        let question = match self.action {
+            Action::NewFile => format!("New file in {}: {}", target, self.input),
+            Action::NewFolder => format!("New folder in {}: {}", target, self.input),
+            Action::Rename => format!("Rename {} to: {}", target, self.input),
+            Action::Move => format!("Move {} to folder: {}", target, self.input),
+            Action::Delete => format!("Delete {}?", target),
+        };
+        if self.confirming {
+            format!("{} (Enter to confirm, Escape to cancel)", question)
+        } else {
+            question
+        }
+    }
+
+    /// Changes the files on disk, returns the path of the changed file and where it was moved from.
+    /// Files can not be created or moved outside of the root.
+    fn apply(&self, root: &Path) -> io::Result<(PathBuf, Option<PathBuf>)> {
+        let inside = |path: PathBuf| {
+            let path = normalize(&path);
+            if path.starts_with(root) && path != root {
+                Ok(path)
+            } else {
+                Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!("{} is outside of the project", path.display()),
+                ))
+            }
+        };
+        let exists = |path: &Path| {
+            if path.exists() {
+                Err(io::Error::new(
+                    io::ErrorKind::AlreadyExists,
+                    format!("{} already exists", path.display()),
+                ))
+            } else {
+                Ok(())
+            }
+        };
+        match self.action {
+            Action::NewFile => {
+                let path = inside(self.target.join(&self.input))?;
+                exists(&path)?;
+                if let Some(parent) = path.parent() {
+                    fs::create_dir_all(parent)?;
+                }
+                fs::File::create(&path)?;
+                Ok((path, None))
+            }
+            Action::NewFolder => {
+                let path = inside(self.target.join(&self.input))?;
+                exists(&path)?;
+                fs::create_dir_all(&path)?;
+                Ok((path, None))
+            }
+            Action::Rename | Action::Move => {
+                let path = inside(match self.action {
+                    Action::Rename => self.target.with_file_name(&self.input),
+                    _ => root
+                        .join(self.input.trim_start_matches('/'))
+                        .join(self.target.file_name().unwrap_or_default()),
+                })?;
+                exists(&path)?;
+                fs::rename(&self.target, &path)?;
+                Ok((path, Some(self.target.clone())))
+            }
+            Action::Delete => {
+                if self.target.is_dir() {
+                    fs::remove_dir_all(&self.target)?;
+                } else {
+                    fs::remove_file(&self.target)?;
+                }
+                let parent = self.target.parent().unwrap_or(root).to_path_buf();
+                Ok((parent, None))
+            }
+        }
+    }
+}
+
+/// A tree of the files in the project, read from disk when it is refreshed.
+#[derive(Debug, Clone)]
+pub struct Explorer {
+    pub root: PathBuf,
+    pub open: bool,
+    // keys go to the explorer instead of the focused pane
+    pub focused: bool,
+    expanded: BTreeSet<PathBuf>,
+    pub entries: Vec<Entry>,
+    pub selected: Option<PathBuf>,
+    git: HashMap<PathBuf, GitStatus>,
+    git_reader: GitReader,
+    pub prompt: Option<Prompt>,
+    // the last action that failed
+    pub error: Option<String>,
+}
+
+impl Explorer {
+    pub fn new(root: PathBuf) -> Self {
+        // git reports absolute paths, so the root has to be absolute to match them
+        let root = root.canonicalize().unwrap_or(root);
+        let mut explorer = Self {
+            root,
+            open: true,
+            focused: false,
+            expanded: BTreeSet::new(),
+            entries: Vec::new(),
+            selected: None,
+            git: HashMap::new(),
+            git_reader: GitReader::default(),
+            prompt: None,
+            error: None,
+        };
+        explorer.refresh();
+        explorer
+    }
+
+    /// Reads the tree from disk again, returns if anything changed.
+    /// The git status is read in the background and shown by the next refresh after it is done.
+    pub fn refresh(&mut self) -> bool {
+        let mut entries = Vec::new();
+        list(&self.root, 0, &self.expanded, &mut entries);
+        let mut changed = entries != self.entries;
+        self.entries = entries;
+        if let Some(git) = self.git_reader.take() {
+            changed |= git != self.git;
+            self.git = git;
+        }
+        self.git_reader.start(&self.root);
+        if self.selected_index().is_none() {
+            self.selected = self.entries.first().map(|e| e.path.clone());
+        }
+        changed
+    }
+
+    pub fn status(&self, path: &Path) -> Option<GitStatus> {
+        self.git
+            .iter()
+            .filter(|(p, _)| p.starts_with(path) || path.starts_with(p))
+            .map(|(_, s)| *s)
+            .max()
+    }
+
+    pub fn is_expanded(&self, path: &Path) -> bool {
+        self.expanded.contains(path)
+    }
+
+    pub fn selected_index(&self) -> Option<usize> {
+        let selected = self.selected.as_ref()?;
+        self.entries.iter().position(|e| e.path == *selected)
+    }
+
+    fn selected_entry(&self) -> Option<&Entry> {
+        self.selected_index().map(|i| &self.entries[i])
+    }
+
+    pub fn toggle(&mut self, path: &Path) {
+        if !self.expanded.remove(path) {
+            self.expanded.insert(path.to_path_buf());
+        }
+        self.refresh();
+    }
+
+    pub fn move_selection(&mut self, change: i32) {
+        if self.entries.is_empty() {
+            return;
+        }
+        let index = self.selected_index().unwrap_or(0) as i32 + change;
+        let index = index.clamp(0, self.entries.len() as i32 - 1) as usize;
+        self.selected = Some(self.entries[index].path.clone());
+    }
+
+    /// Expands the selected directory, or selects its first entry if it is already expanded.
+    fn expand_selected(&mut self) {
+        match self.selected_entry().cloned() {
+            Some(entry) if entry.dir && !self.is_expanded(&entry.path) => self.toggle(&entry.path),
+            Some(entry) if entry.dir => self.move_selection(1),
+            _ => {}
+        }
+    }
+
+    /// Collapses the selected directory, or selects the directory the entry is in.
+    fn collapse_selected(&mut self) {
+        match self.selected_entry().cloned() {
+            Some(entry) if entry.dir && self.is_expanded(&entry.path) => self.toggle(&entry.path),
+            Some(entry) => {
+                if let Some(parent) = entry.path.parent().filter(|p| *p != self.root) {
+                    self.selected = Some(parent.to_path_buf());
+                }
+            }
+            None => {}
+        }
+    }
+
+    /// The directory new files are created in, the selected directory or the directory of the selected file.
+    fn selected_dir(&self) -> PathBuf {
+        match self.selected_entry() {
+            Some(entry) if entry.dir => entry.path.clone(),
+            Some(entry) => entry.path.parent().unwrap_or(&self.root).to_path_buf(),
+            None => self.root.clone(),
+        }
+    }
+
+    pub fn start(&mut self, action: Action) {
+        let selected = self.selected_entry().cloned();
+        self.error = None;
+        self.prompt = match (action, selected) {
+            (Action::NewFile | Action::NewFolder, _) => {
+                Some(Prompt::new(action, self.selected_dir(), String::new()))
+            }
+            (Action::Rename, Some(entry)) => Some(Prompt::new(action, entry.path, entry.name)),
+            (Action::Move, Some(entry)) => {
+                let parent = entry.path.parent().unwrap_or(&self.root);
+                let parent = parent.strip_prefix(&self.root).unwrap_or(parent);
+                let input = parent.to_string_lossy().into_owned();
+                Some(Prompt::new(action, entry.path, input))
+            }
+            (Action::Delete, Some(entry)) => Some(Prompt::new(action, entry.path, String::new())),
+            _ => None,
+        };
+    }
+
+    /// Applies the confirmed action, returns where a file was moved from and to.
+    pub fn confirm(&mut self) -> Option<(PathBuf, PathBuf)> {
+        let prompt = self.prompt.take()?;
+        match prompt.apply(&self.root) {
+            Ok((path, from)) => {
+                // show the changed file
+                let mut parent = path.parent();
+                while let Some(dir) =
+                    parent.filter(|p| p.starts_with(&self.root) && *p != self.root)
+                {
+                    self.expanded.insert(dir.to_path_buf());
+                    parent = dir.parent();
+                }
+                self.selected = Some(path.clone());
+                self.refresh();
+                from.map(|from| (from, path))
+            }
+            Err(e) => {
+                self.error = Some(e.to_string());
+                None
+            }
+        }
+    }
+}
+
+/// Handles a key press while the explorer is focused, returns false for keys it does not use.
+pub fn explorer_input(k: &KeyboardData, workspace: &mut Workspace) -> bool {
+    let explorer = match &mut workspace.explorer {
+        Some(explorer) => explorer,
+        None => return false,
+    };
+    if k.ctrl_key || k.alt_key {
+        return false;
+    }
+    if let Some(prompt) = &mut explorer.prompt {
+        match k.key_code {
+            KeyCode::Escape => explorer.prompt = None,
+            KeyCode::Enter if prompt.confirming => {
+                if let Some((from, to)) = explorer.confirm() {
+                    workspace.rename_path(&from, &to);
+                }
+            }
+            // files can be moved to the root, which has an empty path
+            KeyCode::Enter if !prompt.input.is_empty() || prompt.action == Action::Move => {
+                prompt.confirming = true
+            }
+            KeyCode::Backspace if !prompt.confirming => {
+                prompt.input.pop();
+            }
+            _ if k.key.chars().count() == 1 && !prompt.confirming => {
+                prompt.input.push_str(&k.key);
+            }
+            _ => {}
+        }
+        return true;
+    }
+    match k.key_code {
+        KeyCode::UpArrow => explorer.move_selection(-1),
+        KeyCode::DownArrow => explorer.move_selection(1),
+        KeyCode::RightArrow => explorer.expand_selected(),
+        KeyCode::LeftArrow => explorer.collapse_selected(),
+        KeyCode::Enter => match explorer.selected_entry().cloned() {
+            Some(entry) if entry.dir => explorer.toggle(&entry.path),
+            Some(entry) => {
+                explorer.focused = false;
+                workspace.open_path(&entry.path);
+            }
+            None => {}
+        },
+        KeyCode::Escape => explorer.focused = false,
+        KeyCode::F2 => explorer.start(Action::Rename),
+        KeyCode::Delete => explorer.start(Action::Delete),
+        KeyCode::N if k.shift_key => explorer.start(Action::NewFolder),
+        KeyCode::N => explorer.start(Action::NewFile),
+        KeyCode::M => explorer.start(Action::Move),
+        _ => {}
+    }
+    true
+}
+
+#[test]
+fn git_status_output() {
+    let status = parse_git_status(
+        " M src/main.rs\nA  src/new.rs\n?? notes/\nR  old.rs -> renamed.rs\nUU conflict.rs\n",
+    );
+    assert_eq!(status[Path::new("src/main.rs")], GitStatus::Modified);
+    assert_eq!(status[Path::new("src/new.rs")], GitStatus::Added);
+    assert_eq!(status[Path::new("notes")], GitStatus::Untracked);
+    assert_eq!(status[Path::new("renamed.rs")], GitStatus::Modified);
+    assert_eq!(status[Path::new("conflict.rs")], GitStatus::Conflicted);
+
+    let mut explorer = Explorer::new(PathBuf::from("/project"));
+    explorer.git = status
+        .into_iter()
+        .map(|(path, status)| (explorer.root.join(path), status))
+        .collect();
+    let root = explorer.root.clone();
+    assert_eq!(
+        explorer.status(&root.join("src")),
+        Some(GitStatus::Modified)
+    );
+    assert_eq!(
+        explorer.status(&root.join("notes/todo.md")),
+        Some(GitStatus::Untracked)
+    );
+    assert_eq!(explorer.status(&root.join("README.md")), None);
+}
+
+#[test]
+fn file_operations() {
+    let root = std::env::temp_dir().join(format!("patina-explorer-{}", std::process::id()));
+    fs::create_dir_all(root.join("src")).unwrap();
+    fs::write(root.join("README.md"), "").unwrap();
+    fs::write(root.join("src/main.rs"), "").unwrap();
+    let mut explorer = Explorer::new(root.clone());
+    let root = explorer.root.clone();
+    let names = |explorer: &Explorer| -> Vec<String> {
+        explorer.entries.iter().map(|e| e.name.clone()).collect()
+    };
+    // directories are listed first, expanded directories show their entries
+    assert_eq!(names(&explorer), vec!["src", "README.md"]);
+    explorer.expand_selected();
+    assert_eq!(names(&explorer), vec!["src", "main.rs", "README.md"]);
+    explorer.move_selection(1);
+    assert_eq!(explorer.selected, Some(root.join("src/main.rs")));
+    explorer.collapse_selected();
+    assert_eq!(explorer.selected, Some(root.join("src")));
+
+    explorer.start(Action::NewFile);
+    explorer.prompt.as_mut().unwrap().input = "lib.rs".to_string();
+    explorer.confirm();
+    assert!(root.join("src/lib.rs").is_file());
+    assert_eq!(explorer.selected, Some(root.join("src/lib.rs")));
+
+    explorer.start(Action::Rename);
+    explorer.prompt.as_mut().unwrap().input = "util.rs".to_string();
+    assert_eq!(
+        explorer.confirm(),
+        Some((root.join("src/lib.rs"), root.join("src/util.rs")))
+    );
+
+    explorer.start(Action::Move);
+    explorer.prompt.as_mut().unwrap().input = String::new();
+    explorer.confirm();
+    assert!(root.join("util.rs").is_file());
+
+    // files can not leave the project
+    explorer.selected = Some(root.join("util.rs"));
+    explorer.start(Action::Rename);
+    explorer.prompt.as_mut().unwrap().input = "../util.rs".to_string();
+    assert_eq!(explorer.confirm(), None);
+    explorer.start(Action::Move);
+    explorer.prompt.as_mut().unwrap().input = "src/../..".to_string();
+    assert_eq!(explorer.confirm(), None);
+    assert!(root.join("util.rs").is_file());
+
+    // existing files are not overwritten
+    explorer.start(Action::NewFile);
+    explorer.prompt.as_mut().unwrap().input = "README.md".to_string();
+    assert_eq!(explorer.confirm(), None);
+    assert!(explorer.error.is_some());
+
+    explorer.selected = Some(root.join("src"));
+    explorer.start(Action::Delete);
+    explorer.confirm();
+    assert_eq!(names(&explorer), vec!["README.md", "util.rs"]);
+    fs::remove_dir_all(&root).unwrap();
+}
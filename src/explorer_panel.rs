@@ -0,0 +1,163 @@
+use std::cell::Cell;
+use std::rc::Rc;
+
+use crate::explorer::{explorer_input, Action, GitStatus};
+use crate::utils::{color_to_string, mix, sleep};
+use crate::workspace::Workspace;
+use crate::{editor_colors, theme};
+use dioxus::prelude::*;
+use syntect::highlighting::Color;
+
+// how often the tree is read from disk again in milliseconds
+const REFRESH_INTERVAL: u32 = 1000;
+
+fn git_color(status: GitStatus) -> Color {
+    let (r, g, b) = match status {
+        GitStatus::Untracked => (115, 201, 145),
+        GitStatus::Added => (129, 184, 139),
+        GitStatus::Modified => (226, 192, 141),
+        GitStatus::Conflicted => (224, 108, 117),
+    };
+    Color { r, g, b, a: 255 }
+}
+
+#[derive(Props)]
+pub struct ExplorerPanelProps<'a> {
+    workspace: &'a UseRef<Workspace>,
+}
+pub fn ExplorerPanel<'a>(cx: Scope<'a, ExplorerPanelProps<'a>>) -> Element {
+    let workspace = cx.props.workspace;
+    let (tick, set_tick) = use_state(&cx, || 0usize);
+    let polling = cx.use_hook(|_| Rc::new(Cell::new(false)), |p| &*p);
+    // the tick the tree was last read from disk on
+    let refreshed = cx.use_hook(|_| Cell::new(0usize), |r| &*r);
+
+    if !polling.get() {
+        polling.set(true);
+        let polling = polling.clone();
+        let set_tick = set_tick.clone();
+        let tick = *tick;
+        cx.push_future(async move {
+            sleep(REFRESH_INTERVAL).await;
+            polling.set(false);
+            set_tick(tick + 1);
+        });
+    }
+    // files changed on disk by other programs are shown
+    if refreshed.get() != *tick {
+        refreshed.set(*tick);
+        let mut explorer = workspace.read().explorer.clone();
+        if let Some(explorer) = &mut explorer {
+            if explorer.refresh() {
+                workspace.write().explorer = Some(explorer.clone());
+            }
+        }
+    }
+
+    let explorer = match workspace.read().explorer.clone() {
+        Some(explorer) if explorer.open => explorer,
+        _ => return None,
+    };
+    let root_name = explorer
+        .root
+        .file_name()
+        .map(|n| n.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let prompt = explorer.prompt.as_ref().map(|p| p.question(&explorer.root));
+    let error = explorer.error.clone().unwrap_or_default();
+    let selected = explorer.selected_index();
+
+    let colors = editor_colors();
+    let fg = color_to_string(colors.foreground);
+    let bg = color_to_string(theme().settings.gutter.unwrap_or(colors.background));
+    let selected_bg = color_to_string(if explorer.focused {
+        colors.selection
+    } else {
+        colors.inactive_selection
+    });
+    let start = move |action: Action| {
+        if let Some(explorer) = &mut workspace.write().explorer {
+            explorer.focused = true;
+            explorer.start(action);
+        }
+    };
+
+    cx.render(rsx! {
+        div{
+            width: "20%",
+            min_width: "0px",
+            height: "100%",
+            overflow: "auto",
+            display: "flex",
+            flex_direction: "column",
+            color: "{fg}",
+            background_color: "{bg}",
+            tabindex: "0",
+            onkeydown: move |k| {
+                explorer_input(&*k, &mut workspace.write());
+            },
+            onmousedown: move |_| {
+                if let Some(explorer) = &mut workspace.write().explorer {
+                    explorer.focused = true;
+                }
+            },
+            div{
+                display: "flex",
+                flex_direction: "row",
+                justify_content: "space-between",
+                padding: "2px 6px",
+                span{ font_weight: "bold", "{root_name}" }
+                span{
+                    span{ padding_left: "6px", cursor: "pointer", onclick: move |_| start(Action::NewFile), "+File" }
+                    span{ padding_left: "6px", cursor: "pointer", onclick: move |_| start(Action::NewFolder), "+Folder" }
+                    span{ padding_left: "6px", cursor: "pointer", onclick: move |_| start(Action::Rename), "Rename" }
+                    span{ padding_left: "6px", cursor: "pointer", onclick: move |_| start(Action::Move), "Move" }
+                    span{ padding_left: "6px", cursor: "pointer", onclick: move |_| start(Action::Delete), "Delete" }
+                }
+            }
+            prompt.map(|prompt| rsx! {
+                div{ padding: "0px 6px", "{prompt}" }
+            })
+            div{ padding: "0px 6px", "{error}" }
+            explorer.entries.iter().enumerate().map(|(i, entry)| {
+                let row_bg = if Some(i) == selected { selected_bg.clone() } else { bg.clone() };
+                let row_fg = explorer
+                    .status(&entry.path)
+                    .map(|s| color_to_string(mix(colors.foreground, git_color(s), 0.7)))
+                    .unwrap_or_else(|| fg.clone());
+                let indent = entry.depth * 12 + 6;
+                let icon = match (entry.dir, explorer.is_expanded(&entry.path)) {
+                    (true, true) => "▾ ",
+                    (true, false) => "▸ ",
+                    (false, _) => "  ",
+                };
+                let name = entry.name.clone();
+                let path = entry.path.clone();
+                let dir = entry.dir;
+                rsx! {
+                    div{
+                        key: "{name}{i}",
+                        padding_left: "{indent}px",
+                        white_space: "pre",
+                        cursor: "pointer",
+                        color: "{row_fg}",
+                        background_color: "{row_bg}",
+                        onclick: move |_| {
+                            let mut workspace = workspace.write();
+                            if let Some(explorer) = &mut workspace.explorer {
+                                explorer.selected = Some(path.clone());
+                                if dir {
+                                    explorer.toggle(&path);
+                                    return;
+                                }
+                                explorer.focused = false;
+                            }
+                            workspace.open_path(&path);
+                        },
+                        "{icon}{name}"
+                    }
+                }
+            })
+        }
+    })
+}
@@ -0,0 +1,129 @@
+use std::path::PathBuf;
+use std::sync::atomic::{self, AtomicBool};
+use std::sync::{Arc, RwLock};
+
+use crate::fuzzy::{fuzzy_match, FuzzyMatch};
+
+// the most files shown for a query
+pub const MAX_RESULTS: usize = 50;
+// files found while indexing are added to the index in batches
+const BATCH_SIZE: usize = 1024;
+
+/// The paths of every file in a project relative to its root, indexed in the background.
+#[derive(Debug, Clone)]
+pub struct FileIndex {
+    pub root: PathBuf,
+    files: Arc<RwLock<Vec<String>>>,
+    indexing: Arc<AtomicBool>,
+}
+
+impl FileIndex {
+    /// Starts indexing the files under the root, skipping files ignored by git.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn new(root: PathBuf) -> Self {
+        let index = Self::from_files(root.clone(), Vec::new());
+        index.indexing.store(true, atomic::Ordering::SeqCst);
+        let background = index.clone();
+        std::thread::spawn(move || {
+            let mut batch = Vec::new();
+            for entry in ignore::WalkBuilder::new(&root).build().flatten() {
+                if !entry.file_type().map_or(false, |t| t.is_file()) {
+                    continue;
+                }
+                if let Ok(path) = entry.path().strip_prefix(&root) {
+                    batch.push(path.to_string_lossy().replace('\\', "/"));
+                }
+                if batch.len() == BATCH_SIZE {
+                    background.files.write().unwrap().append(&mut batch);
+                }
+            }
+            background.files.write().unwrap().append(&mut batch);
+            background.indexing.store(false, atomic::Ordering::SeqCst);
+        });
+        index
+    }
+
+    pub fn from_files(root: PathBuf, files: Vec<String>) -> Self {
+        Self {
+            root,
+            files: Arc::new(RwLock::new(files)),
+            indexing: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    pub fn is_indexing(&self) -> bool {
+        self.indexing.load(atomic::Ordering::SeqCst)
+    }
+
+    pub fn file_count(&self) -> usize {
+        self.files.read().unwrap().len()
+    }
+
+    pub fn path(&self, file: &str) -> PathBuf {
+        self.root.join(file)
+    }
+
+    /// The files best matching the query, shorter paths first if they match equally well.
+    pub fn search(&self, query: &str, limit: usize) -> Vec<(String, FuzzyMatch)> {
+        let files = self.files.read().unwrap();
+        let mut matches: Vec<_> = files
+            .iter()
+            .filter_map(|f| fuzzy_match(query, f).map(|m| (f, m)))
+            .collect();
+        let order = |(a, am): &(&String, FuzzyMatch), (b, bm): &(&String, FuzzyMatch)| {
+            bm.score
+                .cmp(&am.score)
+                .then(a.len().cmp(&b.len()))
+                .then(a.cmp(b))
+        };
+        // only the best matches have to be sorted
+        if matches.len() > limit {
+            matches.select_nth_unstable_by(limit, order);
+            matches.truncate(limit);
+        }
+        matches.sort_by(order);
+        matches.into_iter().map(|(f, m)| (f.clone(), m)).collect()
+    }
+}
+
+/// Splits a `path:line` query into the path and the row to jump to.
+pub fn parse_query(query: &str) -> (&str, Option<usize>) {
+    match query.rsplit_once(':') {
+        Some((path, line)) if line.chars().all(|c| c.is_ascii_digit()) => (
+            path,
+            line.parse::<usize>().ok().map(|l| l.saturating_sub(1)),
+        ),
+        _ => (query, None),
+    }
+}
+
+#[test]
+fn search_files() {
+    let index = FileIndex::from_files(
+        PathBuf::from("/project"),
+        vec![
+            "src/code_area.rs".to_string(),
+            "src/main.rs".to_string(),
+            "examples/main/src/main.rs".to_string(),
+            "README.md".to_string(),
+        ],
+    );
+    let found: Vec<_> = index
+        .search("main", MAX_RESULTS)
+        .into_iter()
+        .map(|(f, _)| f)
+        .collect();
+    assert_eq!(found, vec!["src/main.rs", "examples/main/src/main.rs"]);
+
+    assert_eq!(index.search("", 2).len(), 2);
+    assert_eq!(index.search("srcca", 1)[0].0, "src/code_area.rs");
+    assert_eq!(index.path("README.md"), PathBuf::from("/project/README.md"));
+}
+
+#[test]
+fn line_queries() {
+    assert_eq!(parse_query("src/main.rs:12"), ("src/main.rs", Some(11)));
+    assert_eq!(parse_query("main:"), ("main", None));
+    assert_eq!(parse_query("main"), ("main", None));
+    assert_eq!(parse_query("a:b"), ("a:b", None));
+}
@@ -0,0 +1,381 @@
+use std::collections::BTreeMap;
+
+use ropey::Rope;
+use serde::{Deserialize, Serialize};
+
+use crate::word::is_word_char;
+
+// the comments a region marker can start with, like `// region`, `#region` or `<!-- #region -->`
+const COMMENT_PREFIXES: [&str; 6] = ["//", "/*", "<!--", "--", "#", ";"];
+
+/// A foldable block, the start row stays visible and the rows after it up to and including end are hidden when folded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct FoldRange {
+    pub start: usize,
+    pub end: usize,
+}
+
+impl FoldRange {
+    pub fn new(start: usize, end: usize) -> Self {
+        Self { start, end }
+    }
+
+    pub fn contains(&self, row: usize) -> bool {
+        self.start <= row && row <= self.end
+    }
+
+    pub fn hides(&self, row: usize) -> bool {
+        self.start < row && row <= self.end
+    }
+}
+
+fn indent_width(line: &str) -> Option<usize> {
+    if line.trim().is_empty() {
+        return None;
+    }
+    Some(
+        line.chars()
+            .take_while(|c| c.is_whitespace())
+            .map(|c| if c == '\t' { 4 } else { 1 })
+            .sum(),
+    )
+}
+
+/// If the char opens or closes a block.
+pub fn is_bracket(c: char) -> bool {
+    matches!(c, '{' | '[' | '(' | '}' | ']' | ')')
+}
+
+// if a line is a `region` or `endregion` comment, true for the start of a region
+fn region_marker(line: &str) -> Option<bool> {
+    let line = line.trim_start();
+    let rest = COMMENT_PREFIXES
+        .iter()
+        .find_map(|prefix| line.strip_prefix(prefix))?;
+    let rest = rest.trim_start().trim_start_matches('#');
+    let word: String = rest.chars().take_while(|c| is_word_char(*c)).collect();
+    match word.as_str() {
+        "region" => Some(true),
+        "endregion" => Some(false),
+        _ => None,
+    }
+}
+
+/// Every block that can be folded sorted by start row.
+/// Blocks come from brackets, indentation and `region`/`endregion` comments.
+/// Brackets holds the brackets of each line that are code, not the ones inside of strings or comments.
+pub fn fold_ranges(lines: &[&str], brackets: &[&[char]]) -> Vec<FoldRange> {
+    let mut ends: BTreeMap<usize, usize> = BTreeMap::new();
+    let mut add = |start: usize, end: usize| {
+        if end > start {
+            let e = ends.entry(start).or_insert(end);
+            *e = (*e).max(end);
+        }
+    };
+
+    // brackets, the closing bracket stays visible
+    let mut open = Vec::new();
+    for (row, line) in brackets.iter().enumerate() {
+        for c in line.iter() {
+            match c {
+                '{' | '[' | '(' => open.push(row),
+                '}' | ']' | ')' => {
+                    if let Some(start) = open.pop() {
+                        if row > start + 1 {
+                            add(start, row - 1);
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    // indentation, trailing blank lines stay visible
+    let indents: Vec<Option<usize>> = lines.iter().map(|l| indent_width(l)).collect();
+    for (row, indent) in indents.iter().enumerate() {
+        if let Some(indent) = indent {
+            let mut end = row;
+            for (next, next_indent) in indents.iter().enumerate().skip(row + 1) {
+                match next_indent {
+                    Some(i) if i <= indent => break,
+                    Some(_) => end = next,
+                    None => {}
+                }
+            }
+            add(row, end);
+        }
+    }
+
+    // region markers inside of comments, the end marker is hidden
+    let mut regions = Vec::new();
+    for (row, line) in lines.iter().enumerate() {
+        match region_marker(line) {
+            Some(true) => regions.push(row),
+            Some(false) => {
+                if let Some(start) = regions.pop() {
+                    add(start, row);
+                }
+            }
+            None => {}
+        }
+    }
+
+    ends.into_iter()
+        .map(|(start, end)| FoldRange::new(start, end))
+        .collect()
+}
+
+/// The folded blocks of a view.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Folds(pub Vec<FoldRange>);
+
+impl Folds {
+    pub fn is_hidden(&self, row: usize) -> bool {
+        self.0.iter().any(|f| f.hides(row))
+    }
+
+    pub fn is_folded(&self, row: usize) -> bool {
+        self.0.iter().any(|f| f.start == row)
+    }
+
+    /// Folds the innermost unfolded block containing the row.
+    pub fn fold(&mut self, ranges: &[FoldRange], row: usize) {
+        if let Some(range) = ranges
+            .iter()
+            .copied()
+            .filter(|r| r.contains(row) && !self.0.contains(r))
+            .max_by_key(|r| r.start)
+        {
+            self.0.push(range);
+            self.0.sort_unstable();
+        }
+    }
+
+    /// Unfolds the innermost folded block containing the row.
+    pub fn unfold(&mut self, row: usize) {
+        if let Some((i, _)) = self
+            .0
+            .iter()
+            .enumerate()
+            .filter(|(_, f)| f.contains(row))
+            .max_by_key(|(_, f)| f.start)
+        {
+            self.0.remove(i);
+        }
+    }
+
+    /// Folds or unfolds the block starting on the row.
+    pub fn toggle(&mut self, ranges: &[FoldRange], row: usize) {
+        if self.is_folded(row) {
+            self.0.retain(|f| f.start != row);
+        } else if let Some(range) = ranges.iter().find(|r| r.start == row) {
+            self.0.push(*range);
+            self.0.sort_unstable();
+        }
+    }
+
+    pub fn fold_all(&mut self, ranges: &[FoldRange]) {
+        self.fold_to_level(ranges, 0);
+    }
+
+    pub fn unfold_all(&mut self) {
+        self.0.clear();
+    }
+
+    /// Folds every block nested at least level blocks deep, level 0 folds everything.
+    pub fn fold_to_level(&mut self, ranges: &[FoldRange], level: usize) {
+        self.0 = ranges
+            .iter()
+            .filter(|r| {
+                let depth = ranges
+                    .iter()
+                    .filter(|outer| outer.start < r.start && outer.end >= r.end)
+                    .count();
+                depth >= level
+            })
+            .copied()
+            .collect();
+    }
+
+    /// Moves a hidden row to the closest visible row in the direction of movement.
+    pub fn visible_row(&self, mut row: usize, down: bool, num_lines: usize) -> Option<usize> {
+        while let Some(fold) = self
+            .0
+            .iter()
+            .filter(|f| f.hides(row))
+            .min_by_key(|f| f.start)
+        {
+            if down {
+                row = fold.end + 1;
+                if row >= num_lines {
+                    return None;
+                }
+            } else {
+                row = fold.start;
+            }
+        }
+        Some(row)
+    }
+
+    /// Moves the folds to follow an edit, folds with a changed line inside of them are unfolded.
+    pub fn update(&mut self, old: &Rope, new: &Rope) {
+        let old_len = old.len_lines();
+        let new_len = new.len_lines();
+        let shortest = old_len.min(new_len);
+        let prefix = (0..shortest)
+            .take_while(|i| old.line(*i) == new.line(*i))
+            .count();
+        if prefix == old_len && old_len == new_len {
+            return;
+        }
+        let suffix = (0..shortest - prefix)
+            .take_while(|i| old.line(old_len - 1 - i) == new.line(new_len - 1 - i))
+            .count();
+        // the rows prefix..changed_end of the old text were changed
+        let changed_end = old_len - suffix;
+        let change = new_len as i64 - old_len as i64;
+
+        self.0 = self
+            .0
+            .iter()
+            .filter_map(|f| {
+                if f.end < prefix {
+                    Some(*f)
+                } else if f.start >= changed_end {
+                    Some(FoldRange::new(
+                        (f.start as i64 + change) as usize,
+                        (f.end as i64 + change) as usize,
+                    ))
+                } else if change == 0 && prefix >= f.start && changed_end <= f.start + 1 {
+                    // only the first line of the fold changed
+                    Some(*f)
+                } else {
+                    None
+                }
+            })
+            .collect();
+    }
+}
+
+// the foldable blocks of rust code
+#[cfg(test)]
+fn rust_fold_ranges(text: &str) -> Vec<FoldRange> {
+    let mut highlights = crate::highlight::HighlightCache::default();
+    let syntax = crate::PS.find_syntax_by_extension("rs").unwrap();
+    highlights.update(&Rope::from_str(text), syntax);
+    highlights.fold_ranges().to_vec()
+}
+
+#[test]
+fn fold_ranges_from_blocks() {
+    assert_eq!(
+        rust_fold_ranges(
+            "fn main() {\n    let a = [\n        1,\n    ];\n}\n// region helpers\nfn a() {}\n// endregion\ndef f():\n    pass",
+        ),
+        vec![
+            FoldRange::new(0, 3),
+            FoldRange::new(1, 2),
+            FoldRange::new(5, 7),
+            FoldRange::new(8, 9),
+        ]
+    );
+}
+
+#[test]
+fn brackets_in_strings_and_comments_are_not_folded() {
+    assert_eq!(
+        rust_fold_ranges("let a = \"{\";\nlet b = '(';\n// )\nfn c() {\n    d();\n\n}"),
+        vec![FoldRange::new(3, 5)]
+    );
+    // only whole words after a comment start are region markers
+    assert_eq!(
+        rust_fold_ranges(
+            "// regional\nlet a = 1;\n// endregion\nlet r = json!({\n\"region\": 1,\n});\n"
+        ),
+        vec![FoldRange::new(3, 4)]
+    );
+    assert_eq!(
+        rust_fold_ranges("#region\nlet a = 1;\n#endregion"),
+        vec![FoldRange::new(0, 2)]
+    );
+}
+
+#[test]
+fn fold_and_unfold() {
+    let ranges =
+        rust_fold_ranges("fn main() {\n    if a {\n        b();\n    }\n}\nfn c() {\n    d();\n}");
+    let mut folds = Folds::default();
+
+    folds.fold(&ranges, 2);
+    assert_eq!(folds, Folds(vec![FoldRange::new(1, 2)]));
+    folds.fold(&ranges, 2);
+    assert_eq!(
+        folds,
+        Folds(vec![FoldRange::new(0, 3), FoldRange::new(1, 2)])
+    );
+    assert!(folds.is_hidden(3));
+    assert!(!folds.is_hidden(4));
+
+    folds.unfold(1);
+    assert_eq!(folds, Folds(vec![FoldRange::new(0, 3)]));
+
+    folds.fold_to_level(&ranges, 1);
+    assert_eq!(folds, Folds(vec![FoldRange::new(1, 2)]));
+
+    folds.fold_all(&ranges);
+    assert_eq!(
+        folds,
+        Folds(vec![
+            FoldRange::new(0, 3),
+            FoldRange::new(1, 2),
+            FoldRange::new(5, 6)
+        ])
+    );
+
+    folds.toggle(&ranges, 5);
+    folds.toggle(&ranges, 0);
+    assert_eq!(folds, Folds(vec![FoldRange::new(1, 2)]));
+    folds.unfold_all();
+    assert_eq!(folds, Folds::default());
+}
+
+#[test]
+fn visible_rows() {
+    let folds = Folds(vec![FoldRange::new(1, 3), FoldRange::new(4, 5)]);
+
+    assert_eq!(folds.visible_row(2, true, 7), Some(4));
+    assert_eq!(folds.visible_row(5, true, 7), Some(6));
+    assert_eq!(folds.visible_row(5, false, 7), Some(4));
+    assert_eq!(folds.visible_row(3, false, 7), Some(1));
+    assert_eq!(folds.visible_row(5, true, 6), None);
+}
+
+#[test]
+fn update_folds_after_edit() {
+    let old = Rope::from_str("a {\n  b\n}\nc {\n  d\n}");
+    let mut folds = Folds(vec![FoldRange::new(0, 1), FoldRange::new(3, 4)]);
+
+    // a new line before the second fold moves it
+    let new = Rope::from_str("a {\n  b\n}\n\nc {\n  d\n}");
+    folds.update(&old, &new);
+    assert_eq!(
+        folds,
+        Folds(vec![FoldRange::new(0, 1), FoldRange::new(4, 5)])
+    );
+
+    // editing the first line of a fold keeps it
+    let old = new;
+    let new = Rope::from_str("ab {\n  b\n}\n\nc {\n  d\n}");
+    folds.update(&old, &new);
+    assert_eq!(
+        folds,
+        Folds(vec![FoldRange::new(0, 1), FoldRange::new(4, 5)])
+    );
+
+    // editing inside of a fold unfolds it
+    let old = new;
+    let new = Rope::from_str("ab {\n  b\n}\n\nc {\n  de\n}");
+    folds.update(&old, &new);
+    assert_eq!(folds, Folds(vec![FoldRange::new(0, 1)]));
+}
@@ -0,0 +1,100 @@
+/// A match of a query in some text.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FuzzyMatch {
+    // higher is better
+    pub score: i32,
+    // the char indexes of the text that matched the query
+    pub indexes: Vec<usize>,
+}
+
+fn is_word_start(previous: Option<char>, c: char) -> bool {
+    match previous {
+        None => true,
+        Some(p) => {
+            !p.is_alphanumeric()
+                || (p.is_lowercase() && c.is_uppercase())
+                || (!p.is_numeric() && c.is_numeric())
+        }
+    }
+}
+
+/// Matches the chars of the query in order, ignoring case.
+/// Chars at the start of words and runs of chars score higher, gaps between them score lower.
+pub fn fuzzy_match(query: &str, text: &str) -> Option<FuzzyMatch> {
+    let query: Vec<char> = query
+        .chars()
+        .filter(|c| !c.is_whitespace())
+        .flat_map(char::to_lowercase)
+        .collect();
+    let mut indexes = Vec::new();
+    let mut score = 0;
+    let mut previous = None;
+    let mut matched = 0;
+    for (i, c) in text.chars().enumerate() {
+        if matched == query.len() {
+            break;
+        }
+        let word_start = is_word_start(previous, c);
+        previous = Some(c);
+        if !c.to_lowercase().eq(std::iter::once(query[matched])) {
+            continue;
+        }
+        let run = indexes.last().map_or(false, |last| last + 1 == i);
+        score += 1;
+        if run {
+            score += 4;
+        } else if word_start {
+            score += 3;
+        }
+        if let Some(last) = indexes.last() {
+            score -= (i - last - 1).min(3) as i32;
+        }
+        indexes.push(i);
+        matched += 1;
+    }
+    (matched == query.len()).then(|| FuzzyMatch { score, indexes })
+}
+
+/// Splits text into pieces that are all matched chars or all unmatched chars.
+pub fn split_matched(text: &str, indexes: &[usize]) -> Vec<(bool, String)> {
+    let mut pieces: Vec<(bool, String)> = Vec::new();
+    for (i, c) in text.chars().enumerate() {
+        let matched = indexes.contains(&i);
+        match pieces.last_mut() {
+            Some((last, piece)) if *last == matched => piece.push(c),
+            _ => pieces.push((matched, c.to_string())),
+        }
+    }
+    pieces
+}
+
+#[test]
+fn fuzzy_matches() {
+    assert_eq!(
+        fuzzy_match("spr", "Split Right").map(|m| m.indexes),
+        Some(vec![0, 1, 6])
+    );
+    assert_eq!(fuzzy_match("rs", "Split Right"), None);
+    assert!(fuzzy_match("", "anything").is_some());
+
+    // consecutive chars and word starts rank higher than scattered chars
+    let word_start = fuzzy_match("cp", "Command Palette").unwrap();
+    let scattered = fuzzy_match("cp", "Scope").unwrap();
+    assert!(word_start.score > scattered.score);
+    let run = fuzzy_match("fold", "Fold All").unwrap();
+    let spread = fuzzy_match("fold", "Find Or Load Data").unwrap();
+    assert!(run.score > spread.score);
+}
+
+#[test]
+fn split_text() {
+    assert_eq!(
+        split_matched("Fold All", &[0, 1, 5]),
+        vec![
+            (true, "Fo".to_string()),
+            (false, "ld ".to_string()),
+            (true, "A".to_string()),
+            (false, "ll".to_string()),
+        ]
+    );
+}
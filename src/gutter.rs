@@ -0,0 +1,165 @@
+use syntect::highlighting::{Color, Style};
+
+use crate::settings::LineNumbers;
+use crate::theme;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LineChange {
+    Added,
+    Modified,
+    Deleted,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Fold {
+    Open,
+    Closed,
+}
+
+/// The markers shown beside a line number.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct GutterMarkers {
+    pub breakpoint: bool,
+    pub diagnostic: Option<(char, Color)>,
+    pub change: Option<LineChange>,
+    pub fold: Option<Fold>,
+}
+
+fn gutter_style(foreground: Color) -> Style {
+    Style {
+        foreground,
+        background: theme()
+            .settings
+            .gutter
+            .unwrap_or_else(|| theme().settings.background.unwrap()),
+        ..Default::default()
+    }
+}
+
+fn gutter_foreground() -> Color {
+    theme()
+        .settings
+        .gutter_foreground
+        .unwrap_or_else(|| theme().settings.foreground.unwrap())
+}
+
+/// A line of the gutter without the fold marker, each entry is a section of text and the style to draw it with.
+pub fn gutter_line(
+    row: usize,
+    current_rows: &[usize],
+    num_lines: usize,
+    line_numbers: LineNumbers,
+    markers: &GutterMarkers,
+) -> Vec<(Style, String)> {
+    let fg = gutter_foreground();
+    let current = current_rows.contains(&row);
+
+    let breakpoint = if markers.breakpoint { "●" } else { " " };
+    let diagnostic = markers
+        .diagnostic
+        .map_or((fg, ' '), |(c, color)| (color, c));
+    let number = line_number(row, current_rows, line_numbers);
+    let width = number_width(num_lines);
+    let change = match markers.change {
+        Some(LineChange::Added) => (
+            Color {
+                r: 80,
+                g: 160,
+                b: 80,
+                a: 255,
+            },
+            "▎",
+        ),
+        Some(LineChange::Modified) => (
+            Color {
+                r: 80,
+                g: 120,
+                b: 200,
+                a: 255,
+            },
+            "▎",
+        ),
+        Some(LineChange::Deleted) => (
+            Color {
+                r: 200,
+                g: 80,
+                b: 80,
+                a: 255,
+            },
+            "▁",
+        ),
+        None => (fg, " "),
+    };
+
+    vec![
+        (
+            gutter_style(Color {
+                r: 200,
+                g: 60,
+                b: 60,
+                a: 255,
+            }),
+            breakpoint.to_string(),
+        ),
+        (gutter_style(diagnostic.0), diagnostic.1.to_string()),
+        (
+            gutter_style(if current {
+                theme().settings.foreground.unwrap()
+            } else {
+                fg
+            }),
+            format!("{:>width$}", number, width = width),
+        ),
+        (gutter_style(change.0), change.1.to_string()),
+    ]
+}
+
+fn number_width(num_lines: usize) -> usize {
+    num_lines.to_string().len().max(2)
+}
+
+/// The number of columns the gutter takes up, including the fold marker.
+pub fn gutter_width(num_lines: usize) -> usize {
+    number_width(num_lines) + 5
+}
+
+/// An empty gutter for the rows of a wrapped line after the first.
+pub fn blank_gutter(num_lines: usize) -> (Style, String) {
+    (
+        gutter_style(gutter_foreground()),
+        " ".repeat(gutter_width(num_lines)),
+    )
+}
+
+pub fn fold_marker(markers: &GutterMarkers) -> (Style, String) {
+    let fold = match markers.fold {
+        Some(Fold::Open) => "▾",
+        Some(Fold::Closed) => "▸",
+        None => " ",
+    };
+    (gutter_style(gutter_foreground()), format!("{} ", fold))
+}
+
+/// The number to show for a row, relative numbers count from the nearest cursor and show the cursor's own row as absolute.
+pub fn line_number(row: usize, current_rows: &[usize], line_numbers: LineNumbers) -> usize {
+    match line_numbers {
+        LineNumbers::Absolute => row + 1,
+        LineNumbers::Relative => match current_rows
+            .iter()
+            .map(|r| (*r as i64 - row as i64).unsigned_abs() as usize)
+            .min()
+        {
+            Some(0) | None => row + 1,
+            Some(distance) => distance,
+        },
+    }
+}
+
+#[test]
+fn relative_line_numbers() {
+    assert_eq!(line_number(4, &[2], LineNumbers::Absolute), 5);
+    assert_eq!(line_number(4, &[2], LineNumbers::Relative), 2);
+    assert_eq!(line_number(0, &[2], LineNumbers::Relative), 2);
+    assert_eq!(line_number(2, &[2], LineNumbers::Relative), 3);
+    assert_eq!(line_number(5, &[0, 7], LineNumbers::Relative), 2);
+}
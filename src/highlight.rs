@@ -0,0 +1,188 @@
+use std::collections::BTreeSet;
+
+use ropey::Rope;
+use syntect::highlighting::{
+    HighlightIterator, HighlightState, Highlighter, ScopeSelectors, Style,
+};
+use syntect::parsing::{ParseState, ScopeStack, ScopeStackOp, SyntaxReference};
+
+use crate::fold::{fold_ranges, is_bracket, FoldRange};
+use crate::word::is_word_char;
+use crate::{theme, theme_name, PS};
+
+lazy_static! {
+    // the scopes of words that are part of the language, like `fn` and `bool` in rust
+    static ref KEYWORD_SCOPES: ScopeSelectors =
+        "keyword, storage, constant.language, support.type, variable.language"
+            .parse()
+            .unwrap();
+    // the scopes whose brackets do not open or close blocks
+    static ref NOT_CODE_SCOPES: ScopeSelectors = "string, comment".parse().unwrap();
+}
+
+// calls add with each piece of a line and the scopes it is in, the stack holds the scopes at the start of the line
+fn scoped_pieces(
+    text: &str,
+    ops: &[(usize, ScopeStackOp)],
+    mut stack: ScopeStack,
+    mut add: impl FnMut(&ScopeStack, &str),
+) {
+    let mut start = 0;
+    for (idx, op) in ops {
+        add(&stack, &text[start..*idx]);
+        start = *idx;
+        stack.apply(op);
+    }
+    add(&stack, &text[start..]);
+}
+
+/// The words of a line in a keyword scope, the stack holds the scopes at the start of the line.
+fn line_keywords(text: &str, ops: &[(usize, ScopeStackOp)], stack: ScopeStack) -> Vec<String> {
+    let mut keywords = Vec::new();
+    scoped_pieces(text, ops, stack, |stack, piece| {
+        if KEYWORD_SCOPES.does_match(stack.as_slice()).is_some() {
+            let words = piece
+                .split(|c: char| !is_word_char(c))
+                .filter(|w| w.chars().count() > 1 && !w.starts_with(char::is_numeric));
+            keywords.extend(words.map(str::to_string));
+        }
+    });
+    keywords
+}
+
+/// The brackets of a line outside of strings and comments, the stack holds the scopes at the start of the line.
+fn line_brackets(text: &str, ops: &[(usize, ScopeStackOp)], stack: ScopeStack) -> Vec<char> {
+    let mut brackets = Vec::new();
+    scoped_pieces(text, ops, stack, |stack, piece| {
+        if NOT_CODE_SCOPES.does_match(stack.as_slice()).is_none() {
+            brackets.extend(piece.chars().filter(|c| is_bracket(*c)));
+        }
+    });
+    brackets
+}
+
+#[derive(Debug, Clone)]
+struct HighlightedLine {
+    text: String,
+    ranges: Vec<(Style, String)>,
+    keywords: Vec<String>,
+    brackets: Vec<char>,
+    // the state after the line, used to continue highlighting from the next line
+    parse_state: ParseState,
+    highlight_state: HighlightState,
+}
+
+/// The syntax highlighting and foldable blocks of a buffer kept between renders.
+/// Only lines that changed, or come after a line that changed, are highlighted again.
+/// Changing the syntax or theme highlights every line again.
+#[derive(Debug, Default)]
+pub struct HighlightCache {
+    syntax: String,
+    theme: String,
+    lines: Vec<HighlightedLine>,
+    fold_ranges: Vec<FoldRange>,
+}
+
+impl HighlightCache {
+    pub fn update(&mut self, rope: &Rope, syntax: &SyntaxReference) {
+        let theme_name = theme_name();
+        if self.syntax != syntax.name || self.theme != theme_name {
+            self.syntax = syntax.name.clone();
+            self.theme = theme_name;
+            self.lines.clear();
+        }
+        let unchanged = self
+            .lines
+            .iter()
+            .zip(rope.lines())
+            .take_while(|(cached, line)| *line == cached.text.as_str())
+            .count();
+        if unchanged == self.lines.len() && unchanged == rope.len_lines() {
+            return;
+        }
+        self.lines.truncate(unchanged);
+
+        let highlighter = Highlighter::new(theme());
+        let (mut parse_state, mut highlight_state) = match self.lines.last() {
+            Some(last) => (last.parse_state.clone(), last.highlight_state.clone()),
+            None => (
+                ParseState::new(syntax),
+                HighlightState::new(&highlighter, ScopeStack::new()),
+            ),
+        };
+        for line in rope.lines().skip(unchanged) {
+            let text = line.to_string();
+            let ops = parse_state.parse_line(&text, &PS);
+            let keywords = line_keywords(&text, &ops, highlight_state.path.clone());
+            let brackets = line_brackets(&text, &ops, highlight_state.path.clone());
+            let ranges = HighlightIterator::new(&mut highlight_state, &ops, &text, &highlighter)
+                .map(|(style, t)| (style, t.to_string()))
+                .collect();
+            self.lines.push(HighlightedLine {
+                text,
+                ranges,
+                keywords,
+                brackets,
+                parse_state: parse_state.clone(),
+                highlight_state: highlight_state.clone(),
+            });
+        }
+        let lines: Vec<&str> = self.lines.iter().map(|l| l.text.as_str()).collect();
+        let brackets: Vec<&[char]> = self.lines.iter().map(|l| l.brackets.as_slice()).collect();
+        self.fold_ranges = fold_ranges(&lines, &brackets);
+    }
+
+    /// The highlighted sections of a line.
+    pub fn line(&self, row: usize) -> impl Iterator<Item = (Style, &str)> {
+        self.lines[row]
+            .ranges
+            .iter()
+            .map(|(style, text)| (*style, text.as_str()))
+    }
+
+    /// Every block of the buffer that can be folded sorted by start row.
+    pub fn fold_ranges(&self) -> &[FoldRange] {
+        &self.fold_ranges
+    }
+
+    /// The words of the buffer that are part of the language, like keywords and built in types.
+    pub fn keywords(&self) -> BTreeSet<String> {
+        self.lines
+            .iter()
+            .flat_map(|l| l.keywords.iter().cloned())
+            .collect()
+    }
+}
+
+#[test]
+fn only_changed_lines_are_highlighted() {
+    let syntax = PS.find_syntax_by_extension("rs").unwrap();
+    let mut cache = HighlightCache::default();
+    cache.update(&Rope::from_str("/* a\nb */\nc"), syntax);
+    let comment = cache.line(1).next().unwrap().0;
+
+    // the text of the last line is kept as long as the lines before it are the same
+    cache.update(&Rope::from_str("/* a\nb */\nd"), syntax);
+    assert_eq!(cache.lines[2].text, "d");
+    assert_eq!(cache.line(1).next().unwrap().0, comment);
+
+    // closing the comment earlier changes the style of the next line
+    cache.update(&Rope::from_str("/* a */\nb */\nd"), syntax);
+    assert_ne!(cache.line(1).next().unwrap().0, comment);
+}
+
+#[test]
+fn keywords_from_scopes() {
+    let syntax = PS.find_syntax_by_extension("rs").unwrap();
+    let mut cache = HighlightCache::default();
+    cache.update(
+        &Rope::from_str("fn main() {\n    let ok = true;\n}"),
+        syntax,
+    );
+    let keywords = cache.keywords();
+    assert!(keywords.contains("fn"));
+    assert!(keywords.contains("let"));
+    assert!(keywords.contains("true"));
+    assert!(!keywords.contains("main"));
+    assert!(!keywords.contains("ok"));
+}
@@ -0,0 +1,216 @@
+use std::ops::Range;
+
+use ropey::Rope;
+
+use crate::fold::Folds;
+
+static NO_FOLDS: Folds = Folds(Vec::new());
+
+/// How the text is shown on screen, movement between rows depends on what is visible.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Layout<'a> {
+    pub folds: &'a Folds,
+    // the number of columns to wrap lines at
+    pub wrap_width: Option<usize>,
+}
+
+impl<'a> Layout<'a> {
+    pub fn new(folds: &'a Folds, wrap_width: Option<usize>) -> Self {
+        Self { folds, wrap_width }
+    }
+
+    pub fn wrapped(&self, rope: &Rope, row: usize) -> WrappedLine {
+        WrappedLine::new(&rope.line(row).to_string(), self.wrap_width)
+    }
+
+    // the number of visual rows a line is shown on, hidden lines are not shown
+    fn height(&self, rope: &Rope, row: usize) -> usize {
+        if self.folds.is_hidden(row) {
+            0
+        } else {
+            self.wrapped(rope, row).starts.len()
+        }
+    }
+
+    /// The first line to show so that a line is on screen, scrolling as little as possible from the first line shown.
+    pub fn scroll_to(&self, rope: &Rope, scroll: usize, row: usize, rows: usize) -> usize {
+        if row <= scroll {
+            return row;
+        }
+        // the lines above the row that still fit on screen with it
+        let mut first = row;
+        let mut height = self.height(rope, row);
+        while first > scroll {
+            height += self.height(rope, first - 1);
+            if height > rows {
+                break;
+            }
+            first -= 1;
+        }
+        first
+    }
+
+    /// The lines on screen when the first line shown is scroll, including the hidden lines between them.
+    pub fn shown(&self, rope: &Rope, scroll: usize, rows: usize) -> Range<usize> {
+        let mut end = scroll;
+        let mut height = 0;
+        while end < rope.len_lines() && height < rows {
+            height += self.height(rope, end);
+            end += 1;
+        }
+        scroll..end
+    }
+}
+
+impl Default for Layout<'static> {
+    fn default() -> Self {
+        Self {
+            folds: &NO_FOLDS,
+            wrap_width: None,
+        }
+    }
+}
+
+/// A line split into the visual rows it is displayed on.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WrappedLine {
+    // the column each visual row starts at, the first row always starts at 0
+    pub starts: Vec<usize>,
+    // the indent added before every row after the first
+    pub indent: usize,
+}
+
+impl WrappedLine {
+    pub fn new(line: &str, width: Option<usize>) -> Self {
+        let chars: Vec<char> = line.trim_end_matches(&['\n', '\r'][..]).chars().collect();
+        let width = match width {
+            Some(width) => width.max(1),
+            None => {
+                return Self {
+                    starts: vec![0],
+                    indent: 0,
+                }
+            }
+        };
+        let indent = chars.iter().take_while(|c| c.is_whitespace()).count();
+        // only keep the indent if it leaves room for the text
+        let indent = if indent * 2 < width { indent } else { 0 };
+
+        let mut starts = vec![0];
+        let mut start = 0;
+        let mut available = width;
+        while chars.len() - start > available {
+            let limit = start + available;
+            // break before the last word that fits, or in the middle of a word that is too long
+            let row_end = (start + 1..=limit)
+                .rev()
+                .find(|i| {
+                    chars[i - 1].is_whitespace()
+                        && !chars[*i].is_whitespace()
+                        && chars[start..*i].iter().any(|c| !c.is_whitespace())
+                })
+                .unwrap_or(limit);
+            starts.push(row_end);
+            start = row_end;
+            available = width - indent;
+        }
+
+        Self { starts, indent }
+    }
+
+    /// The visual row a column is displayed on.
+    pub fn row_of(&self, col: usize) -> usize {
+        self.starts.iter().rposition(|s| *s <= col).unwrap_or(0)
+    }
+
+    /// The column a position is displayed at, including the continuation indent.
+    pub fn x(&self, col: usize) -> usize {
+        let row = self.row_of(col);
+        col - self.starts[row] + if row > 0 { self.indent } else { 0 }
+    }
+
+    /// The last column of a visual row.
+    pub fn row_end(&self, row: usize, len_line: usize) -> usize {
+        match self.starts.get(row + 1) {
+            Some(next) => next - 1,
+            None => len_line,
+        }
+    }
+
+    /// The column displayed closest to x on a visual row.
+    pub fn col_at(&self, row: usize, x: usize, len_line: usize) -> usize {
+        let indent = if row > 0 { self.indent } else { 0 };
+        (self.starts[row] + x.saturating_sub(indent)).min(self.row_end(row, len_line))
+    }
+}
+
+#[test]
+fn wrap_lines() {
+    assert_eq!(
+        WrappedLine::new("hello world\n", None),
+        WrappedLine {
+            starts: vec![0],
+            indent: 0
+        }
+    );
+    assert_eq!(
+        WrappedLine::new("hello world\n", Some(8)),
+        WrappedLine {
+            starts: vec![0, 6],
+            indent: 0
+        }
+    );
+    // words that are too long are split
+    assert_eq!(
+        WrappedLine::new("abcdefghij", Some(4)),
+        WrappedLine {
+            starts: vec![0, 4, 8],
+            indent: 0
+        }
+    );
+    // continuation rows keep the indent
+    assert_eq!(
+        WrappedLine::new("  aa bb cc dd", Some(7)),
+        WrappedLine {
+            starts: vec![0, 5, 8],
+            indent: 2
+        }
+    );
+}
+
+#[test]
+fn wrapped_columns() {
+    let line = WrappedLine::new("  aa bb cc dd", Some(7));
+
+    assert_eq!(line.row_of(4), 0);
+    assert_eq!(line.row_of(5), 1);
+    assert_eq!(line.x(6), 3);
+    assert_eq!(line.row_end(0, 13), 4);
+    assert_eq!(line.row_end(2, 13), 13);
+    assert_eq!(line.col_at(1, 3, 13), 6);
+    assert_eq!(line.col_at(1, 0, 13), 5);
+    assert_eq!(line.col_at(2, 10, 13), 13);
+}
+
+#[test]
+fn scroll_to_rows() {
+    let rope = Rope::from_str(&"line\n".repeat(30));
+    let layout = Layout::default();
+    // lines already on screen do not scroll
+    assert_eq!(layout.scroll_to(&rope, 5, 10, 10), 5);
+    assert_eq!(layout.scroll_to(&rope, 5, 14, 10), 5);
+    // lines below the screen are shown at its bottom, lines above it at its top
+    assert_eq!(layout.scroll_to(&rope, 5, 20, 10), 11);
+    assert_eq!(layout.scroll_to(&rope, 5, 2, 10), 2);
+    assert_eq!(layout.shown(&rope, 5, 10), 5..15);
+    assert_eq!(layout.shown(&rope, 25, 10), 25..31);
+
+    // folded lines take no room, wrapped lines take a row for each part
+    let folds = Folds(vec![crate::fold::FoldRange::new(12, 18)]);
+    let layout = Layout::new(&folds, None);
+    assert_eq!(layout.scroll_to(&rope, 0, 20, 10), 5);
+    assert_eq!(layout.shown(&rope, 5, 10), 5..21);
+    let rope = Rope::from_str(&"long line\n".repeat(30));
+    let layout = Layout::new(&NO_FOLDS, Some(5));
+    assert_eq!(layout.scroll_to(&rope, 0, 20, 10), 16);
+}
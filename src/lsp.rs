@@ -0,0 +1,926 @@
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::path::Path;
+use std::sync::atomic::{AtomicI64, AtomicU8, AtomicUsize, Ordering};
+use std::sync::{Arc, Condvar, Mutex};
+use std::time::{Duration, Instant};
+
+use lsp_types::{
+    CompletionItem, CompletionResponse, Diagnostic, DocumentChangeOperation, DocumentChanges,
+    GotoDefinitionResponse, Hover, HoverContents, Location, MarkedString, OneOf, Position,
+    PublishDiagnosticsParams, Range, TextEdit, Url, WorkspaceEdit,
+};
+use ropey::Rope;
+use serde::de::DeserializeOwned;
+use serde_json::{json, Value};
+
+use crate::buffer::Indent;
+use crate::utils::changed_chars;
+
+// how long to wait for the answer to a request before giving up
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+// servers index the project before answering the initialize request
+const INITIALIZE_TIMEOUT: Duration = Duration::from_secs(60);
+// how long a server has to exit after it is asked to
+const SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(2);
+
+// the kinds of text document sync of the protocol
+const SYNC_NONE: u8 = 0;
+const SYNC_FULL: u8 = 1;
+const SYNC_INCREMENTAL: u8 = 2;
+
+/// How to start the language server for a language.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ServerConfig {
+    // the name of the syntect syntax the server is used for
+    pub syntax: String,
+    pub language_id: String,
+    pub command: String,
+    pub args: Vec<String>,
+}
+
+impl ServerConfig {
+    fn new(syntax: &str, language_id: &str, command: &str, args: &[&str]) -> Self {
+        Self {
+            syntax: syntax.to_string(),
+            language_id: language_id.to_string(),
+            command: command.to_string(),
+            args: args.iter().map(|a| a.to_string()).collect(),
+        }
+    }
+}
+
+/// The LSP position of a char index, LSP counts columns in UTF-16 code units.
+pub fn to_position(rope: &Rope, idx: usize) -> Position {
+    let line = rope.char_to_line(idx);
+    let start = rope.line_to_char(line);
+    let character: usize = rope.slice(start..idx).chars().map(char::len_utf16).sum();
+    Position::new(line as u32, character as u32)
+}
+
+/// The char index of an LSP position, positions past the end of a line are moved to its end.
+pub fn to_idx(rope: &Rope, position: Position) -> usize {
+    let line = (position.line as usize).min(rope.len_lines() - 1);
+    let mut idx = rope.line_to_char(line);
+    let mut units = 0;
+    for c in rope.line(line).chars() {
+        if units >= position.character as usize || c == '\n' || c == '\r' {
+            break;
+        }
+        units += c.len_utf16();
+        idx += 1;
+    }
+    idx
+}
+
+/// Applies edits made to the text, edits at the same position are inserted in order.
+pub fn apply_edits(rope: &mut Rope, edits: &[TextEdit]) {
+    let mut edits: Vec<_> = edits
+        .iter()
+        .enumerate()
+        .map(|(i, e)| {
+            let start = to_idx(rope, e.range.start);
+            let end = to_idx(rope, e.range.end).max(start);
+            (i, start, end, e.new_text.as_str())
+        })
+        .collect();
+    // later edits are applied first so the positions of earlier edits stay the same
+    edits.sort_by_key(|(i, start, _, _)| std::cmp::Reverse((*start, *i)));
+    for (_, start, end, text) in edits {
+        rope.remove(start..end);
+        rope.insert(start, text);
+    }
+}
+
+/// The text of a hover, code blocks are shown as plain text.
+fn hover_text(hover: Hover) -> String {
+    let marked = |m: MarkedString| match m {
+        MarkedString::String(s) => s,
+        MarkedString::LanguageString(s) => s.value,
+    };
+    match hover.contents {
+        HoverContents::Scalar(m) => marked(m),
+        HoverContents::Array(ms) => ms.into_iter().map(marked).collect::<Vec<_>>().join("\n\n"),
+        HoverContents::Markup(m) => m.value,
+    }
+}
+
+/// The edits of every file changed by a workspace edit.
+fn workspace_edits(edit: WorkspaceEdit) -> HashMap<Url, Vec<TextEdit>> {
+    let mut changes = edit.changes.unwrap_or_default();
+    let documents = match edit.document_changes {
+        Some(DocumentChanges::Edits(edits)) => edits,
+        Some(DocumentChanges::Operations(operations)) => operations
+            .into_iter()
+            .filter_map(|o| match o {
+                DocumentChangeOperation::Edit(edit) => Some(edit),
+                // creating, renaming and deleting files is not supported
+                DocumentChangeOperation::Op(_) => None,
+            })
+            .collect(),
+        None => Vec::new(),
+    };
+    for document in documents {
+        let edits = changes.entry(document.text_document.uri).or_default();
+        edits.extend(document.edits.into_iter().map(|e| match e {
+            OneOf::Left(edit) => edit,
+            OneOf::Right(annotated) => annotated.text_edit,
+        }));
+    }
+    changes
+}
+
+/// Writes a message with the header the protocol expects.
+pub fn write_message(writer: &mut impl Write, message: &Value) -> io::Result<()> {
+    let body = message.to_string();
+    write!(writer, "Content-Length: {}\r\n\r\n{}", body.len(), body)?;
+    writer.flush()
+}
+
+/// Reads the next message, returns None once the stream is closed.
+pub fn read_message(reader: &mut impl BufRead) -> io::Result<Option<Value>> {
+    let mut length = None;
+    loop {
+        let mut line = String::new();
+        if reader.read_line(&mut line)? == 0 {
+            return Ok(None);
+        }
+        let line = line.trim_end();
+        if line.is_empty() {
+            break;
+        }
+        if let Some(value) = line.strip_prefix("Content-Length:") {
+            length = value.trim().parse::<usize>().ok();
+        }
+    }
+    let length = length.ok_or_else(|| {
+        io::Error::new(io::ErrorKind::InvalidData, "message without Content-Length")
+    })?;
+    let mut body = vec![0; length];
+    reader.read_exact(&mut body)?;
+    serde_json::from_slice(&body)
+        .map(Some)
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
+
+#[derive(Default)]
+struct Responses {
+    results: Mutex<HashMap<i64, Result<Value, String>>>,
+    received: Condvar,
+}
+
+/// A connection to a language server, messages from the server are read on a background thread.
+#[derive(Clone)]
+pub struct LspClient {
+    writer: Arc<Mutex<Box<dyn Write + Send>>>,
+    next_id: Arc<AtomicI64>,
+    responses: Arc<Responses>,
+    diagnostics: Arc<Mutex<HashMap<Url, Vec<Diagnostic>>>>,
+    // changes every time diagnostics are published, so views know to show them
+    diagnostics_version: Arc<AtomicUsize>,
+    // how the server wants to be told about changes, read from its answer to initialize
+    sync: Arc<AtomicU8>,
+    // the process of the server, killed if it does not exit when the client shuts it down
+    child: Arc<Mutex<Option<std::process::Child>>>,
+}
+
+impl fmt::Debug for LspClient {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("LspClient")
+            .field("next_id", &self.next_id)
+            .finish()
+    }
+}
+
+impl LspClient {
+    pub fn new(reader: impl Read + Send + 'static, writer: impl Write + Send + 'static) -> Self {
+        let client = Self {
+            writer: Arc::new(Mutex::new(Box::new(writer))),
+            next_id: Arc::new(AtomicI64::new(1)),
+            responses: Arc::new(Responses::default()),
+            diagnostics: Arc::new(Mutex::new(HashMap::new())),
+            diagnostics_version: Arc::new(AtomicUsize::new(0)),
+            sync: Arc::new(AtomicU8::new(SYNC_INCREMENTAL)),
+            child: Arc::new(Mutex::new(None)),
+        };
+        let background = client.clone();
+        std::thread::spawn(move || {
+            let mut reader = BufReader::new(reader);
+            while let Ok(Some(message)) = read_message(&mut reader) {
+                background.receive(message);
+            }
+        });
+        client
+    }
+
+    /// Starts a language server and initializes it for a project.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn start(config: &ServerConfig, root: &Path) -> io::Result<Self> {
+        let mut child = std::process::Command::new(&config.command)
+            .args(&config.args)
+            .current_dir(root)
+            .stdin(std::process::Stdio::piped())
+            .stdout(std::process::Stdio::piped())
+            .stderr(std::process::Stdio::null())
+            .spawn()?;
+        let stdin = child.stdin.take().unwrap();
+        let stdout = child.stdout.take().unwrap();
+        let client = Self::new(stdout, stdin);
+        *client.child.lock().unwrap() = Some(child);
+        if let Err(e) = client.initialize(root) {
+            client.kill();
+            return Err(io::Error::new(io::ErrorKind::Other, e));
+        }
+        Ok(client)
+    }
+
+    /// Asks the server to shut down and exit, it is killed if it does not exit in time.
+    pub fn shutdown(&self) {
+        let _ = self.request_within::<Value>("shutdown", Value::Null, SHUTDOWN_TIMEOUT);
+        self.notify("exit", Value::Null);
+        let deadline = Instant::now() + SHUTDOWN_TIMEOUT;
+        if let Some(child) = &mut *self.child.lock().unwrap() {
+            while Instant::now() < deadline {
+                match child.try_wait() {
+                    Ok(None) => std::thread::sleep(Duration::from_millis(50)),
+                    _ => break,
+                }
+            }
+        }
+        self.kill();
+    }
+
+    // kills the process of the server and waits for it, so it does not linger
+    fn kill(&self) {
+        if let Some(mut child) = self.child.lock().unwrap().take() {
+            let _ = child.kill();
+            let _ = child.wait();
+        }
+    }
+
+    fn receive(&self, message: Value) {
+        let id = message.get("id").and_then(Value::as_i64);
+        match (message.get("method").and_then(Value::as_str), id) {
+            // requests from the server are answered without doing anything, settings are left to their defaults
+            (Some(method), Some(_)) => {
+                let result = match method {
+                    "workspace/configuration" => {
+                        let items = message["params"]["items"].as_array().map_or(0, Vec::len);
+                        Value::Array(vec![Value::Null; items])
+                    }
+                    _ => Value::Null,
+                };
+                self.send(&json!({"jsonrpc": "2.0", "id": message["id"], "result": result}));
+            }
+            (Some("textDocument/publishDiagnostics"), None) => {
+                let params =
+                    serde_json::from_value::<PublishDiagnosticsParams>(message["params"].clone());
+                if let Ok(params) = params {
+                    self.diagnostics
+                        .lock()
+                        .unwrap()
+                        .insert(params.uri, params.diagnostics);
+                    self.diagnostics_version.fetch_add(1, Ordering::SeqCst);
+                }
+            }
+            (Some(_), None) => {}
+            (None, Some(id)) => {
+                let result = match message.get("error") {
+                    Some(error) => Err(error["message"].as_str().unwrap_or("error").to_string()),
+                    None => Ok(message.get("result").cloned().unwrap_or(Value::Null)),
+                };
+                self.responses.results.lock().unwrap().insert(id, result);
+                self.responses.received.notify_all();
+            }
+            (None, None) => {}
+        }
+    }
+
+    fn send(&self, message: &Value) {
+        // a server that exited can not be written to, its requests time out
+        let _ = write_message(&mut *self.writer.lock().unwrap(), message);
+    }
+
+    /// Sends a notification, null params are left out.
+    pub fn notify(&self, method: &str, params: Value) {
+        let mut message = json!({"jsonrpc": "2.0", "method": method});
+        if !params.is_null() {
+            message["params"] = params;
+        }
+        self.send(&message);
+    }
+
+    /// Sends a request and waits for the answer of the server, it blocks so it is called on background threads.
+    pub fn request<T: DeserializeOwned>(&self, method: &str, params: Value) -> Result<T, String> {
+        self.request_within(method, params, REQUEST_TIMEOUT)
+    }
+
+    fn request_within<T: DeserializeOwned>(
+        &self,
+        method: &str,
+        params: Value,
+        timeout: Duration,
+    ) -> Result<T, String> {
+        let id = self.next_id.fetch_add(1, Ordering::SeqCst);
+        let mut message = json!({"jsonrpc": "2.0", "id": id, "method": method});
+        if !params.is_null() {
+            message["params"] = params;
+        }
+        self.send(&message);
+        let deadline = Instant::now() + timeout;
+        let mut results = self.responses.results.lock().unwrap();
+        loop {
+            if let Some(result) = results.remove(&id) {
+                return serde_json::from_value(result?).map_err(|e| e.to_string());
+            }
+            let now = Instant::now();
+            if now >= deadline {
+                return Err(format!("{} timed out", method));
+            }
+            results = self
+                .responses
+                .received
+                .wait_timeout(results, deadline - now)
+                .unwrap()
+                .0;
+        }
+    }
+
+    pub fn initialize(&self, root: &Path) -> Result<(), String> {
+        let root = Url::from_file_path(root).map_err(|_| "invalid root".to_string())?;
+        let result = self.request_within::<Value>(
+            "initialize",
+            json!({
+                "processId": std::process::id(),
+                "rootUri": root,
+                "capabilities": {
+                    "textDocument": {
+                        "synchronization": {"dynamicRegistration": false},
+                        "hover": {"contentFormat": ["plaintext", "markdown"]},
+                        "completion": {"completionItem": {"snippetSupport": true}},
+                        "definition": {"linkSupport": true},
+                        "references": {},
+                        "rename": {},
+                        "formatting": {},
+                        "publishDiagnostics": {},
+                    },
+                    "workspace": {
+                        "workspaceEdit": {"documentChanges": true},
+                        "configuration": true,
+                    },
+                },
+            }),
+            INITIALIZE_TIMEOUT,
+        )?;
+        // the sync kind is either a number or the change field of the sync options, servers that leave it out get the full text
+        let sync = &result["capabilities"]["textDocumentSync"];
+        let kind = sync.as_u64().or_else(|| sync["change"].as_u64());
+        let kind = match kind {
+            Some(0) => SYNC_NONE,
+            Some(2) => SYNC_INCREMENTAL,
+            _ => SYNC_FULL,
+        };
+        self.sync.store(kind, Ordering::SeqCst);
+        self.notify("initialized", json!({}));
+        Ok(())
+    }
+
+    pub fn did_open(&self, uri: &Url, language_id: &str, version: i32, text: &Rope) {
+        self.notify(
+            "textDocument/didOpen",
+            json!({
+                "textDocument": {
+                    "uri": uri,
+                    "languageId": language_id,
+                    "version": version,
+                    "text": text.to_string(),
+                },
+            }),
+        );
+    }
+
+    /// Sends the part of the text that changed, or the whole text to servers that only take full changes.
+    pub fn did_change(&self, uri: &Url, version: i32, old: &Rope, new: &Rope) {
+        let change = match self.sync.load(Ordering::SeqCst) {
+            SYNC_NONE => return,
+            SYNC_FULL => json!({"text": new.to_string()}),
+            _ => {
+                let (start, old_end, new_end) = changed_chars(old, new);
+                let range = Range::new(to_position(old, start), to_position(old, old_end));
+                json!({"range": range, "text": new.slice(start..new_end).to_string()})
+            }
+        };
+        self.notify(
+            "textDocument/didChange",
+            json!({
+                "textDocument": {"uri": uri, "version": version},
+                "contentChanges": [change],
+            }),
+        );
+    }
+
+    fn position_params(uri: &Url, position: Position) -> Value {
+        json!({"textDocument": {"uri": uri}, "position": position})
+    }
+
+    pub fn hover(&self, uri: &Url, position: Position) -> Result<Option<String>, String> {
+        let hover: Option<Hover> =
+            self.request("textDocument/hover", Self::position_params(uri, position))?;
+        Ok(hover.map(hover_text).filter(|h| !h.is_empty()))
+    }
+
+    pub fn completion(&self, uri: &Url, position: Position) -> Result<Vec<CompletionItem>, String> {
+        let completion: Option<CompletionResponse> = self.request(
+            "textDocument/completion",
+            Self::position_params(uri, position),
+        )?;
+        Ok(match completion {
+            Some(CompletionResponse::Array(items)) => items,
+            Some(CompletionResponse::List(list)) => list.items,
+            None => Vec::new(),
+        })
+    }
+
+    pub fn definition(&self, uri: &Url, position: Position) -> Result<Vec<Location>, String> {
+        let definition: Option<GotoDefinitionResponse> = self.request(
+            "textDocument/definition",
+            Self::position_params(uri, position),
+        )?;
+        Ok(match definition {
+            Some(GotoDefinitionResponse::Scalar(location)) => vec![location],
+            Some(GotoDefinitionResponse::Array(locations)) => locations,
+            Some(GotoDefinitionResponse::Link(links)) => links
+                .into_iter()
+                .map(|l| Location {
+                    uri: l.target_uri,
+                    range: l.target_selection_range,
+                })
+                .collect(),
+            None => Vec::new(),
+        })
+    }
+
+    pub fn references(&self, uri: &Url, position: Position) -> Result<Vec<Location>, String> {
+        let mut params = Self::position_params(uri, position);
+        params["context"] = json!({"includeDeclaration": true});
+        let references: Option<Vec<Location>> = self.request("textDocument/references", params)?;
+        Ok(references.unwrap_or_default())
+    }
+
+    pub fn rename(
+        &self,
+        uri: &Url,
+        position: Position,
+        new_name: &str,
+    ) -> Result<HashMap<Url, Vec<TextEdit>>, String> {
+        let mut params = Self::position_params(uri, position);
+        params["newName"] = json!(new_name);
+        let edit: Option<WorkspaceEdit> = self.request("textDocument/rename", params)?;
+        Ok(edit.map(workspace_edits).unwrap_or_default())
+    }
+
+    pub fn formatting(&self, uri: &Url, indent: Indent) -> Result<Vec<TextEdit>, String> {
+        let (tab_size, insert_spaces) = match indent {
+            Indent::Tabs => (4, false),
+            Indent::Spaces(n) => (n, true),
+        };
+        let edits: Option<Vec<TextEdit>> = self.request(
+            "textDocument/formatting",
+            json!({
+                "textDocument": {"uri": uri},
+                "options": {"tabSize": tab_size, "insertSpaces": insert_spaces},
+            }),
+        )?;
+        Ok(edits.unwrap_or_default())
+    }
+
+    pub fn diagnostics(&self, uri: &Url) -> Vec<Diagnostic> {
+        self.diagnostics
+            .lock()
+            .unwrap()
+            .get(uri)
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    pub fn diagnostics_version(&self) -> usize {
+        self.diagnostics_version.load(Ordering::SeqCst)
+    }
+}
+
+/// The answer to a request sent to a language server on a background thread.
+#[derive(Debug)]
+pub struct Pending<T> {
+    result: Arc<Mutex<Option<Result<T, String>>>>,
+}
+
+impl<T> Clone for Pending<T> {
+    fn clone(&self) -> Self {
+        Self {
+            result: self.result.clone(),
+        }
+    }
+}
+
+impl<T: Send + 'static> Pending<T> {
+    /// Sends the request on a background thread, so the editor keeps running while the server answers.
+    pub fn start(ask: impl FnOnce() -> Result<T, String> + Send + 'static) -> Self {
+        let pending = Self {
+            result: Arc::new(Mutex::new(None)),
+        };
+        let result = pending.result.clone();
+        let ask = move || {
+            let answer = ask();
+            *result.lock().unwrap() = Some(answer);
+        };
+        #[cfg(not(target_arch = "wasm32"))]
+        std::thread::spawn(ask);
+        #[cfg(target_arch = "wasm32")]
+        ask();
+        pending
+    }
+
+    pub fn is_received(&self) -> bool {
+        self.result.lock().unwrap().is_some()
+    }
+
+    /// The answer once it is received, it can only be taken once.
+    pub fn take(&self) -> Option<Result<T, String>> {
+        self.result.lock().unwrap().take()
+    }
+}
+
+// the state of the language server of a syntax
+#[derive(Debug)]
+enum Server {
+    Starting,
+    Running(LspClient),
+    // servers that could not be started are not tried again
+    Failed,
+}
+
+/// The language servers of a project, started the first time a file of their language is opened.
+#[derive(Debug, Clone)]
+pub struct LanguageServers {
+    pub configs: Vec<ServerConfig>,
+    // by syntax, shared with the threads starting the servers
+    servers: Arc<Mutex<HashMap<String, Server>>>,
+}
+
+impl Default for LanguageServers {
+    fn default() -> Self {
+        Self {
+            configs: vec![
+                ServerConfig::new("Rust", "rust", "rust-analyzer", &[]),
+                ServerConfig::new("C", "c", "clangd", &[]),
+                ServerConfig::new("C++", "cpp", "clangd", &[]),
+                ServerConfig::new("Python", "python", "pyright-langserver", &["--stdio"]),
+            ],
+            servers: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+}
+
+impl LanguageServers {
+    pub fn config(&self, syntax: &str) -> Option<&ServerConfig> {
+        self.configs.iter().find(|c| c.syntax == syntax)
+    }
+
+    /// The running server for a syntax.
+    pub fn get(&self, syntax: &str) -> Option<LspClient> {
+        match self.servers.lock().unwrap().get(syntax) {
+            Some(Server::Running(client)) => Some(client.clone()),
+            _ => None,
+        }
+    }
+
+    /// If a server is configured for the syntax that was not started yet.
+    pub fn can_start(&self, syntax: &str) -> bool {
+        self.config(syntax).is_some() && !self.servers.lock().unwrap().contains_key(syntax)
+    }
+
+    pub fn is_starting(&self, syntax: &str) -> bool {
+        matches!(
+            self.servers.lock().unwrap().get(syntax),
+            Some(Server::Starting)
+        )
+    }
+
+    /// Starts the server for a syntax on a background thread, it can be used once it is running.
+    pub fn start(&self, syntax: &str, root: &Path) {
+        let config = match self.config(syntax) {
+            Some(config) if self.can_start(syntax) => config.clone(),
+            _ => return,
+        };
+        let mut servers = self.servers.lock().unwrap();
+        #[cfg(not(target_arch = "wasm32"))]
+        {
+            servers.insert(syntax.to_string(), Server::Starting);
+            let servers = self.servers.clone();
+            let syntax = syntax.to_string();
+            let root = root.to_path_buf();
+            std::thread::spawn(move || {
+                let server = match LspClient::start(&config, &root) {
+                    Ok(client) => Server::Running(client),
+                    Err(_) => Server::Failed,
+                };
+                servers.lock().unwrap().insert(syntax, server);
+            });
+        }
+        // the web build can not start processes
+        #[cfg(target_arch = "wasm32")]
+        {
+            let _ = (config, root);
+            servers.insert(syntax.to_string(), Server::Failed);
+        }
+    }
+
+    /// Shuts down the running servers of syntaxes not in use anymore, they are started again when a file needs them.
+    pub fn stop_unused(&self, used: &HashSet<String>) {
+        let mut servers = self.servers.lock().unwrap();
+        let unused: Vec<_> = servers
+            .iter()
+            .filter(|(syntax, server)| {
+                matches!(server, Server::Running(_)) && !used.contains(*syntax)
+            })
+            .map(|(syntax, _)| syntax.clone())
+            .collect();
+        for syntax in unused {
+            if let Some(Server::Running(client)) = servers.remove(&syntax) {
+                #[cfg(not(target_arch = "wasm32"))]
+                std::thread::spawn(move || client.shutdown());
+                #[cfg(target_arch = "wasm32")]
+                client.shutdown();
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+struct PipeReader {
+    receiver: std::sync::mpsc::Receiver<Vec<u8>>,
+    buffer: Vec<u8>,
+}
+
+#[cfg(test)]
+impl Read for PipeReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.buffer.is_empty() {
+            match self.receiver.recv() {
+                Ok(bytes) => self.buffer = bytes,
+                // the writer was dropped
+                Err(_) => return Ok(0),
+            }
+        }
+        let len = buf.len().min(self.buffer.len());
+        buf[..len].copy_from_slice(&self.buffer[..len]);
+        self.buffer.drain(..len);
+        Ok(len)
+    }
+}
+
+#[cfg(test)]
+struct PipeWriter(std::sync::mpsc::Sender<Vec<u8>>);
+
+#[cfg(test)]
+impl Write for PipeWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0
+            .send(buf.to_vec())
+            .map_err(|_| io::Error::from(io::ErrorKind::BrokenPipe))?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+fn pipe() -> (PipeWriter, PipeReader) {
+    let (sender, receiver) = std::sync::mpsc::channel();
+    (
+        PipeWriter(sender),
+        PipeReader {
+            receiver,
+            buffer: Vec::new(),
+        },
+    )
+}
+
+/// A server that answers requests with the scripted result for their method and records every message.
+/// Scripted notifications are sent back after the client sends a notification with the same method.
+#[cfg(test)]
+fn mock_server(script: Vec<(&'static str, Value)>) -> (LspClient, Arc<Mutex<Vec<Value>>>) {
+    let (client_writer, server_reader) = pipe();
+    let (mut server_writer, client_reader) = pipe();
+    let received = Arc::new(Mutex::new(Vec::new()));
+    let server_received = received.clone();
+    std::thread::spawn(move || {
+        let mut reader = BufReader::new(server_reader);
+        while let Ok(Some(message)) = read_message(&mut reader) {
+            server_received.lock().unwrap().push(message.clone());
+            let method = message["method"].as_str().unwrap_or_default();
+            let scripted = script
+                .iter()
+                .find(|(m, _)| *m == method)
+                .map(|(_, v)| v.clone());
+            let answer = match (message.get("id"), scripted) {
+                (Some(id), result) => {
+                    json!({"jsonrpc": "2.0", "id": id, "result": result.unwrap_or(Value::Null)})
+                }
+                (None, Some(notification)) => notification,
+                (None, None) => continue,
+            };
+            write_message(&mut server_writer, &answer).unwrap();
+        }
+    });
+    (LspClient::new(client_reader, client_writer), received)
+}
+
+#[test]
+fn utf16_positions() {
+    let rope = Rope::from_str("a😀b\r\nc");
+    assert_eq!(to_position(&rope, 2), Position::new(0, 3));
+    assert_eq!(to_idx(&rope, Position::new(0, 3)), 2);
+    // positions past the end of a line stay on the line
+    assert_eq!(to_idx(&rope, Position::new(0, 10)), 3);
+    assert_eq!(to_idx(&rope, Position::new(1, 1)), 6);
+
+    let mut rope = Rope::from_str("let a = 1;\nlet b = a;\n");
+    let edit = |line, start, end, text: &str| {
+        TextEdit::new(
+            Range::new(Position::new(line, start), Position::new(line, end)),
+            text.to_string(),
+        )
+    };
+    apply_edits(
+        &mut rope,
+        &[
+            edit(0, 4, 5, "x"),
+            edit(1, 8, 9, "x"),
+            edit(1, 0, 0, "// "),
+            edit(1, 0, 0, "a "),
+        ],
+    );
+    assert_eq!(rope.to_string(), "let x = 1;\n// a let b = x;\n");
+}
+
+#[test]
+fn mock_language_server() {
+    let uri = Url::from_file_path("/project/src/main.rs").unwrap();
+    let location = json!({
+        "uri": uri,
+        "range": {"start": {"line": 0, "character": 4}, "end": {"line": 0, "character": 5}},
+    });
+    let (client, received) = mock_server(vec![
+        (
+            "initialize",
+            json!({"capabilities": {"textDocumentSync": {"openClose": true, "change": 2}}}),
+        ),
+        (
+            "textDocument/didChange",
+            json!({
+                "jsonrpc": "2.0",
+                "method": "textDocument/publishDiagnostics",
+                "params": {
+                    "uri": uri,
+                    "diagnostics": [{
+                        "range": {"start": {"line": 0, "character": 4}, "end": {"line": 0, "character": 5}},
+                        "severity": 2,
+                        "message": "unused variable",
+                    }],
+                },
+            }),
+        ),
+        (
+            "textDocument/hover",
+            json!({"contents": {"kind": "markdown", "value": "let a: i32"}}),
+        ),
+        ("textDocument/definition", location.clone()),
+        (
+            "textDocument/references",
+            json!([location.clone(), location]),
+        ),
+        (
+            "textDocument/completion",
+            json!({"isIncomplete": false, "items": [{"label": "abs"}, {"label": "add"}]}),
+        ),
+        (
+            "textDocument/rename",
+            json!({"changes": {"file:///project/src/main.rs": [{
+                "range": {"start": {"line": 0, "character": 4}, "end": {"line": 0, "character": 5}},
+                "newText": "b",
+            }]}}),
+        ),
+        ("textDocument/formatting", json!([])),
+    ]);
+    client.initialize(Path::new("/project")).unwrap();
+
+    let old = Rope::from_str("let a = 1;\n");
+    client.did_open(&uri, "rust", 0, &old);
+    let version = client.diagnostics_version();
+    let new = Rope::from_str("let a = 12;\n");
+    client.did_change(&uri, 1, &old, &new);
+
+    let position = Position::new(0, 4);
+    assert_eq!(
+        client.hover(&uri, position).unwrap(),
+        Some("let a: i32".to_string())
+    );
+    assert_eq!(client.definition(&uri, position).unwrap().len(), 1);
+    assert_eq!(client.references(&uri, position).unwrap().len(), 2);
+    let labels: Vec<_> = client
+        .completion(&uri, position)
+        .unwrap()
+        .into_iter()
+        .map(|c| c.label)
+        .collect();
+    assert_eq!(labels, vec!["abs", "add"]);
+    let mut renamed = new.clone();
+    apply_edits(
+        &mut renamed,
+        &client.rename(&uri, position, "b").unwrap()[&uri],
+    );
+    assert_eq!(renamed.to_string(), "let b = 12;\n");
+    assert_eq!(
+        client.formatting(&uri, Indent::Spaces(4)).unwrap(),
+        Vec::new()
+    );
+
+    // the diagnostics were published before the answer to the hover
+    assert_ne!(client.diagnostics_version(), version);
+    assert_eq!(client.diagnostics(&uri)[0].message, "unused variable");
+
+    // only the changed text is sent
+    let received = received.lock().unwrap();
+    let change = received
+        .iter()
+        .find(|m| m["method"] == "textDocument/didChange")
+        .unwrap();
+    assert_eq!(
+        change["params"]["contentChanges"][0],
+        json!({
+            "range": {"start": {"line": 0, "character": 9}, "end": {"line": 0, "character": 9}},
+            "text": "2",
+        })
+    );
+    assert_eq!(received[1]["method"], "initialized");
+}
+
+#[test]
+fn full_sync_and_server_requests() {
+    let uri = Url::from_file_path("/project/main.py").unwrap();
+    let (client, received) = mock_server(vec![
+        (
+            "initialize",
+            json!({"capabilities": {"textDocumentSync": 1}}),
+        ),
+        (
+            "initialized",
+            json!({
+                "jsonrpc": "2.0",
+                "id": 7,
+                "method": "workspace/configuration",
+                "params": {"items": [{"section": "python"}, {"section": "python.analysis"}]},
+            }),
+        ),
+    ]);
+    client.initialize(Path::new("/project")).unwrap();
+    client.did_change(
+        &uri,
+        1,
+        &Rope::from_str("a = 1\n"),
+        &Rope::from_str("a = 12\n"),
+    );
+    // the answer to shutdown comes after every message sent before it
+    client.shutdown();
+    // exit is sent after the answer to shutdown
+    while !received
+        .lock()
+        .unwrap()
+        .iter()
+        .any(|m| m["method"] == "exit")
+    {
+        std::thread::yield_now();
+    }
+
+    let received = received.lock().unwrap();
+    let change = received
+        .iter()
+        .find(|m| m["method"] == "textDocument/didChange")
+        .unwrap();
+    assert_eq!(
+        change["params"]["contentChanges"],
+        json!([{"text": "a = 12\n"}])
+    );
+    // every configuration item is answered
+    let answer = received.iter().find(|m| m["id"] == 7).unwrap();
+    assert_eq!(answer["result"], json!([null, null]));
+    let methods: Vec<_> = received
+        .iter()
+        .filter_map(|m| m["method"].as_str())
+        .collect();
+    assert_eq!(&methods[methods.len() - 2..], &["shutdown", "exit"]);
+}
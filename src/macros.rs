@@ -0,0 +1,225 @@
+use std::collections::BTreeMap;
+
+use dioxus::prelude::UseRef;
+#[cfg(test)]
+use dioxus::prelude::{use_ref, Element, Scope, VirtualDom};
+use serde::{Deserialize, Serialize};
+
+#[cfg(test)]
+use crate::buffer::Buffer;
+use crate::commands::{Context, COMMANDS};
+use crate::cursor::{Cursor, Pos};
+use crate::cursors::Cursors;
+use crate::storage;
+use crate::workspace::Workspace;
+
+// the file the saved macros are kept in
+const MACROS_FILE: &str = "macros.json";
+
+/// A step of a macro, commands are kept by title so playback does not depend on the keys that ran them.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Step {
+    Command(String),
+    // text typed at every cursor
+    Type(String),
+}
+
+// text typed one key after another is one step
+fn push_step(steps: &mut Vec<Step>, step: Step) {
+    match (steps.last_mut(), step) {
+        (Some(Step::Type(typed)), Step::Type(text)) => typed.push_str(&text),
+        (_, step) => steps.push(step),
+    }
+}
+
+/// Adds a step to the macro being recorded.
+pub fn record(workspace: &UseRef<Workspace>, step: Step) {
+    if workspace.read().recording.is_none() {
+        return;
+    }
+    if let Some(steps) = &mut workspace.write().recording {
+        push_step(steps, step);
+    }
+}
+
+/// Starts recording a macro, or stops and keeps the recording as the last macro.
+pub fn toggle_recording(workspace: &mut Workspace) {
+    match workspace.recording.take() {
+        Some(steps) => {
+            workspace.message = Some(format!("Recorded a macro of {} steps", steps.len()));
+            workspace.last_macro = steps;
+        }
+        None => workspace.recording = Some(Vec::new()),
+    }
+}
+
+fn play_once(ctx: &mut Context, steps: &[Step]) -> Result<(), String> {
+    for step in steps {
+        match step {
+            Step::Command(title) => COMMANDS
+                .iter()
+                .find(|c| c.title() == *title)
+                .ok_or_else(|| format!("Unknown command {}", title))?
+                .run(ctx),
+            Step::Type(text) => {
+                ctx.cursors.insert_text(text, &mut ctx.buffer.rope);
+                record(ctx.workspace, step.clone());
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Plays the last macro a number of times.
+pub fn play(ctx: &mut Context, times: usize) {
+    let steps = ctx.workspace.read().last_macro.clone();
+    if steps.is_empty() {
+        ctx.workspace.write().message = Some("No macro was recorded".to_string());
+        return;
+    }
+    if let Err(e) = (0..times).try_for_each(|_| play_once(ctx, &steps)) {
+        ctx.workspace.write().message = Some(e);
+    }
+}
+
+/// Plays the last macro once on every selected line, with the cursor at the start of the line.
+pub fn play_on_lines(ctx: &mut Context) {
+    let steps = ctx.workspace.read().last_macro.clone();
+    if steps.is_empty() {
+        ctx.workspace.write().message = Some("No macro was recorded".to_string());
+        return;
+    }
+    let mut rows: Vec<usize> = ctx
+        .cursors
+        .list
+        .iter()
+        .flat_map(|c| {
+            let (first, last) = (c.first(), c.last());
+            // a selection that ends at the start of a line does not include it
+            let last_row = if last.row() > first.row() && last.col(&ctx.buffer.rope) == 0 {
+                last.row() - 1
+            } else {
+                last.row()
+            };
+            first.row()..=last_row
+        })
+        .collect();
+    rows.sort_unstable();
+    rows.dedup();
+    // from the last line up, so edits do not move the lines that are still to be played on
+    for row in rows.into_iter().rev() {
+        *ctx.cursors = Cursors::new(vec![Cursor::from_start(Pos::new(0, row))]);
+        if let Err(e) = play_once(ctx, &steps) {
+            ctx.workspace.write().message = Some(e);
+            return;
+        }
+    }
+}
+
+/// The macros saved by name.
+pub fn saved_macros() -> BTreeMap<String, Vec<Step>> {
+    storage::load(MACROS_FILE)
+        .and_then(|json| serde_json::from_str(&json).ok())
+        .unwrap_or_default()
+}
+
+/// Saves the last macro by name, replacing a saved macro with the same name.
+pub fn save_macro(workspace: &mut Workspace, name: &str) -> Result<(), String> {
+    if workspace.last_macro.is_empty() {
+        return Err("No macro was recorded".to_string());
+    }
+    let mut macros = saved_macros();
+    macros.insert(name.to_string(), workspace.last_macro.clone());
+    let json = serde_json::to_string_pretty(&macros).map_err(|e| e.to_string())?;
+    storage::save(MACROS_FILE, &json)
+}
+
+#[test]
+fn record_steps() {
+    let mut workspace = Workspace::default();
+    toggle_recording(&mut workspace);
+    let steps = workspace.recording.as_mut().unwrap();
+    for step in [
+        Step::Type("a".to_string()),
+        Step::Type("b".to_string()),
+        Step::Command("Edit: New Line".to_string()),
+        Step::Type("c".to_string()),
+    ] {
+        push_step(steps, step);
+    }
+    toggle_recording(&mut workspace);
+    assert_eq!(workspace.recording, None);
+    assert_eq!(
+        workspace.last_macro,
+        vec![
+            Step::Type("ab".to_string()),
+            Step::Command("Edit: New Line".to_string()),
+            Step::Type("c".to_string()),
+        ]
+    );
+    let json = serde_json::to_string(&workspace.last_macro).unwrap();
+    assert_eq!(
+        serde_json::from_str::<Vec<Step>>(&json).unwrap(),
+        workspace.last_macro
+    );
+}
+
+// plays macros on a buffer, hooks only exist while a component renders
+#[cfg(test)]
+fn play_on_buffer(cx: Scope) -> Element {
+    let workspace = use_ref(&cx, Workspace::default);
+    let settings = use_ref(&cx, crate::settings::Settings::default);
+    let mut buffer = Buffer::new("a\nb\nc\n", "txt");
+    let mut cursors = Cursors::new(vec![Cursor::from_start(Pos::new(0, 0))]);
+    let mut folds = crate::fold::Folds::default();
+    let keywords = Default::default();
+    let mut ctx = Context {
+        buffer: &mut buffer,
+        cursors: &mut cursors,
+        folds: &mut folds,
+        settings,
+        workspace,
+        wrap_width: None,
+        tab: 0,
+        keywords: &keywords,
+        fold_ranges: &[],
+    };
+
+    // commands are found by their title
+    workspace.write().last_macro = vec![
+        Step::Command("Cursor: Move Line Start".to_string()),
+        Step::Type("- ".to_string()),
+        Step::Command("Cursor: Move Down".to_string()),
+    ];
+    play(&mut ctx, 2);
+    assert_eq!(ctx.buffer.rope.to_string(), "- a\n- b\nc\n");
+    assert_eq!(ctx.cursors.primary().caret().row(), 2);
+
+    // a selection that ends at the start of a line does not play on it
+    *ctx.cursors = Cursors::new(vec![Cursor::new(Pos::new(1, 0), Pos::new(0, 3))]);
+    workspace.write().last_macro = vec![
+        Step::Command("Cursor: Move Line End".to_string()),
+        Step::Type(";".to_string()),
+    ];
+    play_on_lines(&mut ctx);
+    assert_eq!(ctx.buffer.rope.to_string(), "- a;\n- b;\nc;\n");
+
+    // the cursor is left on the first line, playing stops at a command that does not exist
+    workspace.write().last_macro = vec![
+        Step::Type("x".to_string()),
+        Step::Command("Cursor: Fly".to_string()),
+        Step::Type("y".to_string()),
+    ];
+    play(&mut ctx, 3);
+    assert_eq!(ctx.buffer.rope.to_string(), "- a;x\n- b;\nc;\n");
+    assert_eq!(
+        workspace.read().message.as_deref(),
+        Some("Unknown command Cursor: Fly")
+    );
+    None
+}
+
+#[test]
+fn play_steps() {
+    VirtualDom::new(play_on_buffer).rebuild();
+}
@@ -2,21 +2,122 @@
 extern crate lazy_static;
 
 use dioxus::prelude::*;
+use lsp_types::Url;
+use ropey::Rope;
+use std::cell::{Cell, RefCell};
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use std::sync::RwLock;
 use syntect::highlighting::{Theme, ThemeSet};
 use syntect::parsing::SyntaxSet;
 
+use crate::buffer::Buffer;
+use crate::caret::CARET_KEYFRAMES;
 use crate::code_area::CodeArea;
+use crate::commands::receive_code_request;
+use crate::colors::EditorColors;
+use crate::conflict_panel::ConflictPanel;
+use crate::cursors::Cursors;
+use crate::diagnostics::Diagnostics;
+use crate::explorer::Explorer;
+use crate::explorer_panel::ExplorerPanel;
+use crate::finder::FileIndex;
+use crate::fold::Folds;
+use crate::problems_panel::ProblemsPanel;
+use crate::recovery::{load_swaps, next_run, unrecovered, write_swaps};
+use crate::recovery_panel::RecoveryPanel;
+use crate::search_panel::SearchPanel;
+use crate::session::{load_session, save_session, Session};
+use crate::settings::Settings;
+use crate::status_bar::StatusBar;
+use crate::tab_bar::TabBar;
+#[cfg(all(not(target_arch = "wasm32"), not(feature = "term")))]
+use crate::utils::{CHAR_WIDTH, DEFAULT_COLUMNS, DEFAULT_ROWS, LINE_HEIGHT};
+use crate::utils::{modified, sleep, viewport_columns, viewport_rows};
+use crate::workspace::{Edit, Workspace};
 
+mod buffer;
+mod caret;
 mod code_area;
+mod colors;
+mod commands;
+mod comment;
+mod completion;
+mod completion_popup;
+mod conflict_panel;
 mod cursor;
 mod cursors;
+mod diagnostics;
+mod diff;
+mod encoding;
+mod explorer;
+mod explorer_panel;
+mod finder;
+mod fold;
+mod fuzzy;
+mod gutter;
+mod highlight;
+mod layout;
+mod lsp;
+mod macros;
+mod palette;
+mod problems_panel;
+mod recovery;
+mod recovery_panel;
+mod search;
+mod search_panel;
+mod session;
+mod settings;
+mod snippet;
 mod span;
+mod status_bar;
+mod storage;
+mod tab_bar;
+mod tabs;
 mod utils;
+mod watch;
+mod whitespace;
+mod word;
+mod workspace;
 
 lazy_static! {
     static ref PS: SyntaxSet = SyntaxSet::load_defaults_newlines();
     static ref TS: ThemeSet = ThemeSet::load_defaults();
-    static ref THEME: &'static Theme = &TS.themes["base16-ocean.dark"];
+    // the editor colors of every theme
+    static ref THEME_COLORS: HashMap<String, EditorColors> = TS
+        .themes
+        .iter()
+        .map(|(name, theme)| (name.clone(), EditorColors::new(&theme.settings)))
+        .collect();
+    static ref THEME_NAME: RwLock<String> = RwLock::new("base16-ocean.dark".to_string());
+}
+
+// how often a tab checks for new diagnostics from its language server, or if it is running yet, in milliseconds
+const DIAGNOSTICS_INTERVAL: u32 = 250;
+// how often the editor checks if the language server answered a code command in milliseconds
+const CODE_REQUEST_INTERVAL: u32 = 50;
+// how often the session and swap files are saved if they changed, and open files and the size of the window are checked for changes in milliseconds
+// the editor is only rendered again on a tick if there is something to save, a file changed or the window was resized
+const SESSION_INTERVAL: u32 = 1000;
+
+fn theme_name() -> String {
+    THEME_NAME.read().unwrap().clone()
+}
+
+fn theme() -> &'static Theme {
+    &TS.themes[THEME_NAME.read().unwrap().as_str()]
+}
+
+fn editor_colors() -> &'static EditorColors {
+    &THEME_COLORS[THEME_NAME.read().unwrap().as_str()]
+}
+
+/// Switches the theme, views use it the next time they render.
+fn set_theme(name: &str) {
+    if TS.themes.contains_key(name) {
+        *THEME_NAME.write().unwrap() = name.to_string();
+    }
 }
 
 const DEMO_TEXT: &str = r"// alt-move to spawn cursor
@@ -42,24 +143,434 @@ fn main() {
         #[cfg(feature = "term")]
         rink::launch(App);
 
+        // the window opens with room for the text the views assume it has, it can not be measured
         #[cfg(not(feature = "term"))]
-        dioxus::desktop::launch(App);
+        dioxus::desktop::launch_cfg(App, |config| {
+            config.with_window(|window| {
+                window.with_inner_size(dioxus::desktop::tao::dpi::LogicalSize::new(
+                    DEFAULT_COLUMNS as f64 * CHAR_WIDTH,
+                    DEFAULT_ROWS as f64 * LINE_HEIGHT,
+                ))
+            })
+        });
+    }
+}
+
+#[derive(Props)]
+struct TabProps<'a> {
+    id: usize,
+    workspace: &'a UseRef<Workspace>,
+}
+/// Owns the buffer of a tab and shows it in every pane it is opened in.
+fn Tab<'a>(cx: Scope<'a, TabProps<'a>>) -> Element {
+    let workspace = cx.props.workspace;
+    let id = cx.props.id;
+    let buffer = use_ref(&cx, || {
+        let workspace = workspace.read();
+        let info = workspace.tabs.get(id).unwrap();
+        let mut buffer = Buffer::new(&info.text, &info.extension);
+        buffer.encoding = info.encoding;
+        buffer.read_only = info.read_only;
+        // read-only buffers have no unsaved changes to restore
+        if let Some(unsaved) = &info.unsaved {
+            let _ = buffer.replace_text(unsaved);
+        }
+        buffer
+    });
+    let settings = use_ref(&cx, Settings::default);
+    let (tick, set_tick) = use_state(&cx, || 0usize);
+    let polling = cx.use_hook(|_| Rc::new(Cell::new(false)), |p| &*p);
+    // the version of the language server's diagnostics last shown
+    let published = cx.use_hook(|_| Cell::new(None::<usize>), |p| &*p);
+
+    // edits from the search panel, a language server or the file changing on disk, each is a single step in the history of the buffer
+    let edits: Vec<_> = workspace
+        .read()
+        .edits
+        .iter()
+        .filter(|(tab, _)| *tab == id)
+        .map(|(_, edit)| edit.clone())
+        .collect();
+    if !edits.is_empty() {
+        workspace.write().edits.retain(|(tab, _)| *tab != id);
+        for edit in edits {
+            if let Edit::Reload(text) = &edit {
+                buffer.write().reload(text);
+                continue;
+            }
+            let text = buffer.read().rope.to_string();
+            if let Some(new) = edit.apply(&text) {
+                if new != text {
+                    let replaced = buffer.write().replace_text(&new);
+                    if let Err(e) = replaced {
+                        workspace.write().message = Some(e);
+                    }
+                }
+            }
+        }
+    }
+
+    // diagnostics move with edits to the buffer until they are reported again
+    let anchored = cx.use_hook(|_| RefCell::new(buffer.read().rope.clone()), |a| &*a);
+    if *anchored.borrow() != buffer.read().rope {
+        let new = buffer.read().rope.clone();
+        let moved = workspace
+            .read()
+            .diagnostics
+            .get(&id)
+            .filter(|d| !d.0.is_empty())
+            .cloned();
+        if let Some(mut diagnostics) = moved {
+            diagnostics.update(&anchored.borrow(), &new);
+            workspace.write().diagnostics.insert(id, diagnostics);
+        }
+        *anchored.borrow_mut() = new;
+    }
+
+    // the edits since the buffer was saved are written to its swap file
+    let journaled = cx.use_hook(|_| RefCell::new(buffer.read().saved.clone()), |j| &*j);
+    if !buffer.read().is_dirty() {
+        if workspace.read().journals.contains_key(&id) {
+            workspace.write_silent().journals.remove(&id);
+        }
+        *journaled.borrow_mut() = buffer.read().rope.clone();
+    } else if *journaled.borrow() != buffer.read().rope {
+        let new = buffer.read().rope.clone();
+        workspace
+            .write_silent()
+            .journals
+            .entry(id)
+            .or_default()
+            .record(&journaled.borrow(), &new);
+        *journaled.borrow_mut() = new;
+    }
+
+    // completions look for words in every open buffer
+    if workspace.read().buffers.get(&id) != Some(&buffer.read().rope) {
+        let rope = buffer.read().rope.clone();
+        workspace.write_silent().words.update(id, &rope);
+        workspace.write_silent().buffers.insert(id, rope);
+        workspace.read().changed.set(true);
+    }
+
+    // keep the language server of the file up to date with the buffer
+    let synced = cx.use_hook(|_| RefCell::new(None::<(i32, Rope)>), |s| &*s);
+    let path = workspace.read().tabs.get(id).and_then(|t| t.path.clone());
+    let syntax = buffer.read().syntax.name.clone();
+    let config = workspace.read().language_servers.config(&syntax).cloned();
+    if let (Some(path), Some(config)) = (path, config) {
+        let root = workspace
+            .read()
+            .project
+            .as_ref()
+            .map(|p| p.root.clone())
+            .or_else(|| path.parent().map(Path::to_path_buf));
+        let servers = workspace.read().language_servers.clone();
+        // the server is started in the background the first time a file of its language is shown
+        if let Some(root) = root.filter(|_| servers.can_start(&syntax)) {
+            servers.start(&syntax, &root);
+        }
+        let client = servers.get(&syntax);
+        // render again once the server is running
+        if servers.is_starting(&syntax) && !polling.get() {
+            polling.set(true);
+            let polling = polling.clone();
+            let set_tick = set_tick.clone();
+            let tick = *tick;
+            let syntax = syntax.clone();
+            cx.push_future(async move {
+                while servers.is_starting(&syntax) {
+                    sleep(DIAGNOSTICS_INTERVAL).await;
+                }
+                polling.set(false);
+                set_tick(tick + 1);
+            });
+        }
+        if let (Some(client), Ok(uri)) = (client, Url::from_file_path(&path)) {
+            let rope = buffer.read().rope.clone();
+            let mut synced = synced.borrow_mut();
+            match &mut *synced {
+                None => {
+                    client.did_open(&uri, &config.language_id, 0, &rope);
+                    *synced = Some((0, rope));
+                }
+                Some((version, old)) if *old != rope => {
+                    *version += 1;
+                    client.did_change(&uri, *version, old, &rope);
+                    *old = rope;
+                }
+                Some(_) => {}
+            }
+
+            // show the diagnostics the server published since they were last shown
+            let version = client.diagnostics_version();
+            if published.get() != Some(version) {
+                published.set(Some(version));
+                let diagnostics =
+                    Diagnostics::from_lsp(&buffer.read().rope, &client.diagnostics(&uri));
+                if workspace.read().diagnostics.get(&id) != Some(&diagnostics) {
+                    workspace.write().diagnostics.insert(id, diagnostics);
+                }
+            }
+            // render again once the server publishes new diagnostics
+            if !polling.get() {
+                polling.set(true);
+                let polling = polling.clone();
+                let set_tick = set_tick.clone();
+                let tick = *tick;
+                cx.push_future(async move {
+                    while client.diagnostics_version() == version {
+                        sleep(DIAGNOSTICS_INTERVAL).await;
+                    }
+                    polling.set(false);
+                    set_tick(tick + 1);
+                });
+            }
+        }
     }
+
+    // keep the dirty indicator in the tab bar up to date
+    let dirty = buffer.read().is_dirty();
+    if workspace
+        .read()
+        .tabs
+        .get(id)
+        .map_or(false, |t| t.dirty != dirty)
+    {
+        workspace.write().tabs.set_dirty(id, dirty);
+    }
+
+    // panes the tab was opened in, views of tabs that are not shown are hidden so they keep their state
+    let views: Vec<_> = {
+        let workspace = workspace.read();
+        let rects = workspace.rects();
+        workspace
+            .panes
+            .iter()
+            .filter(|p| p.opened.contains(&id))
+            .filter_map(|p| {
+                let (_, rect) = rects.iter().find(|(pane, _)| *pane == p.id)?;
+                Some((p.id, *rect, p.tab == Some(id)))
+            })
+            .collect()
+    };
+
+    cx.render(rsx! {
+        div{
+            views.into_iter().map(|(pane, rect, shown)| {
+                let display = if shown { "flex" } else { "none" };
+                cx.render(rsx! {
+                    div{
+                        key: "{pane}",
+                        position: "absolute",
+                        left: "{rect.x}%",
+                        top: "{rect.y}%",
+                        width: "{rect.width}%",
+                        height: "{rect.height}%",
+                        display: "{display}",
+                        flex_direction: "column",
+                        View{
+                            pane: pane,
+                            tab: id,
+                            buffer: buffer,
+                            settings: settings,
+                            workspace: workspace,
+                        }
+                    }
+                })
+            })
+        }
+    })
 }
 
-#[derive(Props, PartialEq)]
-struct TabProps {
-    initial_text: String,
+#[derive(Props)]
+struct ViewProps<'a> {
+    pane: usize,
+    tab: usize,
+    buffer: &'a UseRef<Buffer>,
+    settings: &'a UseRef<Settings>,
+    workspace: &'a UseRef<Workspace>,
 }
-fn Tab(cx: Scope<TabProps>) -> Element {
+/// A buffer shown in a pane, with its own cursors and folds.
+fn View<'a>(cx: Scope<'a, ViewProps<'a>>) -> Element {
+    let buffer = cx.props.buffer;
+    let settings = cx.props.settings;
+    // a view restored from the last session starts where it was left, if the text still has its rows
+    let restored = || {
+        let len_lines = buffer.read().rope.len_lines();
+        cx.props
+            .workspace
+            .read()
+            .views
+            .get(&(cx.props.pane, cx.props.tab))
+            .cloned()
+            .filter(|v| {
+                v.cursors.list.iter().all(|c| c.last().row() < len_lines)
+                    && v.folds.0.iter().all(|f| f.end < len_lines)
+            })
+            .unwrap_or_default()
+    };
+    let cursors = use_ref(&cx, || {
+        let cursors = restored().cursors;
+        if cursors.list.is_empty() {
+            Cursors::default()
+        } else {
+            cursors
+        }
+    });
+    let folds = use_ref(&cx, || restored().folds);
+
     cx.render(rsx! {
-        CodeArea{
-            initial_text: cx.props.initial_text.clone()
+        div{
+            width: "100%",
+            height: "100%",
+            display: "flex",
+            flex_direction: "column",
+            CodeArea{
+                buffer: buffer,
+                cursors: cursors,
+                settings: settings,
+                folds: folds,
+                workspace: cx.props.workspace,
+                pane: cx.props.pane,
+                tab: cx.props.tab,
+            }
+            StatusBar{
+                workspace: cx.props.workspace,
+                buffer: buffer,
+                cursors: cursors,
+                settings: settings,
+                recording: cx.props.workspace.read().recording.is_some(),
+            }
         }
     })
 }
 
 fn App(cx: Scope) -> Element {
+    let workspace = use_ref(&cx, || {
+        let mut workspace = Workspace::default();
+        // the editor can be opened on a directory to search its files
+        #[cfg(not(target_arch = "wasm32"))]
+        if let Some(root) = std::env::args().nth(1).map(PathBuf::from) {
+            if root.is_dir() {
+                workspace.explorer = Some(Explorer::new(root.clone()));
+                workspace.project = Some(FileIndex::new(root));
+            }
+        }
+        // open what was open when the editor was last closed
+        match load_session() {
+            Some(session) => workspace.restore(session),
+            None => {
+                workspace.open("demo.rs", DEMO_TEXT, "rs");
+            }
+        }
+        // changes that were not restored with the session are offered to be recovered
+        workspace.recovered = unrecovered(load_swaps(), &workspace);
+        workspace
+    });
+    let run = cx.use_hook(|_| next_run(), |r| *r);
+    let (tick, set_tick) = use_state(&cx, || 0usize);
+    let changed = workspace.read().changed.clone();
+    // the files of the open tabs with when they were modified the last time they were checked
+    let watched = cx.use_hook(
+        |_| Rc::new(RefCell::new(Vec::<(PathBuf, Option<u64>)>::new())),
+        |w| w.clone(),
+    );
+    cx.use_hook(
+        |_| {
+            let set_tick = set_tick.clone();
+            let changed = changed.clone();
+            let watched = watched.clone();
+            cx.push_future(async move {
+                let mut tick = 0;
+                let mut size = (viewport_columns(), viewport_rows());
+                loop {
+                    sleep(SESSION_INTERVAL).await;
+                    let files_changed = watched
+                        .borrow()
+                        .iter()
+                        .any(|(path, time)| modified(path) != *time);
+                    // views wrap and scroll to the size of the window
+                    let resized = (viewport_columns(), viewport_rows());
+                    let resized = std::mem::replace(&mut size, resized) != size;
+                    if changed.replace(false) || files_changed || resized {
+                        tick += 1;
+                        set_tick(tick);
+                    }
+                }
+            });
+        },
+        |_| {},
+    );
+    // the session and swap files are saved on the tick after they changed, so they survive however the editor is closed
+    let saved = cx.use_hook(|_| RefCell::new((0, None::<Session>)), |s| &*s);
+    let written = cx.use_hook(|_| RefCell::new(BTreeMap::new()), |w| &*w);
+    if saved.borrow().0 == *tick {
+        // rendering again without a tick means the workspace changed
+        changed.set(true);
+    } else {
+        let session = workspace.read().session();
+        let mut saved = saved.borrow_mut();
+        saved.0 = *tick;
+        if saved.1.as_ref() != Some(&session) {
+            if let Err(e) = save_session(&session) {
+                workspace.write().message = Some(format!("Could not save the session: {}", e));
+            }
+            saved.1 = Some(session);
+        }
+        // open files are watched by checking when they were modified
+        #[cfg(not(target_arch = "wasm32"))]
+        {
+            let changes = watch::changed_files(&workspace.read());
+            if !changes.is_empty() {
+                watch::apply_changes(&mut workspace.write(), changes);
+            }
+        }
+        let swapped = write_swaps(&workspace.read(), run, &mut written.borrow_mut());
+        if let Err(e) = swapped {
+            workspace.write().message = Some(format!("Could not write swap files: {}", e));
+        }
+        let workspace = workspace.read();
+        *watched.borrow_mut() = workspace
+            .tabs
+            .tabs
+            .iter()
+            .filter_map(|t| {
+                let time = workspace.disk_times.get(&t.id).copied().flatten();
+                Some((t.path.clone()?, time))
+            })
+            .collect();
+    }
+    // the answer of a language server to a code command is shown once it is received
+    let (answered, set_answered) = use_state(&cx, || 0usize);
+    let asking = cx.use_hook(|_| Rc::new(Cell::new(false)), |a| &*a);
+    let request = workspace.read().code_request.clone();
+    if let Some(request) = request {
+        if request.is_received() {
+            receive_code_request(&mut workspace.write());
+        } else if !asking.get() {
+            asking.set(true);
+            let asking = asking.clone();
+            let set_answered = set_answered.clone();
+            let answered = *answered;
+            cx.push_future(async move {
+                while !request.is_received() {
+                    sleep(CODE_REQUEST_INTERVAL).await;
+                }
+                asking.set(false);
+                set_answered(answered + 1);
+            });
+        }
+    }
+    let tabs: Vec<usize> = workspace.read().tabs.tabs.iter().map(|t| t.id).collect();
+    let search_panel = workspace.read().search_panel;
+    let problems_panel = workspace.read().problems_panel;
+    let explorer = workspace.read().explorer.as_ref().map_or(false, |e| e.open);
+    let recovered = !workspace.read().recovered.is_empty();
+    let conflicts = !workspace.read().conflicts.is_empty();
+    // terminals draw carets without an animation
+    let keyframes = !cfg!(feature = "term");
+
     cx.render(rsx! {
         div{
             width: "100%",
@@ -67,8 +578,56 @@ fn App(cx: Scope) -> Element {
             position: "absolute",
             display: "flex",
             flex_direction: "row",
-            Tab{
-                initial_text: DEMO_TEXT.to_string()
+            keyframes.then(|| rsx! {
+                style{ "{CARET_KEYFRAMES}" }
+            })
+            explorer.then(|| rsx! {
+                ExplorerPanel{
+                    workspace: workspace,
+                }
+            })
+            div{
+                flex_grow: "1",
+                min_width: "0px",
+                height: "100%",
+                display: "flex",
+                flex_direction: "column",
+                recovered.then(|| rsx! {
+                    RecoveryPanel{
+                        workspace: workspace,
+                    }
+                })
+                conflicts.then(|| rsx! {
+                    ConflictPanel{
+                        workspace: workspace,
+                    }
+                })
+                TabBar{
+                    workspace: workspace,
+                }
+                div{
+                    width: "100%",
+                    flex_grow: "1",
+                    min_height: "0px",
+                    position: "relative",
+                    tabs.into_iter().map(|id| rsx! {
+                        Tab{
+                            key: "{id}",
+                            id: id,
+                            workspace: workspace,
+                        }
+                    })
+                }
+                search_panel.then(|| rsx! {
+                    SearchPanel{
+                        workspace: workspace,
+                    }
+                })
+                problems_panel.then(|| rsx! {
+                    ProblemsPanel{
+                        workspace: workspace,
+                    }
+                })
             }
         }
     })
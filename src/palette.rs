@@ -0,0 +1,93 @@
+use crate::commands::{palette_items, PaletteMode};
+use crate::fuzzy::split_matched;
+use crate::utils::color_to_string;
+use crate::workspace::Workspace;
+use crate::{editor_colors, theme};
+use dioxus::prelude::*;
+
+// the most entries shown at once
+const MAX_SHOWN: usize = 50;
+
+#[derive(Props)]
+pub struct CommandPaletteProps<'a> {
+    workspace: &'a UseRef<Workspace>,
+}
+pub fn CommandPalette<'a>(cx: Scope<'a, CommandPaletteProps<'a>>) -> Element {
+    let workspace = cx.props.workspace.read();
+    let palette = match workspace.palette.clone() {
+        Some(palette) => palette,
+        None => return None,
+    };
+    let items = palette_items(&palette, &workspace);
+    let header = match (palette.mode, &workspace.project) {
+        (PaletteMode::Commands, _) => format!("> {}", palette.query),
+        (PaletteMode::Files, None) => "No folder opened".to_string(),
+        (PaletteMode::Files, Some(project)) if project.is_indexing() => {
+            format!(
+                "{} (indexing {} files)",
+                palette.query,
+                project.file_count()
+            )
+        }
+        (PaletteMode::Files, Some(_)) => palette.query.clone(),
+        (PaletteMode::Locations, _) => palette.query.clone(),
+        (PaletteMode::Rename, _) => format!("New name: {}", palette.query),
+        (PaletteMode::Repeat, _) => format!("Times: {}", palette.query),
+        (PaletteMode::MacroName, _) => format!("Macro name: {}", palette.query),
+        (PaletteMode::Macros, _) => palette.query.clone(),
+    };
+    drop(workspace);
+
+    let colors = editor_colors();
+    let fg = color_to_string(colors.foreground);
+    let bg = color_to_string(theme().settings.gutter.unwrap_or(colors.background));
+    let selected_bg = color_to_string(colors.selection);
+    let matched_fg = color_to_string(theme().settings.accent.unwrap_or(colors.foreground));
+    // keep the selected entry in view
+    let skip = (palette.selected + 1).saturating_sub(MAX_SHOWN);
+
+    cx.render(rsx! {
+        div{
+            position: "absolute",
+            top: "0px",
+            left: "20%",
+            width: "60%",
+            max_height: "50%",
+            overflow: "hidden",
+            display: "flex",
+            flex_direction: "column",
+            z_index: "1",
+            color: "{fg}",
+            background_color: "{bg}",
+            div{
+                padding: "2px 6px",
+                "{header}"
+            }
+            items.into_iter().enumerate().skip(skip).take(MAX_SHOWN).map(|(i, item)| {
+                let row_bg = if i == palette.selected { selected_bg.clone() } else { bg.clone() };
+                let detail = item.detail;
+                let pieces = split_matched(&item.text, &item.indexes);
+                let matched_fg = matched_fg.clone();
+                cx.render(rsx! {
+                    div{
+                        display: "flex",
+                        flex_direction: "row",
+                        justify_content: "space-between",
+                        padding: "0px 6px",
+                        background_color: "{row_bg}",
+                        span{
+                            pieces.into_iter().map(|(matched, text)| {
+                                if matched {
+                                    rsx!(span{ color: "{matched_fg}", font_weight: "bold", "{text}" })
+                                } else {
+                                    rsx!(span{ "{text}" })
+                                }
+                            })
+                        }
+                        span{ padding_left: "12px", "{detail}" }
+                    }
+                })
+            })
+        }
+    })
+}
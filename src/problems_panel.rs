@@ -0,0 +1,101 @@
+use crate::cursor::Cursor;
+use crate::diagnostics::Severity;
+use crate::utils::color_to_string;
+use crate::workspace::Workspace;
+use crate::{editor_colors, theme};
+use dioxus::prelude::*;
+
+/// Shows a tab in the focused pane with the cursor on a problem.
+fn open_problem(workspace: &mut Workspace, tab: usize, cursor: Cursor) {
+    if let Some(index) = workspace.tabs.index_of(tab) {
+        workspace.activate_tab(index);
+        workspace.goto = Some((tab, cursor));
+    }
+}
+
+#[derive(Props)]
+pub struct ProblemsPanelProps<'a> {
+    workspace: &'a UseRef<Workspace>,
+}
+pub fn ProblemsPanel<'a>(cx: Scope<'a, ProblemsPanelProps<'a>>) -> Element {
+    let workspace = cx.props.workspace;
+    // the tabs with diagnostics and their names
+    let files: Vec<_> = {
+        let workspace = workspace.read();
+        workspace
+            .diagnostics
+            .iter()
+            .filter(|(_, d)| !d.0.is_empty())
+            .filter_map(|(tab, d)| Some((*tab, workspace.tabs.get(*tab)?.name.clone(), d.clone())))
+            .collect()
+    };
+    let count = |severity| -> usize { files.iter().map(|(_, _, d)| d.count(severity)).sum() };
+    let status = format!(
+        "{} errors, {} warnings",
+        count(Severity::Error),
+        count(Severity::Warning)
+    );
+
+    let colors = editor_colors();
+    let fg = color_to_string(colors.foreground);
+    let bg = color_to_string(theme().settings.gutter.unwrap_or(colors.background));
+
+    cx.render(rsx! {
+        div{
+            width: "100%",
+            max_height: "30%",
+            display: "flex",
+            flex_direction: "column",
+            color: "{fg}",
+            background_color: "{bg}",
+            div{
+                display: "flex",
+                flex_direction: "row",
+                justify_content: "space-between",
+                padding: "2px 6px",
+                span{ font_weight: "bold", "Problems" }
+                span{ "{status}" }
+                span{
+                    cursor: "pointer",
+                    onclick: move |_| workspace.write().problems_panel = false,
+                    "×"
+                }
+            }
+            div{
+                overflow: "auto",
+                files.into_iter().map(|(tab, name, diagnostics)| {
+                    let header = format!("{} ({})", name, diagnostics.0.len());
+                    rsx! {
+                        div{
+                            key: "{tab}",
+                            div{ padding: "0px 6px", font_weight: "bold", "{header}" }
+                            diagnostics.0.into_iter().enumerate().map(move |(i, diagnostic)| {
+                                let icon = diagnostic.severity.icon();
+                                let icon_color = color_to_string(diagnostic.severity.color());
+                                let line = diagnostic.start.row() + 1;
+                                let summary = diagnostic.summary();
+                                let cursor = if diagnostic.start == diagnostic.end {
+                                    Cursor::from_start(diagnostic.start)
+                                } else {
+                                    Cursor::new(diagnostic.start, diagnostic.end)
+                                };
+                                rsx! {
+                                    div{
+                                        key: "{i}",
+                                        padding: "0px 6px",
+                                        white_space: "pre",
+                                        cursor: "pointer",
+                                        onclick: move |_| open_problem(&mut workspace.write(), tab, cursor.clone()),
+                                        span{ color: "{icon_color}", padding_right: "6px", "{icon}" }
+                                        span{ padding_right: "12px", "{line}" }
+                                        "{summary}"
+                                    }
+                                }
+                            })
+                        }
+                    }
+                })
+            }
+        }
+    })
+}
@@ -0,0 +1,358 @@
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+
+use ropey::Rope;
+use serde::{Deserialize, Serialize};
+
+use crate::encoding::decode_file;
+use crate::storage;
+use crate::utils::{changed_chars, modified};
+use crate::workspace::{Edit, Workspace};
+
+// the directory swap files are written to, each is named by the run and tab it was written by
+const RECOVERY_DIR: &str = "recovery/";
+
+/// An edit to a buffer, the chars from the start are removed and the text is inserted in their place.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct JournalEdit {
+    pub start: usize,
+    pub removed: usize,
+    pub inserted: String,
+}
+
+/// The edits made to a buffer since it was saved, in order.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Journal(pub Vec<JournalEdit>);
+
+impl Journal {
+    /// Adds the change between two texts, typing right after the last edit extends it.
+    pub fn record(&mut self, old: &Rope, new: &Rope) {
+        let (start, old_end, new_end) = changed_chars(old, new);
+        let inserted = new.slice(start..new_end).to_string();
+        let removed = old_end - start;
+        if let Some(last) = self.0.last_mut() {
+            if removed == 0 && start == last.start + last.inserted.chars().count() {
+                last.inserted.push_str(&inserted);
+                return;
+            }
+        }
+        self.0.push(JournalEdit {
+            start,
+            removed,
+            inserted,
+        });
+    }
+
+    /// Applies the edits to the text they were made to, or None if they do not fit it.
+    pub fn replay(&self, text: &str) -> Option<String> {
+        let mut rope = Rope::from_str(text);
+        for edit in &self.0 {
+            let end = edit.start + edit.removed;
+            if end > rope.len_chars() {
+                return None;
+            }
+            rope.remove(edit.start..end);
+            rope.insert(edit.start, &edit.inserted);
+        }
+        Some(rope.to_string())
+    }
+}
+
+/// The unsaved changes of a buffer, written while the editor runs so they survive a crash.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Swap {
+    pub name: String,
+    pub extension: String,
+    pub path: Option<PathBuf>,
+    // when the file was modified as the swap was written
+    pub modified: Option<u64>,
+    pub journal: Journal,
+    pub text: String,
+    // the process of the editor that wrote the swap, 0 if it is not known
+    #[serde(default)]
+    pub pid: u32,
+    // the name the swap is stored under
+    #[serde(skip)]
+    pub file: String,
+}
+
+// the id of this process, web builds have none
+fn process_id() -> u32 {
+    #[cfg(target_arch = "wasm32")]
+    {
+        0
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    {
+        std::process::id()
+    }
+}
+
+// if a process is still running
+fn is_running(pid: u32) -> bool {
+    if pid == 0 {
+        return false;
+    }
+    #[cfg(target_os = "linux")]
+    {
+        std::path::Path::new(&format!("/proc/{}", pid)).exists()
+    }
+
+    #[cfg(all(unix, not(target_os = "linux")))]
+    {
+        std::process::Command::new("kill")
+            .args(["-0", &pid.to_string()])
+            .stderr(std::process::Stdio::null())
+            .status()
+            .map_or(false, |s| s.success())
+    }
+
+    #[cfg(not(unix))]
+    {
+        false
+    }
+}
+
+impl Swap {
+    /// If the editor that wrote the swap is still running, its changes are not recovered by other editors.
+    pub fn is_owned(&self) -> bool {
+        self.pid != process_id() && is_running(self.pid)
+    }
+
+    /// If the file was changed on disk after the swap was written.
+    pub fn changed_on_disk(&self) -> bool {
+        self.path
+            .as_deref()
+            .map_or(false, |path| modified(path) != self.modified)
+    }
+
+    /// The text to recover, the journal replayed on the saved file if it did not change since, otherwise the text of the swap.
+    pub fn recovered_text(&self, saved: Option<&str>) -> String {
+        match saved {
+            Some(saved) if !self.changed_on_disk() => self
+                .journal
+                .replay(saved)
+                .unwrap_or_else(|| self.text.clone()),
+            _ => self.text.clone(),
+        }
+    }
+}
+
+// the run and tab a swap file was written by
+fn run_and_tab(file: &str) -> Option<(usize, usize)> {
+    let name = file.strip_prefix(RECOVERY_DIR)?.strip_suffix(".json")?;
+    let (run, tab) = name.split_once('-')?;
+    Some((run.parse().ok()?, tab.parse().ok()?))
+}
+
+fn swap_file(run: usize, tab: usize) -> String {
+    format!("{}{}-{}.json", RECOVERY_DIR, run, tab)
+}
+
+/// The swaps left behind by earlier runs, oldest first.
+pub fn load_swaps() -> Vec<Swap> {
+    let mut swaps: Vec<Swap> = storage::list(RECOVERY_DIR)
+        .into_iter()
+        .filter_map(|file| {
+            let mut swap: Swap = serde_json::from_str(&storage::load(&file)?).ok()?;
+            swap.file = file;
+            Some(swap)
+        })
+        .collect();
+    swaps.sort_by_key(|s| run_and_tab(&s.file));
+    swaps
+}
+
+// the first run after the runs that wrote the swap files
+fn run_after(files: &[String]) -> usize {
+    files
+        .iter()
+        .filter_map(|file| run_and_tab(file))
+        .map(|(run, _)| run + 1)
+        .max()
+        .unwrap_or(0)
+}
+
+/// The number of this run, after the runs of every swap file, including those of editors still running, so they are not overwritten.
+pub fn next_run() -> usize {
+    run_after(&storage::list(RECOVERY_DIR))
+}
+
+/// Drops the swaps whose changes were already restored with the session and the swaps of other editors that are still running,
+/// the rest can be recovered.
+pub fn unrecovered(swaps: Vec<Swap>, workspace: &Workspace) -> Vec<Swap> {
+    swaps
+        .into_iter()
+        .filter(|swap| !swap.is_owned())
+        .filter(|swap| {
+            let restored = workspace.tabs.tabs.iter().any(|t| {
+                t.path == swap.path && *t.unsaved.as_ref().unwrap_or(&t.text) == swap.text
+            });
+            if restored {
+                storage::remove(&swap.file);
+            }
+            !restored
+        })
+        .collect()
+}
+
+/// Writes the swaps of buffers with unsaved changes that changed since they were last written,
+/// and removes the swaps of buffers that were saved or closed.
+/// Written keeps the text each swap of this run was last written with.
+pub fn write_swaps(
+    workspace: &Workspace,
+    run: usize,
+    written: &mut BTreeMap<usize, Rope>,
+) -> Result<(), String> {
+    let dirty: Vec<_> = workspace.tabs.tabs.iter().filter(|t| t.dirty).collect();
+    written.retain(|tab, _| {
+        let keep = dirty.iter().any(|t| t.id == *tab);
+        if !keep {
+            storage::remove(&swap_file(run, *tab));
+        }
+        keep
+    });
+    for tab in dirty {
+        let (rope, journal) = match (
+            workspace.buffers.get(&tab.id),
+            workspace.journals.get(&tab.id),
+        ) {
+            (Some(rope), Some(journal)) => (rope, journal),
+            _ => continue,
+        };
+        if written.get(&tab.id) == Some(rope) {
+            continue;
+        }
+        let swap = Swap {
+            name: tab.name.clone(),
+            extension: tab.extension.clone(),
+            path: tab.path.clone(),
+            modified: tab.path.as_deref().and_then(modified),
+            journal: journal.clone(),
+            text: rope.to_string(),
+            pid: process_id(),
+            file: String::new(),
+        };
+        let json = serde_json::to_string(&swap).map_err(|e| e.to_string())?;
+        storage::save(&swap_file(run, tab.id), &json)?;
+        written.insert(tab.id, rope.clone());
+    }
+    Ok(())
+}
+
+/// Opens a recovered buffer with its changes unsaved, a tab of the file that is already open gets the changes as an edit.
+pub fn recover(workspace: &mut Workspace, index: usize) {
+    if index >= workspace.recovered.len() {
+        return;
+    }
+    let swap = workspace.recovered.remove(index);
+    storage::remove(&swap.file);
+    let decoded = swap
+        .path
+        .as_deref()
+        .and_then(|path| std::fs::read(path).ok())
+        .map(|bytes| decode_file(&bytes));
+    let text = swap.recovered_text(decoded.as_ref().map(|d| d.text.as_str()));
+    let open = swap.path.as_deref().and_then(|path| {
+        workspace
+            .tabs
+            .tabs
+            .iter()
+            .position(|t| t.path.as_deref() == Some(path))
+    });
+    if let Some(index) = open {
+        workspace.activate_tab(index);
+        let id = workspace.tabs.tabs[index].id;
+        workspace.edits.push((id, Edit::Text(text)));
+        return;
+    }
+    let id = match (&swap.path, &decoded) {
+        (Some(path), Some(decoded)) => workspace.open_decoded(path, decoded),
+        (Some(path), None) => workspace.open_file(path, ""),
+        (None, _) => workspace.open(&swap.name, "", &swap.extension),
+    };
+    if let Some(tab) = workspace.tabs.tabs.iter_mut().find(|t| t.id == id) {
+        tab.dirty = true;
+        tab.unsaved = Some(text);
+    }
+}
+
+/// Throws the changes of a recovered buffer away.
+pub fn discard(workspace: &mut Workspace, index: usize) {
+    if index < workspace.recovered.len() {
+        storage::remove(&workspace.recovered.remove(index).file);
+    }
+}
+
+#[test]
+fn journal_replays_edits() {
+    let saved = Rope::from_str("fn main() {}\n");
+    let mut journal = Journal::default();
+    let mut rope = saved.clone();
+    let mut edit = |journal: &mut Journal, f: &dyn Fn(&mut Rope)| {
+        let old = rope.clone();
+        f(&mut rope);
+        journal.record(&old, &rope);
+    };
+    edit(&mut journal, &|r| r.insert(11, "\n"));
+    edit(&mut journal, &|r| r.insert(12, "    "));
+    edit(&mut journal, &|r| r.insert(16, "x"));
+    edit(&mut journal, &|r| r.remove(3..7));
+    // typing continues the last insertion
+    assert_eq!(journal.0.len(), 2);
+    assert_eq!(
+        journal.0[1],
+        JournalEdit {
+            start: 3,
+            removed: 4,
+            inserted: String::new(),
+        }
+    );
+    assert_eq!(journal.replay(&saved.to_string()), Some(rope.to_string()));
+    assert_eq!(journal.replay("fn"), None);
+}
+
+#[test]
+fn recover_swaps() {
+    let swap = Swap {
+        name: "untitled".to_string(),
+        extension: "txt".to_string(),
+        path: None,
+        modified: None,
+        journal: Journal::default(),
+        text: "lost".to_string(),
+        pid: 0,
+        file: swap_file(3, 1),
+    };
+    let mut workspace = Workspace::default();
+    workspace.open("untitled", "lost", "txt");
+    // the session already restored the changes of the first swap
+    let other = Swap {
+        text: "found".to_string(),
+        file: swap_file(4, 0),
+        ..swap.clone()
+    };
+    // an editor that is still running keeps its swap
+    let running = std::process::Command::new("sleep").arg("10").spawn();
+    let owned = Swap {
+        text: "owned".to_string(),
+        pid: running.as_ref().map_or(0, |c| c.id()),
+        file: swap_file(5, 0),
+        ..swap.clone()
+    };
+    workspace.recovered = unrecovered(vec![swap, other, owned], &workspace);
+    assert_eq!(workspace.recovered.len(), 1);
+    assert_eq!(run_after(&[swap_file(4, 0), swap_file(5, 2)]), 6);
+    if let Ok(mut running) = running {
+        running.kill().unwrap();
+        running.wait().unwrap();
+    }
+    assert!(!workspace.recovered[0].changed_on_disk());
+
+    recover(&mut workspace, 0);
+    assert!(workspace.recovered.is_empty());
+    let tab = &workspace.tabs.tabs[1];
+    assert_eq!((tab.text.as_str(), tab.dirty), ("", true));
+    assert_eq!(tab.unsaved.as_deref(), Some("found"));
+}
@@ -0,0 +1,101 @@
+use crate::recovery::{discard, recover};
+use crate::utils::color_to_string;
+use crate::workspace::Workspace;
+use crate::{editor_colors, theme};
+use dioxus::prelude::*;
+
+#[derive(Props)]
+pub struct RecoveryPanelProps<'a> {
+    workspace: &'a UseRef<Workspace>,
+}
+/// Lists the buffers with unsaved changes left behind by the last run, each can be restored or discarded.
+pub fn RecoveryPanel<'a>(cx: Scope<'a, RecoveryPanelProps<'a>>) -> Element {
+    let workspace = cx.props.workspace;
+    let swaps: Vec<_> = workspace
+        .read()
+        .recovered
+        .iter()
+        .map(|swap| {
+            let name = swap
+                .path
+                .as_ref()
+                .map_or(swap.name.clone(), |p| p.display().to_string());
+            let mut details = format!("{} edits", swap.journal.0.len());
+            if swap.changed_on_disk() {
+                details.push_str(", the file changed on disk after the changes were saved");
+            }
+            (name, details)
+        })
+        .collect();
+    let count = swaps.len();
+
+    let colors = editor_colors();
+    let fg = color_to_string(colors.foreground);
+    let bg = color_to_string(theme().settings.gutter.unwrap_or(colors.background));
+
+    cx.render(rsx! {
+        div{
+            width: "100%",
+            max_height: "30%",
+            display: "flex",
+            flex_direction: "column",
+            color: "{fg}",
+            background_color: "{bg}",
+            div{
+                display: "flex",
+                flex_direction: "row",
+                justify_content: "space-between",
+                padding: "2px 6px",
+                span{ font_weight: "bold", "Recovered {count} buffers with unsaved changes" }
+                span{
+                    span{
+                        cursor: "pointer",
+                        padding_right: "12px",
+                        onclick: move |_| {
+                            let mut workspace = workspace.write();
+                            while !workspace.recovered.is_empty() {
+                                recover(&mut workspace, 0);
+                            }
+                        },
+                        "Restore all"
+                    }
+                    span{
+                        cursor: "pointer",
+                        onclick: move |_| {
+                            let mut workspace = workspace.write();
+                            while !workspace.recovered.is_empty() {
+                                discard(&mut workspace, 0);
+                            }
+                        },
+                        "Discard all"
+                    }
+                }
+            }
+            div{
+                overflow: "auto",
+                swaps.into_iter().enumerate().map(|(i, (name, details))| rsx! {
+                    div{
+                        key: "{i}-{name}",
+                        padding: "0px 6px",
+                        white_space: "pre",
+                        display: "flex",
+                        flex_direction: "row",
+                        span{ font_weight: "bold", padding_right: "12px", "{name}" }
+                        span{ flex_grow: "1", "{details}" }
+                        span{
+                            cursor: "pointer",
+                            padding_right: "12px",
+                            onclick: move |_| recover(&mut workspace.write(), i),
+                            "Restore"
+                        }
+                        span{
+                            cursor: "pointer",
+                            onclick: move |_| discard(&mut workspace.write(), i),
+                            "Discard"
+                        }
+                    }
+                })
+            }
+        }
+    })
+}
@@ -0,0 +1,315 @@
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, RwLock};
+
+use regex::{Regex, RegexBuilder};
+
+/// What to search for across the files of a project.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SearchQuery {
+    pub pattern: String,
+    pub regex: bool,
+    pub case_sensitive: bool,
+    // comma separated globs of the files to search, every file if empty
+    pub include: String,
+    // comma separated globs of the files to skip
+    pub exclude: String,
+}
+
+impl SearchQuery {
+    pub fn to_regex(&self) -> Result<Regex, String> {
+        let pattern = if self.regex {
+            self.pattern.clone()
+        } else {
+            regex::escape(&self.pattern)
+        };
+        RegexBuilder::new(&pattern)
+            .case_insensitive(!self.case_sensitive)
+            .build()
+            .map_err(|e| e.to_string())
+    }
+
+    /// The globs to search, excluded globs start with a `!`.
+    fn globs(&self) -> Vec<String> {
+        let split = |globs: &str| {
+            globs
+                .split(',')
+                .map(|g| g.trim().to_string())
+                .filter(|g| !g.is_empty())
+                .collect::<Vec<_>>()
+        };
+        let mut globs = split(&self.include);
+        globs.extend(split(&self.exclude).into_iter().map(|g| format!("!{}", g)));
+        globs
+    }
+}
+
+/// A line of a search result, either a match or context around it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ResultLine {
+    pub row: usize,
+    pub text: String,
+    // char ranges of the matches in the line, empty for context lines
+    pub matches: Vec<(usize, usize)>,
+}
+
+/// The matches in a file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileResult {
+    // relative to the root of the project
+    pub path: String,
+    pub lines: Vec<ResultLine>,
+}
+
+impl FileResult {
+    pub fn match_count(&self) -> usize {
+        self.lines.iter().map(|l| l.matches.len()).sum()
+    }
+}
+
+/// Finds the matches in the text of a file with the lines around them.
+pub fn search_text(text: &str, regex: &Regex, context: usize) -> Vec<ResultLine> {
+    let lines: Vec<&str> = text.split('\n').map(|l| l.trim_end_matches('\r')).collect();
+    let matches: Vec<Vec<(usize, usize)>> = lines
+        .iter()
+        .map(|line| {
+            regex
+                .find_iter(line)
+                .filter(|m| m.start() != m.end())
+                .map(|m| {
+                    (
+                        line[..m.start()].chars().count(),
+                        line[..m.end()].chars().count(),
+                    )
+                })
+                .collect()
+        })
+        .collect();
+    let mut result = Vec::new();
+    // lines before this row are already in the result
+    let mut shown = 0;
+    for (row, line_matches) in matches.iter().enumerate() {
+        if line_matches.is_empty() {
+            continue;
+        }
+        let first = row.saturating_sub(context).max(shown);
+        let last = (row + context).min(lines.len() - 1);
+        for (row, (line, matches)) in lines
+            .iter()
+            .zip(&matches)
+            .enumerate()
+            .take(last + 1)
+            .skip(first)
+        {
+            result.push(ResultLine {
+                row,
+                text: line.to_string(),
+                matches: matches.clone(),
+            });
+        }
+        shown = shown.max(last + 1);
+    }
+    result
+}
+
+/// Text that replaces every match of a search.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Replacement {
+    pub query: SearchQuery,
+    pub text: String,
+}
+
+impl Replacement {
+    /// Replaces every match in the text, regex searches expand `$1` style groups.
+    /// Lines are matched one at a time without their line break, like search_text finds them.
+    pub fn apply(&self, text: &str) -> Result<String, String> {
+        let regex = self.query.to_regex()?;
+        let replacement = if self.query.regex {
+            self.text.clone()
+        } else {
+            self.text.replace('$', "$$")
+        };
+        let mut replaced = String::with_capacity(text.len());
+        for (row, line) in text.split('\n').enumerate() {
+            if row > 0 {
+                replaced.push('\n');
+            }
+            let content = line.trim_end_matches('\r');
+            // empty matches are not shown as results, so they are not replaced either
+            let mut end = 0;
+            for captures in regex.captures_iter(content) {
+                let m = captures.get(0).unwrap();
+                if m.start() == m.end() {
+                    continue;
+                }
+                replaced.push_str(&content[end..m.start()]);
+                captures.expand(&replacement, &mut replaced);
+                end = m.end();
+            }
+            replaced.push_str(&line[end..]);
+        }
+        Ok(replaced)
+    }
+}
+
+/// A search running in the background, results are added as files are searched.
+#[derive(Debug, Clone)]
+pub struct Search {
+    pub query: SearchQuery,
+    results: Arc<RwLock<Vec<FileResult>>>,
+    running: Arc<AtomicBool>,
+    cancelled: Arc<AtomicBool>,
+}
+
+impl Search {
+    /// Starts searching the files under the root, skipping files ignored by git.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn start(root: PathBuf, query: SearchQuery, context: usize) -> Result<Self, String> {
+        let regex = query.to_regex()?;
+        let mut overrides = ignore::overrides::OverrideBuilder::new(&root);
+        for glob in query.globs() {
+            overrides.add(&glob).map_err(|e| e.to_string())?;
+        }
+        let overrides = overrides.build().map_err(|e| e.to_string())?;
+        let search = Self {
+            query,
+            results: Arc::new(RwLock::new(Vec::new())),
+            running: Arc::new(AtomicBool::new(true)),
+            cancelled: Arc::new(AtomicBool::new(false)),
+        };
+        let background = search.clone();
+        std::thread::spawn(move || {
+            let walk = ignore::WalkBuilder::new(&root).overrides(overrides).build();
+            for entry in walk.flatten() {
+                if background.cancelled.load(Ordering::SeqCst) {
+                    break;
+                }
+                if !entry.file_type().map_or(false, |t| t.is_file()) {
+                    continue;
+                }
+                // files that are not text are skipped
+                let text = match std::fs::read_to_string(entry.path()) {
+                    Ok(text) => text,
+                    Err(_) => continue,
+                };
+                let lines = search_text(&text, &regex, context);
+                if let (false, Ok(path)) = (lines.is_empty(), entry.path().strip_prefix(&root)) {
+                    background.results.write().unwrap().push(FileResult {
+                        path: path.to_string_lossy().replace('\\', "/"),
+                        lines,
+                    });
+                }
+            }
+            background.running.store(false, Ordering::SeqCst);
+        });
+        Ok(search)
+    }
+
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::SeqCst);
+    }
+
+    pub fn is_running(&self) -> bool {
+        self.running.load(Ordering::SeqCst)
+    }
+
+    pub fn results(&self) -> Vec<FileResult> {
+        self.results.read().unwrap().clone()
+    }
+}
+
+#[cfg(test)]
+fn rows(lines: &[ResultLine]) -> Vec<(usize, bool)> {
+    lines
+        .iter()
+        .map(|l| (l.row, !l.matches.is_empty()))
+        .collect()
+}
+
+#[test]
+fn search_with_context() {
+    let text = "a\nfoo\nb\nc\nd\nfoo foo\nfoo\ne";
+    let query = SearchQuery {
+        pattern: "foo".to_string(),
+        ..Default::default()
+    };
+    let lines = search_text(text, &query.to_regex().unwrap(), 1);
+    assert_eq!(
+        rows(&lines),
+        vec![
+            (0, false),
+            (1, true),
+            (2, false),
+            (4, false),
+            (5, true),
+            (6, true),
+            (7, false)
+        ]
+    );
+    assert_eq!(lines[4].matches, vec![(0, 3), (4, 7)]);
+
+    // literal searches escape the pattern, regex searches do not
+    let literal = SearchQuery {
+        pattern: "a.c".to_string(),
+        ..Default::default()
+    };
+    assert_eq!(
+        search_text("abc\na.c", &literal.to_regex().unwrap(), 0).len(),
+        1
+    );
+    let regex = SearchQuery {
+        regex: true,
+        ..literal
+    };
+    assert_eq!(
+        search_text("abc\na.c", &regex.to_regex().unwrap(), 0).len(),
+        2
+    );
+}
+
+#[test]
+fn replace_matches() {
+    let query = SearchQuery {
+        pattern: "(\\w+)@".to_string(),
+        regex: true,
+        case_sensitive: true,
+        ..Default::default()
+    };
+    let replacement = Replacement {
+        query,
+        text: "<$1>".to_string(),
+    };
+    assert_eq!(replacement.apply("a@ b@").unwrap(), "<a> <b>");
+    let literal = SearchQuery {
+        pattern: "A".to_string(),
+        ..Default::default()
+    };
+    let replacement = Replacement {
+        query: literal,
+        text: "$1".to_string(),
+    };
+    assert_eq!(replacement.apply("a A").unwrap(), "$1 $1");
+
+    // anchors match at every line and classes do not match line breaks, like the results show
+    let anchored = SearchQuery {
+        pattern: "^\\s*[^x]".to_string(),
+        regex: true,
+        ..Default::default()
+    };
+    let text = "a\r\n  b\nx\n";
+    assert_eq!(search_text(text, &anchored.to_regex().unwrap(), 0).len(), 2);
+    let replacement = Replacement {
+        query: anchored,
+        text: "-".to_string(),
+    };
+    assert_eq!(replacement.apply(text).unwrap(), "-\r\n-\nx\n");
+    assert_eq!(
+        SearchQuery {
+            include: "*.rs, src/**".to_string(),
+            exclude: "target".to_string(),
+            ..Default::default()
+        }
+        .globs(),
+        vec!["*.rs", "src/**", "!target"]
+    );
+}
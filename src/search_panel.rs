@@ -0,0 +1,309 @@
+use std::cell::Cell;
+use std::rc::Rc;
+
+use crate::cursor::{Cursor, Pos};
+use crate::fuzzy::split_matched;
+#[cfg(not(target_arch = "wasm32"))]
+use crate::search::Search;
+use crate::search::{Replacement, SearchQuery};
+use crate::utils::{color_to_string, sleep};
+use crate::workspace::{Edit, Workspace};
+use crate::{editor_colors, theme};
+use dioxus::prelude::*;
+use dioxus_html::KeyCode;
+
+// lines shown before and after each match
+const CONTEXT_LINES: usize = 1;
+// the most result lines shown at once
+const MAX_SHOWN: usize = 1000;
+// how often new results are shown while searching in milliseconds
+const POLL_INTERVAL: u32 = 100;
+
+/// Cancels the last search and starts searching the project.
+fn start_search(workspace: &mut Workspace, query: SearchQuery) -> Result<(), String> {
+    if let Some(search) = workspace.search.take() {
+        search.cancel();
+    }
+    let root = match &workspace.project {
+        Some(project) => project.root.clone(),
+        None => return Err("No folder opened".to_string()),
+    };
+    if query.pattern.is_empty() {
+        return Ok(());
+    }
+    #[cfg(not(target_arch = "wasm32"))]
+    {
+        workspace.search = Some(Search::start(root, query, CONTEXT_LINES)?);
+    }
+    #[cfg(target_arch = "wasm32")]
+    let _ = (root, query);
+    Ok(())
+}
+
+/// Opens every file with a match and replaces the matches in its buffer.
+fn replace_all(workspace: &mut Workspace, text: &str) {
+    let (project, search) = match (workspace.project.clone(), workspace.search.take()) {
+        (Some(project), Some(search)) => (project, search),
+        _ => return,
+    };
+    let replacement = Replacement {
+        query: search.query.clone(),
+        text: text.to_string(),
+    };
+    for result in search.results() {
+        if let Some(tab) = workspace.open_path(&project.path(&result.path)) {
+            workspace
+                .edits
+                .push((tab, Edit::Replace(replacement.clone())));
+        }
+    }
+}
+
+/// Opens a file from the results with the cursor on a line.
+fn open_result(workspace: &mut Workspace, path: &str, cursor: Cursor) {
+    let path = match &workspace.project {
+        Some(project) => project.path(path),
+        None => return,
+    };
+    if let Some(tab) = workspace.open_path(&path) {
+        workspace.goto = Some((tab, cursor));
+    }
+}
+
+#[derive(Props)]
+pub struct SearchPanelProps<'a> {
+    workspace: &'a UseRef<Workspace>,
+}
+pub fn SearchPanel<'a>(cx: Scope<'a, SearchPanelProps<'a>>) -> Element {
+    let workspace = cx.props.workspace;
+    let (query, set_query) = use_state(&cx, SearchQuery::default);
+    let (replace, set_replace) = use_state(&cx, String::new);
+    // replacements are previewed in the results before they are applied
+    let (preview, set_preview) = use_state(&cx, || false);
+    let (error, set_error) = use_state(&cx, || None::<String>);
+    let (tick, set_tick) = use_state(&cx, || 0usize);
+    let polling = cx.use_hook(|_| Rc::new(Cell::new(false)), |p| &*p);
+
+    let search = workspace.read().search.clone();
+    let running = search.as_ref().map_or(false, |s| s.is_running());
+    // show new results while searching
+    if running && !polling.get() {
+        polling.set(true);
+        let polling = polling.clone();
+        let set_tick = set_tick.clone();
+        let tick = *tick;
+        cx.push_future(async move {
+            sleep(POLL_INTERVAL).await;
+            polling.set(false);
+            set_tick(tick + 1);
+        });
+    }
+    let results = search.as_ref().map(|s| s.results()).unwrap_or_default();
+    let matches: usize = results.iter().map(|r| r.match_count()).sum();
+    let status = match (error, running) {
+        (Some(error), _) => error.clone(),
+        (None, true) => format!(
+            "Searching... {} matches in {} files",
+            matches,
+            results.len()
+        ),
+        (None, false) if search.is_some() => {
+            format!("{} matches in {} files", matches, results.len())
+        }
+        (None, false) => String::new(),
+    };
+    let replacement = Replacement {
+        query: search.map(|s| s.query).unwrap_or_default(),
+        text: replace.clone(),
+    };
+    let preview = *preview && !results.is_empty();
+
+    let run = move || {
+        set_preview(false);
+        set_error(start_search(&mut workspace.write(), query.clone()).err());
+    };
+    let toggle = move |regex: bool| {
+        set_query(if regex {
+            SearchQuery {
+                regex: !query.regex,
+                ..query.clone()
+            }
+        } else {
+            SearchQuery {
+                case_sensitive: !query.case_sensitive,
+                ..query.clone()
+            }
+        });
+    };
+
+    let colors = editor_colors();
+    let fg = color_to_string(colors.foreground);
+    let bg = color_to_string(theme().settings.gutter.unwrap_or(colors.background));
+    let found_bg = color_to_string(colors.find_highlight);
+    let found_fg = color_to_string(
+        colors
+            .find_highlight_foreground
+            .unwrap_or(colors.foreground),
+    );
+    let replaced_fg = color_to_string(theme().settings.accent.unwrap_or(colors.foreground));
+    let regex_weight = if query.regex { "bold" } else { "normal" };
+    let case_weight = if query.case_sensitive {
+        "bold"
+    } else {
+        "normal"
+    };
+    let mut shown = 0;
+
+    cx.render(rsx! {
+        div{
+            width: "100%",
+            max_height: "40%",
+            display: "flex",
+            flex_direction: "column",
+            color: "{fg}",
+            background_color: "{bg}",
+            div{
+                display: "flex",
+                flex_direction: "row",
+                flex_wrap: "wrap",
+                padding: "2px 6px",
+                input{
+                    placeholder: "Search",
+                    value: "{query.pattern}",
+                    oninput: move |e| set_query(SearchQuery {
+                        pattern: e.value.clone(),
+                        ..query.clone()
+                    }),
+                    onkeydown: move |k| if k.key_code == KeyCode::Enter { run() },
+                }
+                span{
+                    padding: "0px 6px",
+                    cursor: "pointer",
+                    font_weight: "{regex_weight}",
+                    onclick: move |_| toggle(true),
+                    ".*"
+                }
+                span{
+                    padding: "0px 6px",
+                    cursor: "pointer",
+                    font_weight: "{case_weight}",
+                    onclick: move |_| toggle(false),
+                    "Aa"
+                }
+                input{
+                    placeholder: "Replace",
+                    value: "{replace}",
+                    oninput: move |e| set_replace(e.value.clone()),
+                }
+                input{
+                    placeholder: "Files to include",
+                    value: "{query.include}",
+                    oninput: move |e| set_query(SearchQuery {
+                        include: e.value.clone(),
+                        ..query.clone()
+                    }),
+                    onkeydown: move |k| if k.key_code == KeyCode::Enter { run() },
+                }
+                input{
+                    placeholder: "Files to exclude",
+                    value: "{query.exclude}",
+                    oninput: move |e| set_query(SearchQuery {
+                        exclude: e.value.clone(),
+                        ..query.clone()
+                    }),
+                    onkeydown: move |k| if k.key_code == KeyCode::Enter { run() },
+                }
+                button{ onclick: move |_| run(), "Search" }
+                running.then(|| rsx! {
+                    button{
+                        onclick: move |_| {
+                            if let Some(search) = &workspace.read().search {
+                                search.cancel();
+                            }
+                        },
+                        "Stop"
+                    }
+                })
+                (!running && !preview).then(|| rsx! {
+                    button{ onclick: move |_| set_preview(true), "Replace All" }
+                })
+                preview.then(|| rsx! {
+                    button{
+                        onclick: move |_| {
+                            replace_all(&mut workspace.write(), replace);
+                            set_preview(false);
+                        },
+                        "Apply"
+                    }
+                    button{ onclick: move |_| set_preview(false), "Cancel" }
+                })
+                span{
+                    padding: "0px 6px",
+                    cursor: "pointer",
+                    onclick: move |_| workspace.write().search_panel = false,
+                    "×"
+                }
+            }
+            div{ padding: "0px 6px", "{status}" }
+            div{
+                overflow: "auto",
+                results.into_iter().map(|result| {
+                    let header = format!("{} ({})", result.path, result.match_count());
+                    let path = result.path;
+                    let lines: Vec<_> = result.lines.into_iter().take(MAX_SHOWN.saturating_sub(shown)).collect();
+                    shown += lines.len();
+                    let found_bg = found_bg.clone();
+                    let found_fg = found_fg.clone();
+                    let replaced_fg = replaced_fg.clone();
+                    let replacement = &replacement;
+                    rsx! {
+                        div{
+                            key: "{path}",
+                            div{ padding: "0px 6px", font_weight: "bold", "{header}" }
+                            lines.into_iter().map(move |line| {
+                                let number = line.row + 1;
+                                let row = line.row;
+                                // clicking a match selects it, clicking a context line moves the cursor to it
+                                let cursor = match line.matches.first() {
+                                    Some((start, end)) => Cursor::new(Pos::new(*start, row), Pos::new(*end, row)),
+                                    None => Cursor::from_start(Pos::new(0, row)),
+                                };
+                                let opacity = if line.matches.is_empty() { "0.6" } else { "1" };
+                                let indexes: Vec<usize> = line.matches.iter().flat_map(|(start, end)| *start..*end).collect();
+                                let pieces = split_matched(&line.text, &indexes);
+                                let replaced = (preview && !line.matches.is_empty())
+                                    .then(|| replacement.apply(&line.text).ok())
+                                    .flatten();
+                                let path = path.clone();
+                                let found_bg = found_bg.clone();
+                                let found_fg = found_fg.clone();
+                                let replaced_fg = replaced_fg.clone();
+                                rsx! {
+                                    div{
+                                        key: "{row}",
+                                        padding: "0px 6px",
+                                        white_space: "pre",
+                                        cursor: "pointer",
+                                        opacity: "{opacity}",
+                                        onclick: move |_| open_result(&mut workspace.write(), &path, cursor.clone()),
+                                        span{ padding_right: "12px", "{number}" }
+                                        pieces.into_iter().map(|(matched, text)| {
+                                            if matched {
+                                                rsx!(span{ color: "{found_fg}", background_color: "{found_bg}", "{text}" })
+                                            } else {
+                                                rsx!(span{ "{text}" })
+                                            }
+                                        })
+                                        replaced.map(|replaced| rsx! {
+                                            span{ padding_left: "12px", color: "{replaced_fg}", "→ {replaced}" }
+                                        })
+                                    }
+                                }
+                            })
+                        }
+                    }
+                })
+            }
+        }
+    })
+}
@@ -0,0 +1,58 @@
+use std::path::PathBuf;
+
+use serde::{Deserialize, Serialize};
+
+use crate::encoding::Encoding;
+use crate::storage;
+use crate::workspace::{Node, Pane, ViewState};
+
+// the file the session is kept in
+const SESSION_FILE: &str = "session.json";
+
+/// A tab of the session, the text of files is read again when it is restored.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SessionTab {
+    pub id: usize,
+    pub name: String,
+    pub extension: String,
+    pub path: Option<PathBuf>,
+    pub pinned: bool,
+    pub dirty: bool,
+    // sessions saved before encodings were detected have UTF-8 files
+    #[serde(default)]
+    pub encoding: Encoding,
+    // the text of a buffer that is not a file or has changes that are not saved
+    pub text: Option<String>,
+}
+
+/// The state of a view of a tab in a pane.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SessionView {
+    pub pane: usize,
+    pub tab: usize,
+    pub state: ViewState,
+}
+
+/// Everything open in the editor, saved while it runs and restored on the next launch.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Session {
+    pub tabs: Vec<SessionTab>,
+    // the index of the active tab
+    pub active: usize,
+    pub panes: Vec<Pane>,
+    pub layout: Node,
+    pub focused: usize,
+    pub views: Vec<SessionView>,
+}
+
+/// The session saved by the last run, if there is one that can be restored.
+pub fn load_session() -> Option<Session> {
+    let session: Session = serde_json::from_str(&storage::load(SESSION_FILE)?).ok()?;
+    let valid = !session.tabs.is_empty() && session.panes.iter().any(|p| p.id == session.focused);
+    valid.then(|| session)
+}
+
+pub fn save_session(session: &Session) -> Result<(), String> {
+    let json = serde_json::to_string(session).map_err(|e| e.to_string())?;
+    storage::save(SESSION_FILE, &json)
+}
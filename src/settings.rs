@@ -0,0 +1,46 @@
+use std::fmt::{self, Display};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LineNumbers {
+    Absolute,
+    // distance from the cursor, for vim style motions
+    Relative,
+}
+
+impl Display for LineNumbers {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LineNumbers::Absolute => write!(f, "Absolute Numbers"),
+            LineNumbers::Relative => write!(f, "Relative Numbers"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CaretShape {
+    Bar,
+    Block,
+    Underline,
+}
+
+/// Display options for an editor view.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Settings {
+    pub line_numbers: LineNumbers,
+    pub caret_shape: CaretShape,
+    pub caret_blink: bool,
+    pub show_whitespace: bool,
+    pub indent_guides: bool,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            line_numbers: LineNumbers::Absolute,
+            caret_shape: CaretShape::Bar,
+            caret_blink: true,
+            show_whitespace: false,
+            indent_guides: true,
+        }
+    }
+}
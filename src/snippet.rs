@@ -0,0 +1,753 @@
+use std::collections::{BTreeMap, HashMap};
+use std::iter::Peekable;
+use std::path::Path;
+use std::str::Chars;
+
+use dioxus::events::KeyboardData;
+use dioxus_html::KeyCode;
+use ropey::Rope;
+use serde::Deserialize;
+
+use crate::commands::Context;
+use crate::completion::{Candidate, Completion, Kind};
+use crate::cursor::{Cursor, Pos};
+use crate::cursors::Cursors;
+use crate::fuzzy::fuzzy_match;
+use crate::utils::changed_chars;
+use crate::word::{word_at, word_start};
+
+// the snippet files of each syntax, in the format of VS Code snippet files
+const SNIPPET_FILES: &[(&str, &str)] = &[
+    ("Rust", include_str!("../snippets/rust.json")),
+    ("Python", include_str!("../snippets/python.json")),
+    ("C", include_str!("../snippets/c.json")),
+    ("C++", include_str!("../snippets/cpp.json")),
+];
+
+lazy_static! {
+    static ref SNIPPETS: HashMap<&'static str, Vec<SnippetDefinition>> = SNIPPET_FILES
+        .iter()
+        .map(|(syntax, json)| (*syntax, parse_snippets(json).unwrap()))
+        .collect();
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum OneOrMany {
+    One(String),
+    Many(Vec<String>),
+}
+
+#[derive(Deserialize)]
+struct SnippetEntry {
+    prefix: OneOrMany,
+    body: OneOrMany,
+    description: Option<String>,
+}
+
+/// A snippet that can be inserted by typing one of its prefixes.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SnippetDefinition {
+    pub name: String,
+    pub prefixes: Vec<String>,
+    pub body: String,
+    pub description: Option<String>,
+}
+
+/// Reads the snippets of a snippet file, a body given as a list is a list of lines.
+pub fn parse_snippets(json: &str) -> Result<Vec<SnippetDefinition>, String> {
+    let entries: BTreeMap<String, SnippetEntry> =
+        serde_json::from_str(json).map_err(|e| e.to_string())?;
+    Ok(entries
+        .into_iter()
+        .map(|(name, entry)| SnippetDefinition {
+            name,
+            prefixes: match entry.prefix {
+                OneOrMany::One(prefix) => vec![prefix],
+                OneOrMany::Many(prefixes) => prefixes,
+            },
+            body: match entry.body {
+                OneOrMany::One(body) => body,
+                OneOrMany::Many(lines) => lines.join("\n"),
+            },
+            description: entry.description,
+        })
+        .collect())
+}
+
+/// The snippets of a syntax.
+pub fn snippets(syntax: &str) -> &'static [SnippetDefinition] {
+    SNIPPETS.get(syntax).map_or(&[], |s| s.as_slice())
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Node {
+    Text(String),
+    // a tab stop with its placeholder
+    Stop(usize, Vec<Node>),
+    Choice(usize, Vec<String>),
+    // a variable with the text used if it is empty
+    Variable(String, Vec<Node>),
+}
+
+// a name after a $, the number of a tab stop or the name of a variable
+fn parse_name(chars: &mut Peekable<Chars>) -> String {
+    let mut name = String::new();
+    let numeric = chars.peek().map_or(false, |c| c.is_ascii_digit());
+    while let Some(&c) = chars.peek() {
+        let valid = if numeric {
+            c.is_ascii_digit()
+        } else {
+            c.is_ascii_alphabetic() || c == '_' || (!name.is_empty() && c.is_ascii_digit())
+        };
+        if !valid {
+            break;
+        }
+        name.push(c);
+        chars.next();
+    }
+    name
+}
+
+fn stop_or_variable(name: String, children: Vec<Node>) -> Node {
+    match name.parse() {
+        Ok(n) => Node::Stop(n, children),
+        Err(_) => Node::Variable(name, children),
+    }
+}
+
+// the choices of a ${n|a,b|} tab stop, after the first |
+fn parse_choices(chars: &mut Peekable<Chars>) -> Option<Vec<String>> {
+    let mut choices = vec![String::new()];
+    loop {
+        match chars.next()? {
+            '\\' => {
+                let c = chars.next()?;
+                choices.last_mut()?.push(c);
+            }
+            ',' => choices.push(String::new()),
+            '|' => return (chars.next()? == '}').then(|| choices),
+            c => choices.last_mut()?.push(c),
+        }
+    }
+}
+
+// the node after a $, or none if it is not valid and the $ is text
+fn parse_dollar(chars: &mut Peekable<Chars>) -> Option<Node> {
+    if chars.peek() != Some(&'{') {
+        let name = parse_name(chars);
+        return (!name.is_empty()).then(|| stop_or_variable(name, Vec::new()));
+    }
+    chars.next();
+    let name = parse_name(chars);
+    if name.is_empty() {
+        return None;
+    }
+    match chars.next()? {
+        '}' => Some(stop_or_variable(name, Vec::new())),
+        ':' => {
+            let children = parse_nodes(chars, true);
+            (chars.next()? == '}').then(|| stop_or_variable(name, children))
+        }
+        '|' => {
+            let n = name.parse().ok()?;
+            Some(Node::Choice(n, parse_choices(chars)?))
+        }
+        _ => None,
+    }
+}
+
+// nested nodes end at the } of their placeholder
+fn parse_nodes(chars: &mut Peekable<Chars>, nested: bool) -> Vec<Node> {
+    let mut nodes = Vec::new();
+    let mut text = String::new();
+    while let Some(&c) = chars.peek() {
+        match c {
+            '}' if nested => break,
+            '\\' => {
+                chars.next();
+                match chars.peek() {
+                    Some(&c) if matches!(c, '$' | '}' | '\\') => {
+                        text.push(c);
+                        chars.next();
+                    }
+                    _ => text.push('\\'),
+                }
+            }
+            '$' => {
+                chars.next();
+                let mut attempt = chars.clone();
+                match parse_dollar(&mut attempt) {
+                    Some(node) => {
+                        *chars = attempt;
+                        if !text.is_empty() {
+                            nodes.push(Node::Text(std::mem::take(&mut text)));
+                        }
+                        nodes.push(node);
+                    }
+                    None => text.push('$'),
+                }
+            }
+            c => {
+                text.push(c);
+                chars.next();
+            }
+        }
+    }
+    if !text.is_empty() {
+        nodes.push(Node::Text(text));
+    }
+    nodes
+}
+
+/// A tab stop of an expanded snippet.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Stop {
+    // the char ranges of the stop and its mirrors
+    pub ranges: Vec<(usize, usize)>,
+    pub choices: Vec<String>,
+}
+
+/// The text of a snippet with its tab stops in order, the final stop is last.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Expansion {
+    pub text: String,
+    pub stops: Vec<Stop>,
+}
+
+struct Expander<'a> {
+    // the first placeholder of each tab stop, used by its mirrors
+    placeholders: BTreeMap<usize, Vec<Node>>,
+    variables: &'a BTreeMap<&'static str, String>,
+    // the indent of the line the snippet is inserted on
+    line_indent: &'a str,
+    // the line ending of the buffer
+    line_ending: &'a str,
+    // the text of one level of indentation of the buffer
+    indent: &'a str,
+    text: String,
+    len: usize,
+    stops: BTreeMap<usize, Stop>,
+    // the tab stops whose placeholder is being expanded
+    expanding: Vec<usize>,
+}
+
+impl<'a> Expander<'a> {
+    fn find_placeholders(&mut self, nodes: &[Node]) {
+        for node in nodes {
+            match node {
+                Node::Stop(n, children) if !children.is_empty() => {
+                    self.placeholders
+                        .entry(*n)
+                        .or_insert_with(|| children.clone());
+                    self.find_placeholders(children);
+                }
+                Node::Choice(n, choices) => {
+                    self.placeholders
+                        .entry(*n)
+                        .or_insert_with(|| vec![Node::Text(choices[0].clone())]);
+                }
+                Node::Variable(_, children) => self.find_placeholders(children),
+                _ => {}
+            }
+        }
+    }
+
+    // lines are indented like the line of the snippet, tabs in the snippet are indents of the buffer
+    fn push(&mut self, text: &str, from_snippet: bool) {
+        let mut chars = text.chars().peekable();
+        while let Some(c) = chars.next() {
+            let piece = match c {
+                // line breaks are written with the line ending of the buffer
+                '\r' if chars.peek() == Some(&'\n') => continue,
+                '\n' | '\r' => format!("{}{}", self.line_ending, self.line_indent),
+                '\t' if from_snippet => self.indent.to_string(),
+                c => c.to_string(),
+            };
+            self.len += piece.chars().count();
+            self.text.push_str(&piece);
+        }
+    }
+
+    fn expand(&mut self, nodes: &[Node]) {
+        for node in nodes {
+            match node {
+                Node::Text(text) => self.push(text, true),
+                Node::Stop(n, children) => {
+                    let start = self.len;
+                    if !children.is_empty() {
+                        self.expand(children);
+                    } else if !self.expanding.contains(n) {
+                        if let Some(placeholder) = self.placeholders.get(n).cloned() {
+                            self.expanding.push(*n);
+                            self.expand(&placeholder);
+                            self.expanding.pop();
+                        }
+                    }
+                    let end = self.len;
+                    self.stops.entry(*n).or_default().ranges.push((start, end));
+                }
+                Node::Choice(n, choices) => {
+                    let start = self.len;
+                    self.push(&choices[0], true);
+                    let stop = self.stops.entry(*n).or_default();
+                    stop.ranges.push((start, self.len));
+                    stop.choices = choices.clone();
+                }
+                Node::Variable(name, default) => {
+                    let variables = self.variables;
+                    match variables.get(name.as_str()) {
+                        Some(value) if !value.is_empty() => self.push(value, false),
+                        Some(_) => self.expand(default),
+                        // unknown variables are inserted as their name
+                        None if default.is_empty() => self.push(name, false),
+                        None => self.expand(default),
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Expands a snippet body, the variables are the ones given by `variables`.
+pub fn expand(
+    body: &str,
+    variables: &BTreeMap<&'static str, String>,
+    line_indent: &str,
+    line_ending: &str,
+    indent: &str,
+) -> Expansion {
+    let nodes = parse_nodes(&mut body.chars().peekable(), false);
+    let mut expander = Expander {
+        placeholders: BTreeMap::new(),
+        variables,
+        line_indent,
+        line_ending,
+        indent,
+        text: String::new(),
+        len: 0,
+        stops: BTreeMap::new(),
+        expanding: Vec::new(),
+    };
+    expander.find_placeholders(&nodes);
+    expander.expand(&nodes);
+    // the final stop is $0, or the end of the snippet if it has none
+    let last = expander.stops.remove(&0).unwrap_or_else(|| Stop {
+        ranges: vec![(expander.len, expander.len)],
+        choices: Vec::new(),
+    });
+    let mut stops: Vec<_> = expander.stops.into_values().collect();
+    stops.push(last);
+    Expansion {
+        text: expander.text,
+        stops,
+    }
+}
+
+/// The values of the snippet variables for a snippet inserted over a range of a buffer.
+pub fn variables(
+    path: Option<&Path>,
+    rope: &Rope,
+    start: usize,
+    selected: String,
+) -> BTreeMap<&'static str, String> {
+    let row = rope.char_to_line(start);
+    let name = |f: fn(&Path) -> Option<&std::ffi::OsStr>| {
+        path.and_then(f)
+            .map(|n| n.to_string_lossy().to_string())
+            .unwrap_or_default()
+    };
+    let word = word_at(&Pos::from_idx(start, rope), rope)
+        .map(|(s, e)| rope.slice(s..e).to_string())
+        .unwrap_or_default();
+    let line = rope.line(row).to_string();
+    BTreeMap::from([
+        ("TM_FILENAME", name(Path::file_name)),
+        ("TM_FILENAME_BASE", name(Path::file_stem)),
+        (
+            "TM_DIRECTORY",
+            path.and_then(Path::parent)
+                .map(|p| p.display().to_string())
+                .unwrap_or_default(),
+        ),
+        (
+            "TM_FILEPATH",
+            path.map(|p| p.display().to_string()).unwrap_or_default(),
+        ),
+        ("TM_LINE_INDEX", row.to_string()),
+        ("TM_LINE_NUMBER", (row + 1).to_string()),
+        (
+            "TM_CURRENT_LINE",
+            line.trim_end_matches(&['\r', '\n'][..]).to_string(),
+        ),
+        ("TM_CURRENT_WORD", word),
+        ("TM_SELECTED_TEXT", selected),
+    ])
+}
+
+/// The tab stops of a snippet inserted in a buffer, kept up to date with edits until it is finished.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SnippetSession {
+    // the tab the snippet was inserted in
+    pub tab: usize,
+    stops: Vec<Stop>,
+    current: usize,
+    // the text the ranges of the stops are in
+    rope: Rope,
+}
+
+// moves an index over edits given as old and new char ranges,
+// an index at the start of an edit only stays before it if it sticks left
+fn shift(idx: usize, edits: &[(usize, usize, usize, usize)], left: bool) -> usize {
+    for &(start, end, new_start, new_end) in edits {
+        if idx < start {
+            return new_start - (start - idx);
+        }
+        if idx == start && (start < end || left) {
+            return new_start;
+        }
+        if idx < end {
+            return if left { new_start } else { new_end };
+        }
+        if idx == end {
+            return new_end;
+        }
+    }
+    match edits.last() {
+        Some(&(_, end, _, new_end)) => idx - end + new_end,
+        None => idx,
+    }
+}
+
+// the edits if the same text replaced every range and nothing else changed, like typing at linked cursors
+fn mirrored_edits(
+    old: &Rope,
+    new: &Rope,
+    ranges: &[(usize, usize)],
+) -> Option<Vec<(usize, usize, usize, usize)>> {
+    let mut ranges = ranges.to_vec();
+    ranges.sort_unstable();
+    let removed: usize = ranges.iter().map(|(s, e)| e - s).sum();
+    let added = new.len_chars().checked_sub(old.len_chars() - removed)?;
+    if added % ranges.len() != 0 {
+        return None;
+    }
+    let len = added / ranges.len();
+    let mut edits = Vec::new();
+    let (mut old_idx, mut new_idx) = (0, 0);
+    for (start, end) in ranges {
+        let gap = start.checked_sub(old_idx)?;
+        if old.slice(old_idx..start) != new.slice(new_idx..new_idx + gap) {
+            return None;
+        }
+        new_idx += gap;
+        if let Some(&(_, _, first, _)) = edits.first() {
+            if new.slice(first..first + len) != new.slice(new_idx..new_idx + len) {
+                return None;
+            }
+        }
+        edits.push((start, end, new_idx, new_idx + len));
+        old_idx = end;
+        new_idx += len;
+    }
+    (old.slice(old_idx..) == new.slice(new_idx..)).then(|| edits)
+}
+
+impl SnippetSession {
+    /// Selects the current tab stop with a cursor at each of its ranges.
+    fn select(&self, cursors: &mut Cursors) {
+        let rope = &self.rope;
+        *cursors = Cursors::new(
+            self.stops[self.current]
+                .ranges
+                .iter()
+                .map(|&(start, end)| {
+                    let start = Pos::from_idx(start, rope);
+                    let end = Pos::from_idx(end, rope);
+                    if start == end {
+                        Cursor::from_start(start)
+                    } else {
+                        Cursor::new(start, end)
+                    }
+                })
+                .collect(),
+        );
+    }
+
+    /// If the current stop is the final one, the snippet is finished once it is reached.
+    fn finished(&self) -> bool {
+        self.current + 1 == self.stops.len()
+    }
+
+    /// The choices of the current stop shown in the completion popup.
+    fn choices(&self) -> Option<Completion> {
+        let choices = &self.stops[self.current].choices;
+        (!choices.is_empty()).then(|| Completion {
+            candidates: choices
+                .iter()
+                .filter_map(|c| {
+                    let m = fuzzy_match("", c)?;
+                    let candidate = Candidate {
+                        detail: "choice".to_string(),
+                        ..Candidate::new(c, Kind::Snippet)
+                    };
+                    Some((candidate, m))
+                })
+                .collect(),
+            selected: 0,
+        })
+    }
+
+    /// Moves the stops to follow an edit of the buffer.
+    /// Text typed at the edges of the current stop is added to it.
+    pub fn update(&mut self, new: &Rope) {
+        if self.rope == *new {
+            return;
+        }
+        let old = std::mem::replace(&mut self.rope, new.clone());
+        let edits =
+            mirrored_edits(&old, new, &self.stops[self.current].ranges).unwrap_or_else(|| {
+                let (prefix, changed_end, new_end) = changed_chars(&old, new);
+                vec![(prefix, changed_end, prefix, new_end)]
+            });
+        for (i, stop) in self.stops.iter_mut().enumerate() {
+            let current = i == self.current;
+            for (start, end) in stop.ranges.iter_mut() {
+                *start = shift(*start, &edits, current);
+                *end = shift(*end, &edits, false).max(*start);
+            }
+        }
+    }
+
+    /// If a position is in one of the ranges of the current stop.
+    pub fn contains(&self, idx: usize) -> bool {
+        self.stops[self.current]
+            .ranges
+            .iter()
+            .any(|&(start, end)| start <= idx && idx <= end)
+    }
+}
+
+/// Inserts a snippet at every cursor, replacing the selection or the word before the cursor.
+/// The first tab stop is selected, with a linked cursor at each of its mirrors.
+pub fn insert_snippet(ctx: &mut Context, body: &str) {
+    let path = ctx
+        .workspace
+        .read()
+        .tabs
+        .get(ctx.tab)
+        .and_then(|t| t.path.clone());
+    let indent = ctx.buffer.indent.text();
+    let line_ending = ctx.buffer.line_ending.as_str();
+    let rope = &mut ctx.buffer.rope;
+    let mut sites: Vec<_> = ctx
+        .cursors
+        .list
+        .iter()
+        .map(|c| {
+            let (start, end) = match c.end {
+                Some(_) => (c.first().idx(rope), c.last().idx(rope)),
+                None => {
+                    let idx = c.start.idx(rope);
+                    (word_start(idx, rope), idx)
+                }
+            };
+            let selected = if c.end.is_some() {
+                rope.slice(start..end).to_string()
+            } else {
+                String::new()
+            };
+            let line_indent: String = rope
+                .line(rope.char_to_line(start))
+                .chars()
+                .take_while(|c| *c == ' ' || *c == '\t')
+                .collect();
+            let variables = variables(path.as_deref(), rope, start, selected);
+            (
+                start,
+                end,
+                expand(body, &variables, &line_indent, line_ending, &indent),
+            )
+        })
+        .collect();
+    sites.sort_by_key(|(start, _, _)| *start);
+    for (start, end, expansion) in sites.iter().rev() {
+        rope.remove(*start..*end);
+        rope.insert(*start, &expansion.text);
+    }
+
+    // every site has the same stops, the ranges of a stop at each site are linked
+    let mut stops = vec![Stop::default(); sites[0].2.stops.len()];
+    let mut inserted = 0;
+    let mut removed = 0;
+    for (start, end, expansion) in &sites {
+        let offset = start + inserted - removed;
+        for (stop, site_stop) in stops.iter_mut().zip(&expansion.stops) {
+            stop.ranges.extend(
+                site_stop
+                    .ranges
+                    .iter()
+                    .map(|(s, e)| (s + offset, e + offset)),
+            );
+            stop.choices = site_stop.choices.clone();
+        }
+        inserted += expansion.text.chars().count();
+        removed += end - start;
+    }
+
+    let session = SnippetSession {
+        tab: ctx.tab,
+        stops,
+        current: 0,
+        rope: rope.clone(),
+    };
+    session.select(ctx.cursors);
+    let mut workspace = ctx.workspace.write();
+    workspace.close_completion();
+    workspace.completion = session.choices();
+    workspace.snippet = (!session.finished()).then(|| session);
+}
+
+/// Handles a key press while a snippet is inserted, returns false for keys that are not used by it.
+/// Tab and shift tab move between the stops, escape leaves the cursors where they are.
+pub fn snippet_input(k: &KeyboardData, ctx: &mut Context) -> bool {
+    let used = match k.key_code {
+        KeyCode::Tab => !k.ctrl_key && !k.alt_key,
+        KeyCode::Escape => true,
+        _ => false,
+    };
+    let mut session = match ctx.workspace.read().snippet.clone() {
+        Some(session) if used && session.tab == ctx.tab => session,
+        _ => return false,
+    };
+    let mut workspace = ctx.workspace.write();
+    if k.key_code == KeyCode::Escape {
+        workspace.snippet = None;
+        return true;
+    }
+    if k.shift_key {
+        session.current = session.current.saturating_sub(1);
+    } else {
+        session.current += 1;
+    }
+    session.select(ctx.cursors);
+    workspace.close_completion();
+    workspace.completion = session.choices();
+    workspace.snippet = (!session.finished()).then(|| session);
+    true
+}
+
+/// Moves the stops of the snippet after a key press, the snippet is finished once the cursor leaves its current stop.
+pub fn update_snippet(ctx: &mut Context) {
+    let old = match ctx.workspace.read().snippet.clone() {
+        Some(session) if session.tab == ctx.tab => session,
+        _ => return,
+    };
+    let rope = &ctx.buffer.rope;
+    let mut session = old.clone();
+    session.update(rope);
+    if !session.contains(ctx.cursors.primary().caret().idx(rope)) {
+        ctx.workspace.write().snippet = None;
+    } else if session != old {
+        ctx.workspace.write().snippet = Some(session);
+    }
+}
+
+#[test]
+fn expand_snippets() {
+    let variables = BTreeMap::from([("TM_FILENAME", "main.rs".to_string())]);
+    let expansion = expand(
+        "fn ${1:name}(${2:arg}: ${3|i32,u8|}) {\n\t$1($2) // $TM_FILENAME \\$ $UNKNOWN ${TM_SELECTED_TEXT:x}\n}",
+        &variables,
+        "  ",
+        "\n",
+        "    ",
+    );
+    assert_eq!(
+        expansion.text,
+        "fn name(arg: i32) {\n      name(arg) // main.rs $ UNKNOWN x\n  }"
+    );
+    assert_eq!(
+        expansion.stops,
+        vec![
+            Stop {
+                ranges: vec![(3, 7), (26, 30)],
+                choices: Vec::new()
+            },
+            Stop {
+                ranges: vec![(8, 11), (31, 34)],
+                choices: Vec::new()
+            },
+            Stop {
+                ranges: vec![(13, 16)],
+                choices: vec!["i32".to_string(), "u8".to_string()]
+            },
+            // without a $0 the snippet ends at its end
+            Stop {
+                ranges: vec![(62, 62)],
+                choices: Vec::new()
+            },
+        ]
+    );
+
+    // nested placeholders and invalid syntax that is kept as text
+    let expansion = expand("${1:a ${2:b}} ${x $0", &BTreeMap::new(), "", "\n", "\t");
+    assert_eq!(expansion.text, "a b ${x ");
+    assert_eq!(expansion.stops[0].ranges, vec![(0, 3)]);
+    assert_eq!(expansion.stops[1].ranges, vec![(2, 3)]);
+    assert_eq!(expansion.stops[2].ranges, vec![(8, 8)]);
+
+    let rust = parse_snippets(include_str!("../snippets/rust.json")).unwrap();
+    let function = rust.iter().find(|s| s.prefixes == ["fn"]).unwrap();
+    assert_eq!(function.body, "fn ${1:name}(${2}) {\n\t$0\n}");
+    assert!(SNIPPET_FILES
+        .iter()
+        .all(|(syntax, _)| !snippets(syntax).is_empty()));
+}
+
+#[test]
+fn snippet_session_stops() {
+    let expansion = expand("${1:a}(${1:a}, $2)$0", &BTreeMap::new(), "", "\n", "\t");
+    let mut rope = Rope::from_str("x = \n");
+    rope.insert(4, &expansion.text);
+    let offset = |stop: &Stop| Stop {
+        ranges: stop.ranges.iter().map(|(s, e)| (s + 4, e + 4)).collect(),
+        choices: Vec::new(),
+    };
+    let mut session = SnippetSession {
+        tab: 0,
+        stops: expansion.stops.iter().map(offset).collect(),
+        current: 0,
+        rope: rope.clone(),
+    };
+    let mut cursors = Cursors::default();
+    session.select(&mut cursors);
+    assert_eq!(
+        cursors.list,
+        vec![
+            Cursor::new(Pos::new(4, 0), Pos::new(5, 0)),
+            Cursor::new(Pos::new(6, 0), Pos::new(7, 0)),
+        ]
+    );
+
+    // typing replaces both mirrors and moves the later stops
+    cursors.insert_text("abc", &mut rope);
+    assert_eq!(rope.to_string(), "x = abc(abc, )\n");
+    session.update(&rope);
+    assert_eq!(session.stops[0].ranges, vec![(4, 7), (8, 11)]);
+    assert_eq!(session.stops[1].ranges, vec![(13, 13)]);
+    assert!(session.contains(cursors.primary().caret().idx(&rope)));
+
+    session.current = 1;
+    session.select(&mut cursors);
+    cursors.insert_text("1", &mut rope);
+    session.update(&rope);
+    assert_eq!(session.stops[1].ranges, vec![(13, 14)]);
+    assert_eq!(session.stops[2].ranges, vec![(15, 15)]);
+
+    // an edit before the snippet moves every stop
+    rope.insert(0, "let ");
+    session.update(&rope);
+    assert_eq!(session.stops[0].ranges, vec![(8, 11), (12, 15)]);
+    assert_eq!(session.stops[2].ranges, vec![(19, 19)]);
+}
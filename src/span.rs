@@ -1,21 +1,27 @@
 use crate::utils::color_to_string;
 use dioxus::prelude::*;
-use syntect::highlighting::{FontStyle, Style};
+use syntect::highlighting::{Color, FontStyle, Style};
 
 #[derive(Props, PartialEq)]
 pub struct SpanProps {
     style: Style,
     text: String,
+    // a wavy line under the text, like the underline of a diagnostic
+    #[props(default)]
+    underline: Option<Color>,
 }
 pub fn Span(cx: Scope<SpanProps>) -> Element {
     let text = &cx.props.text;
 
     let fg = color_to_string(cx.props.style.foreground);
     let bg = color_to_string(cx.props.style.background);
-    let text_decoration = if cx.props.style.font_style.contains(FontStyle::UNDERLINE) {
-        "underline"
-    } else {
-        "none"
+    let text_decoration = match cx.props.underline {
+        #[cfg(feature = "term")]
+        Some(_) => "underline".to_string(),
+        #[cfg(not(feature = "term"))]
+        Some(color) => format!("underline wavy {}", color_to_string(color)),
+        None if cx.props.style.font_style.contains(FontStyle::UNDERLINE) => "underline".to_string(),
+        None => "none".to_string(),
     };
     let font_weight = if cx.props.style.font_style.contains(FontStyle::BOLD) {
         "bold"
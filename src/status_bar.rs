@@ -0,0 +1,211 @@
+use crate::buffer::{Buffer, Indent, LineEnding, Wrap};
+use crate::cursors::Cursors;
+use crate::settings::{LineNumbers, Settings};
+use crate::utils::color_to_string;
+use crate::workspace::Workspace;
+use crate::{theme, PS};
+use dioxus::prelude::*;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Picker {
+    Language,
+    Indent,
+    LineEnding,
+    Wrap,
+}
+
+impl Picker {
+    fn options(&self) -> Vec<String> {
+        match self {
+            Picker::Language => {
+                let mut names: Vec<_> = PS
+                    .syntaxes()
+                    .iter()
+                    .filter(|s| !s.hidden)
+                    .map(|s| s.name.clone())
+                    .collect();
+                names.sort_unstable();
+                names
+            }
+            Picker::Indent => Indent::OPTIONS.iter().map(|i| i.to_string()).collect(),
+            Picker::LineEnding => LineEnding::OPTIONS.iter().map(|l| l.to_string()).collect(),
+            Picker::Wrap => Wrap::options().iter().map(|w| w.to_string()).collect(),
+        }
+    }
+
+    // only changing the line ending changes the text, which read-only buffers refuse
+    fn apply(&self, option: usize, name: &str, buffer: &mut Buffer) -> Result<(), String> {
+        match self {
+            Picker::Language => {
+                if let Some(syntax) = PS.find_syntax_by_name(name) {
+                    buffer.syntax = syntax;
+                }
+            }
+            Picker::Indent => buffer.indent = Indent::OPTIONS[option],
+            Picker::LineEnding => return buffer.set_line_ending(LineEnding::OPTIONS[option]),
+            Picker::Wrap => buffer.wrap = Wrap::options()[option],
+        }
+        Ok(())
+    }
+}
+
+#[derive(Props)]
+pub struct StatusBarProps<'a> {
+    // read-only buffers show why they were not changed in it
+    workspace: &'a UseRef<Workspace>,
+    buffer: &'a UseRef<Buffer>,
+    cursors: &'a UseRef<Cursors>,
+    settings: &'a UseRef<Settings>,
+    // a macro is being recorded
+    recording: bool,
+}
+pub fn StatusBar<'a>(cx: Scope<'a, StatusBarProps<'a>>) -> Element {
+    let (picker, set_picker) = use_state(&cx, || None::<Picker>);
+    let workspace = cx.props.workspace;
+    let buffer = cx.props.buffer;
+    let settings = cx.props.settings;
+
+    let current = buffer.read();
+    let cursors = cx.props.cursors.read();
+    let caret = cursors.primary().caret();
+    let position = format!("{}:{}", caret.row() + 1, caret.col(&current.rope) + 1);
+    let cursor_count = match cursors.list.len() {
+        1 => String::new(),
+        n => format!("{} cursors", n),
+    };
+    let selection = match cursors.selection_len(&current.rope) {
+        (0, _) => String::new(),
+        (chars, lines) => format!("({} chars, {} lines selected)", chars, lines),
+    };
+    let language = current.syntax.name.clone();
+    let indent = current.indent.to_string();
+    let encoding = if current.read_only {
+        format!("{} (read-only)", current.encoding)
+    } else {
+        current.encoding.to_string()
+    };
+    let line_ending = current.line_ending.to_string();
+    let wrap = current.wrap.to_string();
+    let line_numbers = settings.read().line_numbers;
+    let whitespace = if settings.read().show_whitespace {
+        "Whitespace: Shown"
+    } else {
+        "Whitespace: Hidden"
+    };
+    drop(current);
+    drop(cursors);
+
+    let fg = color_to_string(theme().settings.foreground.unwrap());
+    let bg = color_to_string(
+        theme()
+            .settings
+            .gutter
+            .unwrap_or(theme().settings.background.unwrap()),
+    );
+
+    let options = picker.map(|p| p.options()).unwrap_or_default();
+    let toggle = move |p: Picker| {
+        set_picker(if *picker == Some(p) { None } else { Some(p) });
+    };
+
+    cx.render(rsx! {
+        div{
+            width: "100%",
+            display: "flex",
+            flex_direction: "column",
+            color: "{fg}",
+            background_color: "{bg}",
+
+            div{
+                display: "flex",
+                flex_direction: "row",
+                flex_wrap: "wrap",
+                max_height: "30vh",
+                overflow: "auto",
+                options.into_iter().enumerate().map(|(i, name)| {
+                    let label = name.clone();
+                    cx.render(rsx! {
+                        div{
+                            padding: "0px 6px",
+                            cursor: "pointer",
+                            onclick: move |_| {
+                                if let Some(p) = picker {
+                                    let applied = p.apply(i, &name, &mut buffer.write());
+                                    if let Err(e) = applied {
+                                        workspace.write().message = Some(e);
+                                    }
+                                }
+                                set_picker(None);
+                            },
+                            "{label}"
+                        }
+                    })
+                })
+            }
+
+            div{
+                display: "flex",
+                flex_direction: "row",
+                justify_content: "space-between",
+                div{
+                    display: "flex",
+                    flex_direction: "row",
+                    span{ padding: "0px 6px", "{position}" }
+                    span{ padding: "0px 6px", "{cursor_count}" }
+                    span{ padding: "0px 6px", "{selection}" }
+                    cx.props.recording.then(|| rsx!(span{ padding: "0px 6px", "● Recording macro" }))
+                }
+                div{
+                    display: "flex",
+                    flex_direction: "row",
+                    span{
+                        padding: "0px 6px",
+                        cursor: "pointer",
+                        onclick: move |_| toggle(Picker::Indent),
+                        "{indent}"
+                    }
+                    span{
+                        padding: "0px 6px",
+                        cursor: "pointer",
+                        onclick: move |_| {
+                            let mut settings = settings.write();
+                            settings.line_numbers = match settings.line_numbers {
+                                LineNumbers::Absolute => LineNumbers::Relative,
+                                LineNumbers::Relative => LineNumbers::Absolute,
+                            };
+                        },
+                        "{line_numbers}"
+                    }
+                    span{
+                        padding: "0px 6px",
+                        cursor: "pointer",
+                        onclick: move |_| {
+                            let mut settings = settings.write();
+                            settings.show_whitespace = !settings.show_whitespace;
+                        },
+                        "{whitespace}"
+                    }
+                    span{
+                        padding: "0px 6px",
+                        cursor: "pointer",
+                        onclick: move |_| toggle(Picker::Wrap),
+                        "{wrap}"
+                    }
+                    span{ padding: "0px 6px", "{encoding}" }
+                    span{
+                        padding: "0px 6px",
+                        cursor: "pointer",
+                        onclick: move |_| toggle(Picker::LineEnding),
+                        "{line_ending}"
+                    }
+                    span{
+                        padding: "0px 6px",
+                        cursor: "pointer",
+                        onclick: move |_| toggle(Picker::Language),
+                        "{language}"
+                    }
+                }
+            }
+        }
+    })
+}
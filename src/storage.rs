@@ -0,0 +1,103 @@
+#[cfg(not(target_arch = "wasm32"))]
+use std::path::PathBuf;
+
+// the directory the data files are kept in
+#[cfg(not(target_arch = "wasm32"))]
+fn data_dir() -> Option<PathBuf> {
+    let base = std::env::var_os("XDG_DATA_HOME")
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("APPDATA").map(PathBuf::from))
+        .or_else(|| {
+            std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".local/share"))
+        })?;
+    Some(base.join("editor"))
+}
+
+/// Reads the data saved under a name, like the saved macros.
+/// Desktop builds keep data in files of the user's data directory, web builds in the browser's local storage.
+pub fn load(name: &str) -> Option<String> {
+    #[cfg(target_arch = "wasm32")]
+    {
+        web_sys::window()?
+            .local_storage()
+            .ok()??
+            .get_item(&format!("editor/{}", name))
+            .ok()?
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    {
+        std::fs::read_to_string(data_dir()?.join(name)).ok()
+    }
+}
+
+/// Saves data under a name, replacing what was saved before.
+/// Files are written next to the old data and moved over it, so a crash leaves either the old or the new data.
+pub fn save(name: &str, data: &str) -> Result<(), String> {
+    #[cfg(target_arch = "wasm32")]
+    {
+        web_sys::window()
+            .and_then(|w| w.local_storage().ok().flatten())
+            .ok_or_else(|| "Local storage is not available".to_string())?
+            .set_item(&format!("editor/{}", name), data)
+            .map_err(|_| format!("Could not save {}", name))
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    {
+        let path = data_dir()
+            .ok_or_else(|| "No data directory was found".to_string())?
+            .join(name);
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir).map_err(|e| e.to_string())?;
+        }
+        let file = path.file_name().unwrap_or_default().to_string_lossy();
+        let temp = path.with_file_name(format!(".{}.tmp", file));
+        std::fs::write(&temp, data).map_err(|e| e.to_string())?;
+        std::fs::rename(&temp, &path).map_err(|e| e.to_string())
+    }
+}
+
+/// Removes the data saved under a name.
+pub fn remove(name: &str) {
+    #[cfg(target_arch = "wasm32")]
+    if let Some(storage) = web_sys::window().and_then(|w| w.local_storage().ok().flatten()) {
+        let _ = storage.remove_item(&format!("editor/{}", name));
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    if let Some(dir) = data_dir() {
+        let _ = std::fs::remove_file(dir.join(name));
+    }
+}
+
+/// The names of the data saved in a directory, like `recovery/`.
+pub fn list(dir: &str) -> Vec<String> {
+    #[cfg(target_arch = "wasm32")]
+    {
+        let prefix = format!("editor/{}", dir);
+        let storage = match web_sys::window().and_then(|w| w.local_storage().ok().flatten()) {
+            Some(storage) => storage,
+            None => return Vec::new(),
+        };
+        (0..storage.length().unwrap_or(0))
+            .filter_map(|i| storage.key(i).ok().flatten())
+            .filter(|key| key.starts_with(&prefix))
+            .map(|key| key["editor/".len()..].to_string())
+            .collect()
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    {
+        let entries = match data_dir().and_then(|d| std::fs::read_dir(d.join(dir)).ok()) {
+            Some(entries) => entries,
+            None => return Vec::new(),
+        };
+        entries
+            .filter_map(|e| e.ok()?.file_name().into_string().ok())
+            // files that are still being written start with a dot
+            .filter(|file| !file.starts_with('.'))
+            .map(|file| format!("{}{}", dir, file))
+            .collect()
+    }
+}
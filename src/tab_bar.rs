@@ -0,0 +1,98 @@
+use crate::utils::color_to_string;
+use crate::workspace::Workspace;
+use crate::{editor_colors, theme};
+use dioxus::prelude::*;
+
+#[derive(Props)]
+pub struct TabBarProps<'a> {
+    workspace: &'a UseRef<Workspace>,
+}
+pub fn TabBar<'a>(cx: Scope<'a, TabBarProps<'a>>) -> Element {
+    // the tab being dragged to a new position
+    let (drag_start, set_drag_start) = use_state(&cx, || None::<usize>);
+    let workspace = cx.props.workspace;
+
+    let current = workspace.read().tabs.clone();
+    let fg = color_to_string(editor_colors().foreground);
+    let active_bg = color_to_string(editor_colors().background);
+    let bg = color_to_string(
+        theme()
+            .settings
+            .gutter
+            .unwrap_or(editor_colors().background),
+    );
+
+    cx.render(rsx! {
+        div{
+            width: "100%",
+            display: "flex",
+            flex_direction: "row",
+            overflow: "auto",
+            color: "{fg}",
+            background_color: "{bg}",
+            onmouseup: move |_| set_drag_start(None),
+
+            current.tabs.into_iter().enumerate().map(|(i, tab)| {
+                let tab_bg = if i == current.active { active_bg.clone() } else { bg.clone() };
+                let name = tab.name;
+                let pin = if tab.pinned { "📌" } else { "" };
+                let dirty = if tab.dirty { "●" } else { "" };
+                let (decoration, title) = if tab.missing {
+                    ("line-through", "The file was deleted or moved outside of the editor")
+                } else {
+                    ("none", "")
+                };
+                let pinned = tab.pinned;
+                cx.render(rsx! {
+                    div{
+                        key: "{tab.id}",
+                        display: "flex",
+                        flex_direction: "row",
+                        padding: "0px 6px",
+                        cursor: "pointer",
+                        background_color: "{tab_bg}",
+                        onmousedown: move |_| {
+                            set_drag_start(Some(i));
+                            workspace.write().activate_tab(i);
+                        },
+                        onmouseup: move |_| {
+                            if let Some(from) = *drag_start {
+                                if from != i {
+                                    workspace.write().tabs.move_tab(from, i);
+                                }
+                            }
+                            set_drag_start(None);
+                        },
+                        ondblclick: move |_| workspace.write().tabs.toggle_pin(i),
+                        span{ "{pin}" }
+                        span{
+                            padding: "0px 4px",
+                            text_decoration: "{decoration}",
+                            title: "{title}",
+                            "{name}"
+                        }
+                        span{ "{dirty}" }
+                        // pinned tabs can not be closed from the tab bar
+                        (!pinned).then(|| rsx! {
+                            span{
+                                padding: "0px 4px",
+                                onclick: move |_| workspace.write().close_tab(i),
+                                "×"
+                            }
+                        })
+                    }
+                })
+            })
+            div{
+                padding: "0px 6px",
+                cursor: "pointer",
+                onclick: move |_| {
+                    let mut workspace = workspace.write();
+                    let name = format!("Untitled-{}", workspace.tabs.tabs.len() + 1);
+                    workspace.open(&name, "", "txt");
+                },
+                "+"
+            }
+        }
+    })
+}
@@ -0,0 +1,204 @@
+use std::path::PathBuf;
+
+use crate::encoding::Encoding;
+
+/// An open buffer in the tab bar.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TabInfo {
+    pub id: usize,
+    pub name: String,
+    // the text and file extension the buffer is created with
+    pub text: String,
+    // changes restored from the last session, applied to the text as an edit that can be undone
+    pub unsaved: Option<String>,
+    pub extension: String,
+    // the file the buffer was opened from
+    pub path: Option<PathBuf>,
+    pub pinned: bool,
+    // if the buffer has changes that are not saved
+    pub dirty: bool,
+    // if the file was deleted or moved outside of the editor
+    pub missing: bool,
+    // the encoding of the file, files with invalid sequences in it can not be edited
+    pub encoding: Encoding,
+    pub read_only: bool,
+}
+
+/// The open buffers in the order they are shown, pinned tabs are always in front.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Tabs {
+    pub tabs: Vec<TabInfo>,
+    pub active: usize,
+    next_id: usize,
+}
+
+impl Tabs {
+    /// Opens a new tab after the other tabs and switches to it.
+    pub fn open(&mut self, name: &str, text: &str, extension: &str) -> usize {
+        let id = self.next_id;
+        self.next_id += 1;
+        self.tabs.push(TabInfo {
+            id,
+            name: name.to_string(),
+            text: text.to_string(),
+            unsaved: None,
+            extension: extension.to_string(),
+            path: None,
+            pinned: false,
+            dirty: false,
+            missing: false,
+            encoding: Encoding::default(),
+            read_only: false,
+        });
+        self.active = self.tabs.len() - 1;
+        id
+    }
+
+    /// The tabs of the last session, new tabs get ids after theirs.
+    pub fn restore(tabs: Vec<TabInfo>, active: usize) -> Self {
+        let next_id = tabs.iter().map(|t| t.id + 1).max().unwrap_or(0);
+        Self {
+            active: active.min(tabs.len().saturating_sub(1)),
+            tabs,
+            next_id,
+        }
+    }
+
+    /// Closes a tab, closing the active tab switches to the tab after it.
+    pub fn close(&mut self, index: usize) {
+        if index >= self.tabs.len() {
+            return;
+        }
+        self.tabs.remove(index);
+        if index < self.active || self.active >= self.tabs.len() {
+            self.active = self.active.saturating_sub(1);
+        }
+    }
+
+    pub fn activate(&mut self, index: usize) {
+        if index < self.tabs.len() {
+            self.active = index;
+        }
+    }
+
+    /// Moves the active tab by change, wrapping around at the ends.
+    pub fn cycle(&mut self, change: i32) {
+        let len = self.tabs.len() as i32;
+        if len > 0 {
+            self.active = (self.active as i32 + change).rem_euclid(len) as usize;
+        }
+    }
+
+    fn pinned_count(&self) -> usize {
+        self.tabs.iter().take_while(|t| t.pinned).count()
+    }
+
+    /// Moves a tab to a new index, pinned and unpinned tabs can only be reordered among themselves.
+    pub fn move_tab(&mut self, from: usize, to: usize) {
+        if from >= self.tabs.len() {
+            return;
+        }
+        let pinned = self.pinned_count();
+        let to = if self.tabs[from].pinned {
+            to.min(pinned - 1)
+        } else {
+            to.max(pinned).min(self.tabs.len() - 1)
+        };
+        let active = self.tabs[self.active].id;
+        let tab = self.tabs.remove(from);
+        self.tabs.insert(to, tab);
+        self.active = self.index_of(active).unwrap_or(0);
+    }
+
+    /// Pins or unpins a tab, moving it to the edge between the pinned and unpinned tabs.
+    pub fn toggle_pin(&mut self, index: usize) {
+        if index >= self.tabs.len() {
+            return;
+        }
+        let active = self.tabs[self.active].id;
+        let mut tab = self.tabs.remove(index);
+        tab.pinned = !tab.pinned;
+        let pinned = self.pinned_count();
+        self.tabs.insert(pinned, tab);
+        self.active = self.index_of(active).unwrap_or(0);
+    }
+
+    pub fn index_of(&self, id: usize) -> Option<usize> {
+        self.tabs.iter().position(|t| t.id == id)
+    }
+
+    pub fn get(&self, id: usize) -> Option<&TabInfo> {
+        self.tabs.iter().find(|t| t.id == id)
+    }
+
+    pub fn set_dirty(&mut self, id: usize, dirty: bool) {
+        if let Some(tab) = self.tabs.iter_mut().find(|t| t.id == id) {
+            tab.dirty = dirty;
+        }
+    }
+
+    pub fn set_encoding(&mut self, id: usize, encoding: Encoding, read_only: bool) {
+        if let Some(tab) = self.tabs.iter_mut().find(|t| t.id == id) {
+            tab.encoding = encoding;
+            tab.read_only = read_only;
+        }
+    }
+
+    pub fn active_id(&self) -> Option<usize> {
+        self.tabs.get(self.active).map(|t| t.id)
+    }
+}
+
+#[cfg(test)]
+fn tab_names(tabs: &Tabs) -> Vec<&str> {
+    tabs.tabs.iter().map(|t| t.name.as_str()).collect()
+}
+
+#[test]
+fn open_close_and_switch() {
+    let mut tabs = Tabs::default();
+    tabs.open("a", "", "rs");
+    tabs.open("b", "", "rs");
+    let c = tabs.open("c", "", "rs");
+    assert_eq!(tabs.active_id(), Some(c));
+
+    tabs.cycle(1);
+    assert_eq!(tabs.active, 0);
+    tabs.cycle(-1);
+    assert_eq!(tabs.active, 2);
+
+    // closing the active tab switches to the one before it if it was last
+    tabs.close(2);
+    assert_eq!(tab_names(&tabs), vec!["a", "b"]);
+    assert_eq!(tabs.active, 1);
+
+    // closing a tab before the active tab keeps the same tab active
+    tabs.close(0);
+    assert_eq!(tabs.active, 0);
+    assert_eq!(tab_names(&tabs), vec!["b"]);
+}
+
+#[test]
+fn reorder_and_pin() {
+    let mut tabs = Tabs::default();
+    tabs.open("a", "", "rs");
+    tabs.open("b", "", "rs");
+    tabs.open("c", "", "rs");
+    tabs.activate(0);
+
+    tabs.move_tab(0, 2);
+    assert_eq!(tab_names(&tabs), vec!["b", "c", "a"]);
+    assert_eq!(tabs.active, 2);
+
+    tabs.toggle_pin(1);
+    assert_eq!(tab_names(&tabs), vec!["c", "b", "a"]);
+    assert!(tabs.tabs[0].pinned);
+
+    // unpinned tabs can not move in front of pinned tabs
+    tabs.move_tab(2, 0);
+    assert_eq!(tab_names(&tabs), vec!["c", "a", "b"]);
+
+    tabs.toggle_pin(0);
+    assert_eq!(tab_names(&tabs), vec!["c", "a", "b"]);
+    assert!(!tabs.tabs[0].pinned);
+}
@@ -1,9 +1,112 @@
+use std::path::Path;
+use std::time::UNIX_EPOCH;
+
+use ropey::Rope;
 use syntect::highlighting::Color;
 
 pub fn color_to_string(c: Color) -> String {
     format!("rgba({}, {}, {}, {})", c.r, c.g, c.b, c.a as f32 / 255.0)
 }
 
+/// Blends two colors, an amount of 0 gives the first color and 1 gives the second.
+pub fn mix(a: Color, b: Color, amount: f32) -> Color {
+    let channel = |a: u8, b: u8| (a as f32 + (b as f32 - a as f32) * amount).round() as u8;
+    Color {
+        r: channel(a.r, b.r),
+        g: channel(a.g, b.g),
+        b: channel(a.b, b.b),
+        a: channel(a.a, b.a),
+    }
+}
+
+/// The chars that differ between two texts, as the start of the change and its end in the old and new text.
+pub fn changed_chars(old: &Rope, new: &Rope) -> (usize, usize, usize) {
+    let old_len = old.len_chars();
+    let new_len = new.len_chars();
+    let prefix = old
+        .chars()
+        .zip(new.chars())
+        .take_while(|(a, b)| a == b)
+        .count();
+    let suffix = old
+        .chars_at(old_len)
+        .reversed()
+        .zip(new.chars_at(new_len).reversed())
+        .take(old_len.min(new_len) - prefix)
+        .take_while(|(a, b)| a == b)
+        .count();
+    (prefix, old_len - suffix, new_len - suffix)
+}
+
+/// When a file was last modified in milliseconds since the epoch, or None if it does not exist.
+pub fn modified(path: &Path) -> Option<u64> {
+    let time = std::fs::metadata(path).ok()?.modified().ok()?;
+    Some(time.duration_since(UNIX_EPOCH).ok()?.as_millis() as u64)
+}
+
+// the size of the text area the desktop window opens with, the desktop renderer can not measure the window after it is resized
+pub const DEFAULT_COLUMNS: usize = 120;
+pub const DEFAULT_ROWS: usize = 40;
+// the size of a monospace character in pixels at the default font size
+#[cfg(not(feature = "term"))]
+pub const CHAR_WIDTH: f64 = 8.4;
+#[cfg(not(feature = "term"))]
+pub const LINE_HEIGHT: f64 = 19.0;
+
+/// The number of monospace characters that fit across the window, None on desktop where it can not be measured.
+pub fn viewport_columns() -> Option<usize> {
+    #[cfg(target_arch = "wasm32")]
+    {
+        web_sys::window()
+            .and_then(|w| w.inner_width().ok())
+            .and_then(|w| w.as_f64())
+            .map(|w| (w / CHAR_WIDTH) as usize)
+    }
+
+    #[cfg(all(not(target_arch = "wasm32"), feature = "term"))]
+    {
+        crossterm::terminal::size().ok().map(|(w, _)| w as usize)
+    }
+
+    #[cfg(all(not(target_arch = "wasm32"), not(feature = "term")))]
+    {
+        None
+    }
+}
+
+/// The number of lines of text that fit in the height of the window, desktop windows are assumed to keep the size they open with.
+pub fn viewport_rows() -> usize {
+    #[cfg(target_arch = "wasm32")]
+    {
+        web_sys::window()
+            .and_then(|w| w.inner_height().ok())
+            .and_then(|h| h.as_f64())
+            .map(|h| (h / LINE_HEIGHT) as usize)
+            .unwrap_or(DEFAULT_ROWS)
+    }
+
+    #[cfg(all(not(target_arch = "wasm32"), feature = "term"))]
+    {
+        crossterm::terminal::size()
+            .map(|(_, h)| h as usize)
+            .unwrap_or(DEFAULT_ROWS)
+    }
+
+    #[cfg(all(not(target_arch = "wasm32"), not(feature = "term")))]
+    {
+        DEFAULT_ROWS
+    }
+}
+
+/// Waits without blocking the renderer.
+pub async fn sleep(ms: u32) {
+    #[cfg(target_arch = "wasm32")]
+    gloo_timers::future::TimeoutFuture::new(ms).await;
+
+    #[cfg(not(target_arch = "wasm32"))]
+    tokio::time::sleep(std::time::Duration::from_millis(ms as u64)).await;
+}
+
 #[test]
 fn color_to_string_test() {
     assert_eq!(
@@ -25,3 +128,20 @@ fn color_to_string_test() {
         })
     );
 }
+
+#[test]
+fn mix_colors() {
+    let black = Color::BLACK;
+    let white = Color::WHITE;
+    assert_eq!(mix(black, white, 0.0), black);
+    assert_eq!(mix(black, white, 1.0), white);
+    assert_eq!(
+        mix(black, white, 0.5),
+        Color {
+            r: 128,
+            g: 128,
+            b: 128,
+            a: 255,
+        }
+    );
+}
@@ -0,0 +1,126 @@
+use ropey::Rope;
+
+use crate::diff::unified;
+use crate::utils::modified;
+use crate::workspace::{Edit, Workspace};
+
+/// The tabs whose file was modified, deleted or moved since it was last checked,
+/// with when it was modified or None if it is gone.
+pub fn changed_files(workspace: &Workspace) -> Vec<(usize, Option<u64>)> {
+    workspace
+        .tabs
+        .tabs
+        .iter()
+        .filter_map(|t| {
+            let time = modified(t.path.as_deref()?);
+            (workspace.disk_times.get(&t.id) != Some(&time)).then(|| (t.id, time))
+        })
+        .collect()
+}
+
+/// Reloads the buffers of changed files that have no unsaved changes,
+/// the others wait for the user to reload them, keep their changes or compare both.
+pub fn apply_changes(workspace: &mut Workspace, changes: Vec<(usize, Option<u64>)>) {
+    for (id, time) in changes {
+        let tab = match workspace.tabs.tabs.iter_mut().find(|t| t.id == id) {
+            Some(tab) => tab,
+            None => continue,
+        };
+        tab.missing = time.is_none();
+        let (path, dirty, encoding) = (tab.path.clone(), tab.dirty, tab.encoding);
+        // the file was just read to open it the first time it is seen
+        let seen = workspace.disk_times.insert(id, time);
+        if seen.is_none() || time.is_none() {
+            continue;
+        }
+        let text = match path.and_then(|p| std::fs::read(p).ok()) {
+            Some(bytes) => encoding.decode(&bytes).text,
+            None => continue,
+        };
+        let same = workspace
+            .buffers
+            .get(&id)
+            .map_or(false, |r| *r == text.as_str());
+        if dirty && !same {
+            workspace.conflicts.insert(id, text);
+        } else {
+            workspace.edits.push((id, Edit::Reload(text)));
+        }
+    }
+}
+
+/// Replaces the unsaved changes of a buffer with its file on disk.
+pub fn reload(workspace: &mut Workspace, tab: usize) {
+    if let Some(text) = workspace.conflicts.remove(&tab) {
+        workspace.edits.push((tab, Edit::Reload(text)));
+    }
+}
+
+/// Keeps the unsaved changes of a buffer, the change on disk is ignored.
+pub fn keep_changes(workspace: &mut Workspace, tab: usize) {
+    workspace.conflicts.remove(&tab);
+}
+
+/// Opens the differences between a file on disk and its buffer in a new tab.
+pub fn show_diff(workspace: &mut Workspace, tab: usize) {
+    let (name, disk, buffer) = match (
+        workspace.tabs.get(tab),
+        workspace.conflicts.get(&tab),
+        workspace.buffers.get(&tab),
+    ) {
+        (Some(info), Some(disk), Some(buffer)) => (info.name.clone(), disk, buffer),
+        _ => return,
+    };
+    let text = unified(
+        &format!("{} (on disk)", name),
+        &format!("{} (unsaved)", name),
+        &Rope::from_str(disk),
+        buffer,
+    );
+    workspace.open(&format!("{}.diff", name), &text, "diff");
+}
+
+#[test]
+fn watch_files() {
+    let dir = std::env::temp_dir().join(format!("editor-watch-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let (a, b) = (dir.join("a.txt"), dir.join("b.txt"));
+    std::fs::write(&a, "a\n").unwrap();
+    std::fs::write(&b, "b\n").unwrap();
+    let mut workspace = Workspace::default();
+    let a_id = workspace.open_path(&a).unwrap();
+    let b_id = workspace.open_path(&b).unwrap();
+    workspace.buffers.insert(b_id, Rope::from_str("mine\n"));
+    workspace.tabs.set_dirty(b_id, true);
+    let changes = changed_files(&workspace);
+    assert_eq!(changes.len(), 2);
+    apply_changes(&mut workspace, changes);
+    assert!(changed_files(&workspace).is_empty());
+    assert!(workspace.edits.is_empty());
+
+    // pretend both were last seen earlier
+    workspace.disk_times.insert(a_id, Some(0));
+    workspace.disk_times.insert(b_id, Some(0));
+    std::fs::write(&a, "changed\n").unwrap();
+    std::fs::write(&b, "theirs\n").unwrap();
+    let changes = changed_files(&workspace);
+    apply_changes(&mut workspace, changes);
+    assert_eq!(
+        workspace.edits,
+        vec![(a_id, Edit::Reload("changed\n".to_string()))]
+    );
+    assert_eq!(workspace.conflicts.get(&b_id).unwrap(), "theirs\n");
+    show_diff(&mut workspace, b_id);
+    assert_eq!(
+        workspace.tabs.tabs.last().unwrap().text,
+        "--- b.txt (on disk)\n+++ b.txt (unsaved)\n@@ -1,1 +1,1 @@\n-theirs\n+mine\n"
+    );
+    keep_changes(&mut workspace, b_id);
+    assert!(workspace.conflicts.is_empty());
+
+    std::fs::remove_file(&a).unwrap();
+    let changes = changed_files(&workspace);
+    apply_changes(&mut workspace, changes);
+    assert!(workspace.tabs.get(a_id).unwrap().missing);
+    std::fs::remove_dir_all(&dir).unwrap();
+}
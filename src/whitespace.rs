@@ -0,0 +1,184 @@
+use ropey::Rope;
+
+pub const SPACE: char = '·';
+pub const TAB: char = '→';
+pub const LINE_ENDING: char = '¬';
+pub const GUIDE: char = '│';
+
+/// What a piece of rendered text is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Kind {
+    Text,
+    Whitespace,
+    Trailing,
+    Guide,
+    ActiveGuide,
+}
+
+/// The visual column of each char in the line, tabs are expanded to the next multiple of the indent width.
+fn visual_columns(line: &str, unit: usize) -> Vec<usize> {
+    let mut col = 0;
+    line.chars()
+        .map(|c| {
+            let start = col;
+            col += if c == '\t' { unit - col % unit } else { 1 };
+            start
+        })
+        .collect()
+}
+
+fn indent_level(line: &str, unit: usize) -> Option<usize> {
+    let leading = line.chars().take_while(|c| *c == ' ' || *c == '\t').count();
+    if line
+        .chars()
+        .nth(leading)
+        .map_or(true, |c| c == '\n' || c == '\r')
+    {
+        return None;
+    }
+    let columns = visual_columns(line, unit);
+    Some(columns.get(leading).copied().unwrap_or(0) / unit)
+}
+
+/// The guide highlighted for a cursor on the row, and the rows it spans.
+/// The guide belongs to the innermost block the row is in.
+pub fn active_guide(rope: &Rope, row: usize, unit: usize) -> Option<(usize, usize, usize)> {
+    let level_of = |row: usize| indent_level(&rope.line(row).to_string(), unit);
+    let level = level_of(row)?.checked_sub(1)?;
+    let inside = |row: usize| level_of(row).map_or(true, |l| l > level);
+    let mut start = row;
+    while start > 0 && inside(start - 1) {
+        start -= 1;
+    }
+    let mut end = row;
+    while end + 1 < rope.len_lines() && inside(end + 1) {
+        end += 1;
+    }
+    Some((level, start, end))
+}
+
+/// How the whitespace of a line is drawn.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LineWhitespace {
+    unit: usize,
+    show: bool,
+    columns: Vec<usize>,
+    // the indent level of the guide drawn at each char of the indent
+    guides: Vec<Option<usize>>,
+    active_guide: Option<usize>,
+    // chars from here on are trailing whitespace
+    trailing_start: usize,
+}
+
+impl LineWhitespace {
+    pub fn new(
+        line: &str,
+        unit: usize,
+        show: bool,
+        indent_guides: bool,
+        active_guide: Option<usize>,
+    ) -> Self {
+        let unit = unit.max(1);
+        let line = line.trim_end_matches(&['\n', '\r'][..]);
+        let columns = visual_columns(line, unit);
+        let leading = line.chars().take_while(|c| c.is_whitespace()).count();
+        let guides = columns
+            .iter()
+            .take(leading)
+            .map(|col| (indent_guides && col % unit == 0).then(|| col / unit))
+            .collect();
+        let trailing_start = line.trim_end().chars().count();
+        Self {
+            unit,
+            show,
+            columns,
+            guides,
+            active_guide,
+            trailing_start,
+        }
+    }
+
+    /// Splits text starting at the column of the line into pieces with the whitespace replaced.
+    pub fn render(&self, text: &str, col: usize) -> Vec<(Kind, String)> {
+        let mut pieces: Vec<(Kind, String)> = Vec::new();
+        for (i, c) in text.chars().enumerate() {
+            let col = col + i;
+            let width = if c == '\t' {
+                let visual = self.columns.get(col).copied().unwrap_or(0);
+                self.unit - visual % self.unit
+            } else {
+                1
+            };
+            let (kind, first) = match self.guides.get(col).copied().flatten() {
+                Some(level) if self.active_guide == Some(level) => (Kind::ActiveGuide, GUIDE),
+                Some(_) => (Kind::Guide, GUIDE),
+                None if !c.is_whitespace() => (Kind::Text, c),
+                None if !self.show && c == '\t' => (Kind::Text, ' '),
+                None if !self.show => (Kind::Text, c),
+                None => {
+                    let kind = if col >= self.trailing_start {
+                        Kind::Trailing
+                    } else {
+                        Kind::Whitespace
+                    };
+                    (kind, if c == '\t' { TAB } else { SPACE })
+                }
+            };
+            let mut rendered = first.to_string();
+            rendered.push_str(&" ".repeat(width - 1));
+            match pieces.last_mut() {
+                Some((last, piece)) if *last == kind => piece.push_str(&rendered),
+                _ => pieces.push((kind, rendered)),
+            }
+        }
+        pieces
+    }
+}
+
+#[test]
+fn render_whitespace() {
+    let line = LineWhitespace::new("\tlet a;  \n", 4, true, false, None);
+    assert_eq!(
+        line.render("\tlet a;  ", 0),
+        vec![
+            (Kind::Whitespace, "→   ".to_string()),
+            (Kind::Text, "let".to_string()),
+            (Kind::Whitespace, "·".to_string()),
+            (Kind::Text, "a;".to_string()),
+            (Kind::Trailing, "··".to_string()),
+        ]
+    );
+    // pieces are rendered from their column in the line
+    assert_eq!(
+        line.render(" a", 4),
+        vec![
+            (Kind::Whitespace, "·".to_string()),
+            (Kind::Text, "a".to_string()),
+        ]
+    );
+
+    let hidden = LineWhitespace::new("\ta ", 4, false, false, None);
+    assert_eq!(
+        hidden.render("\ta ", 0),
+        vec![(Kind::Text, "    a ".to_string())]
+    );
+}
+
+#[test]
+fn indent_guides() {
+    let line = LineWhitespace::new("        a", 4, false, true, Some(1));
+    assert_eq!(
+        line.render("        a", 0),
+        vec![
+            (Kind::Guide, "│".to_string()),
+            (Kind::Text, "   ".to_string()),
+            (Kind::ActiveGuide, "│".to_string()),
+            (Kind::Text, "   a".to_string()),
+        ]
+    );
+
+    let rope = Rope::from_str("fn a() {\n    if b {\n        c();\n\n        d();\n    }\n}");
+    assert_eq!(active_guide(&rope, 2, 4), Some((1, 2, 4)));
+    assert_eq!(active_guide(&rope, 1, 4), Some((0, 1, 5)));
+    assert_eq!(active_guide(&rope, 0, 4), None);
+}
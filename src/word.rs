@@ -0,0 +1,86 @@
+use std::ops::Range;
+
+use ropey::Rope;
+
+use crate::cursor::Pos;
+
+pub fn is_word_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+/// The start of the word chars right before a char index, the index itself if there are none.
+pub fn word_start(idx: usize, rope: &Rope) -> usize {
+    idx - rope
+        .chars_at(idx)
+        .reversed()
+        .take_while(|c| is_word_char(*c))
+        .count()
+}
+
+/// The char range of the identifier touching the position.
+pub fn word_at(pos: &Pos, rope: &Rope) -> Option<(usize, usize)> {
+    let idx = pos.idx(rope);
+    let start = word_start(idx, rope);
+    let end = idx + rope.chars_at(idx).take_while(|c| is_word_char(*c)).count();
+    // numbers are not identifiers
+    let first = rope.get_char(start)?;
+    (end > start && !first.is_numeric()).then(|| (start, end))
+}
+
+/// The char ranges of every whole word occurrence of the word in a range of lines.
+pub fn occurrences(word: &str, rope: &Rope, rows: Range<usize>) -> Vec<(usize, usize)> {
+    let word: Vec<char> = word.chars().collect();
+    let start = rope.line_to_char(rows.start.min(rope.len_lines()));
+    let end = rope.line_to_char(rows.end.min(rope.len_lines()));
+    let mut found = Vec::new();
+    let mut previous = None;
+    let mut matched = 0;
+    // lines start after a line break, so words do not continue across the start of the range
+    for (i, c) in rope.slice(start..end).chars().enumerate() {
+        let i = start + i;
+        if matched == word.len() {
+            if !is_word_char(c) {
+                found.push((i - word.len(), i));
+            }
+            matched = 0;
+        }
+        if matched > 0 && word[matched] == c {
+            matched += 1;
+        } else if !previous.map_or(false, is_word_char) && word.first() == Some(&c) {
+            matched = 1;
+        } else {
+            matched = 0;
+        }
+        previous = Some(c);
+    }
+    // a word at the end of the range ends there if the text does
+    let ends = rope.get_char(end).map_or(true, |c| !is_word_char(c));
+    if matched == word.len() && matched > 0 && ends {
+        found.push((end - word.len(), end));
+    }
+    found
+}
+
+#[test]
+fn word_under_cursor() {
+    let rope = Rope::from_str("let foo_bar = 10;");
+
+    assert_eq!(word_at(&Pos::new(5, 0), &rope), Some((4, 11)));
+    assert_eq!(word_at(&Pos::new(11, 0), &rope), Some((4, 11)));
+    assert_eq!(word_at(&Pos::new(12, 0), &rope), None);
+    assert_eq!(word_at(&Pos::new(15, 0), &rope), None);
+}
+
+#[test]
+fn word_occurrences() {
+    let rope = Rope::from_str("a ab a\nba a_ a");
+
+    assert_eq!(
+        occurrences("a", &rope, 0..2),
+        vec![(0, 1), (5, 6), (13, 14)]
+    );
+    assert_eq!(occurrences("ab", &rope, 0..2), vec![(2, 4)]);
+    // only the lines in the range are searched
+    assert_eq!(occurrences("a", &rope, 1..2), vec![(13, 14)]);
+    assert_eq!(occurrences("a", &rope, 0..1), vec![(0, 1), (5, 6)]);
+}
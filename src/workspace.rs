@@ -0,0 +1,748 @@
+use std::cell::Cell;
+use std::collections::{BTreeMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+
+use dioxus::events::KeyboardData;
+use lsp_types::TextEdit;
+use ropey::Rope;
+use serde::{Deserialize, Serialize};
+
+use crate::commands::{CodeRequest, Palette};
+use crate::completion::{Completion, ServerCompletion, WordCache};
+use crate::cursor::Cursor;
+#[cfg(test)]
+use crate::cursor::Pos;
+use crate::cursors::Cursors;
+use crate::diagnostics::Diagnostics;
+use crate::encoding::{decode_file, Decoded};
+use crate::explorer::Explorer;
+use crate::finder::FileIndex;
+use crate::fold::Folds;
+use crate::lsp::{apply_edits, LanguageServers};
+use crate::macros::Step;
+use crate::recovery::{Journal, Swap};
+use crate::search::{Replacement, Search};
+use crate::session::{Session, SessionTab, SessionView};
+use crate::snippet::SnippetSession;
+use crate::tabs::{TabInfo, Tabs};
+use crate::PS;
+
+// the number of recently used commands remembered for the command palette
+const RECENT_COMMANDS: usize = 10;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Direction {
+    // side by side
+    Horizontal,
+    // stacked on top of each other
+    Vertical,
+}
+
+/// How the panes are split.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Node {
+    Pane(usize),
+    Split(Direction, Vec<Node>),
+}
+
+impl Node {
+    fn panes(&self, panes: &mut Vec<usize>) {
+        match self {
+            Node::Pane(id) => panes.push(*id),
+            Node::Split(_, children) => children.iter().for_each(|c| c.panes(panes)),
+        }
+    }
+
+    fn rects(&self, rect: Rect, rects: &mut Vec<(usize, Rect)>) {
+        match self {
+            Node::Pane(id) => rects.push((*id, rect)),
+            Node::Split(direction, children) => {
+                let n = children.len() as f32;
+                for (i, child) in children.iter().enumerate() {
+                    let i = i as f32;
+                    let child_rect = match direction {
+                        Direction::Horizontal => Rect {
+                            x: rect.x + rect.width * i / n,
+                            width: rect.width / n,
+                            ..rect
+                        },
+                        Direction::Vertical => Rect {
+                            y: rect.y + rect.height * i / n,
+                            height: rect.height / n,
+                            ..rect
+                        },
+                    };
+                    child.rects(child_rect, rects);
+                }
+            }
+        }
+    }
+
+    /// Adds a pane next to another pane, splitting in the direction if they are not already.
+    fn split(&mut self, pane: usize, new: usize, direction: Direction) -> bool {
+        match self {
+            Node::Pane(id) if *id == pane => {
+                *self = Node::Split(direction, vec![Node::Pane(pane), Node::Pane(new)]);
+                true
+            }
+            Node::Pane(_) => false,
+            Node::Split(split_direction, children) => {
+                if *split_direction == direction {
+                    if let Some(i) = children.iter().position(|c| *c == Node::Pane(pane)) {
+                        children.insert(i + 1, Node::Pane(new));
+                        return true;
+                    }
+                }
+                children.iter_mut().any(|c| c.split(pane, new, direction))
+            }
+        }
+    }
+
+    /// Removes a pane, splits with a single pane left are replaced by that pane.
+    fn remove(&mut self, pane: usize) {
+        if let Node::Split(_, children) = self {
+            children.retain(|c| *c != Node::Pane(pane));
+            children.iter_mut().for_each(|c| c.remove(pane));
+            if children.len() == 1 {
+                *self = children.remove(0);
+            }
+        }
+    }
+}
+
+/// The position and size of a pane in percent of the editor.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Rect {
+    pub x: f32,
+    pub y: f32,
+    pub width: f32,
+    pub height: f32,
+}
+
+impl Rect {
+    fn center(&self) -> (f32, f32) {
+        (self.x + self.width / 2.0, self.y + self.height / 2.0)
+    }
+}
+
+/// A part of the editor that shows one tab at a time.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Pane {
+    pub id: usize,
+    pub tab: Option<usize>,
+    // every tab shown in the pane, each keeps its own cursors and scroll position
+    pub opened: Vec<usize>,
+}
+
+impl Pane {
+    fn show(&mut self, tab: Option<usize>) {
+        self.tab = tab;
+        if let Some(tab) = tab {
+            if !self.opened.contains(&tab) {
+                self.opened.push(tab);
+            }
+        }
+    }
+}
+
+/// What a view of a tab keeps when it is not shown, and between sessions.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ViewState {
+    pub cursors: Cursors,
+    pub folds: Folds,
+    pub scroll: f32,
+}
+
+/// A change to the buffer of a tab made from outside of its views.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Edit {
+    Replace(Replacement),
+    Lsp(Vec<TextEdit>),
+    // replaces the whole text, like recovered changes
+    Text(String),
+    // the text of the file changed on disk, the buffer is saved after it
+    Reload(String),
+}
+
+impl Edit {
+    /// The text after the edit, or None if it could not be applied.
+    pub fn apply(&self, text: &str) -> Option<String> {
+        match self {
+            Edit::Replace(replacement) => replacement.apply(text).ok(),
+            Edit::Lsp(edits) => {
+                let mut rope = Rope::from_str(text);
+                apply_edits(&mut rope, edits);
+                Some(rope.to_string())
+            }
+            Edit::Text(text) | Edit::Reload(text) => Some(text.clone()),
+        }
+    }
+}
+
+/// The open tabs and the panes they are shown in.
+/// The active tab in the tab bar is always the tab of the focused pane.
+#[derive(Debug, Clone)]
+pub struct Workspace {
+    pub tabs: Tabs,
+    pub panes: Vec<Pane>,
+    pub focused: usize,
+    layout: Node,
+    next_pane: usize,
+    // keys sent to a pane that has keyboard focus, but is not the focused pane
+    forwarded: Vec<KeyboardData>,
+    // the command palette is shown in the focused pane
+    pub palette: Option<Palette>,
+    // titles of the commands run from the palette, the most recent first
+    pub recent_commands: Vec<String>,
+    // the files of the directory the editor was opened on
+    pub project: Option<FileIndex>,
+    // a tab and the cursor its view in the focused pane jumps to
+    pub goto: Option<(usize, Cursor)>,
+    pub search_panel: bool,
+    // the last search in the project, kept after it finishes to show its results
+    pub search: Option<Search>,
+    // edits waiting to be applied to the buffers of tabs
+    pub edits: Vec<(usize, Edit)>,
+    // the file tree of the project
+    pub explorer: Option<Explorer>,
+    pub language_servers: LanguageServers,
+    // shown over the focused pane until the next key press, like hover information
+    pub message: Option<String>,
+    // a code command waiting for the answer of a language server, like go to definition
+    pub code_request: Option<CodeRequest>,
+    // the locations listed in the palette, like references, with a description of each
+    pub locations: Vec<(String, PathBuf, Cursor)>,
+    // the completion popup shown in the focused pane
+    pub completion: Option<Completion>,
+    // completions asked from a language server for the popup, added to it once they are received
+    pub completion_request: Option<ServerCompletion>,
+    // the snippet whose tab stops are being filled in
+    pub snippet: Option<SnippetSession>,
+    // the text of every open buffer, kept up to date without rendering again
+    pub buffers: BTreeMap<usize, Rope>,
+    // the words of every open buffer for completions
+    pub words: WordCache,
+    // the diagnostics of each tab
+    pub diagnostics: BTreeMap<usize, Diagnostics>,
+    pub problems_panel: bool,
+    // the steps of the macro being recorded
+    pub recording: Option<Vec<Step>>,
+    // the macro that is replayed
+    pub last_macro: Vec<Step>,
+    // the state of the view of each tab in each pane, by pane and tab
+    pub views: BTreeMap<(usize, usize), ViewState>,
+    // the edits to each buffer with unsaved changes, written to its swap file
+    pub journals: BTreeMap<usize, Journal>,
+    // buffers with unsaved changes left behind by a run that did not exit cleanly
+    pub recovered: Vec<Swap>,
+    // when the file of each tab was modified the last time it was checked, None if it was gone
+    pub disk_times: BTreeMap<usize, Option<u64>>,
+    // the text on disk of files that changed while their buffer has unsaved changes
+    pub conflicts: BTreeMap<usize, String>,
+    // set when the workspace or a buffer changed since the session and swap files were saved
+    pub changed: Rc<Cell<bool>>,
+}
+
+impl Default for Workspace {
+    fn default() -> Self {
+        Self {
+            tabs: Tabs::default(),
+            panes: vec![Pane {
+                id: 0,
+                tab: None,
+                opened: Vec::new(),
+            }],
+            focused: 0,
+            layout: Node::Pane(0),
+            next_pane: 1,
+            forwarded: Vec::new(),
+            palette: None,
+            recent_commands: Vec::new(),
+            project: None,
+            goto: None,
+            search_panel: false,
+            search: None,
+            edits: Vec::new(),
+            explorer: None,
+            language_servers: LanguageServers::default(),
+            message: None,
+            code_request: None,
+            locations: Vec::new(),
+            completion: None,
+            completion_request: None,
+            snippet: None,
+            buffers: BTreeMap::new(),
+            words: WordCache::default(),
+            diagnostics: BTreeMap::new(),
+            problems_panel: false,
+            recording: None,
+            last_macro: Vec::new(),
+            views: BTreeMap::new(),
+            journals: BTreeMap::new(),
+            recovered: Vec::new(),
+            disk_times: BTreeMap::new(),
+            conflicts: BTreeMap::new(),
+            changed: Rc::new(Cell::new(false)),
+        }
+    }
+}
+
+impl Workspace {
+    pub fn pane(&self, id: usize) -> Option<&Pane> {
+        self.panes.iter().find(|p| p.id == id)
+    }
+
+    fn focused_pane(&mut self) -> &mut Pane {
+        let focused = self.focused;
+        self.panes.iter_mut().find(|p| p.id == focused).unwrap()
+    }
+
+    fn show_active(&mut self) {
+        let tab = self.tabs.active_id();
+        self.focused_pane().show(tab);
+    }
+
+    /// Opens a new tab in the focused pane.
+    pub fn open(&mut self, name: &str, text: &str, extension: &str) -> usize {
+        let id = self.tabs.open(name, text, extension);
+        self.show_active();
+        id
+    }
+
+    /// Opens a file in the focused pane, switching to its tab if it is already open.
+    pub fn open_file(&mut self, path: &Path, text: &str) -> usize {
+        if let Some(index) = self
+            .tabs
+            .tabs
+            .iter()
+            .position(|t| t.path.as_deref() == Some(path))
+        {
+            self.activate_tab(index);
+            return self.tabs.tabs[index].id;
+        }
+        let name = path
+            .file_name()
+            .unwrap_or(path.as_os_str())
+            .to_string_lossy();
+        let extension = path
+            .extension()
+            .map(|e| e.to_string_lossy())
+            .unwrap_or_default();
+        let id = self.open(&name, text, &extension);
+        let index = self.tabs.active;
+        self.tabs.tabs[index].path = Some(path.to_path_buf());
+        id
+    }
+
+    /// Opens the decoded text of a file, files with invalid sequences are opened read-only.
+    pub fn open_decoded(&mut self, path: &Path, decoded: &Decoded) -> usize {
+        let open = self
+            .tabs
+            .tabs
+            .iter()
+            .any(|t| t.path.as_deref() == Some(path));
+        let id = self.open_file(path, &decoded.text);
+        if !open {
+            self.tabs.set_encoding(id, decoded.encoding, !decoded.valid);
+            if !decoded.valid {
+                self.message = Some(format!(
+                    "{} has sequences that are not valid {} and was opened read-only",
+                    path.display(),
+                    decoded.encoding
+                ));
+            }
+        }
+        id
+    }
+
+    /// Reads a file and opens it, files that can not be read are not opened.
+    pub fn open_path(&mut self, path: &Path) -> Option<usize> {
+        let bytes = std::fs::read(path).ok()?;
+        Some(self.open_decoded(path, &decode_file(&bytes)))
+    }
+
+    /// Keeps tabs of files in a renamed or moved file or directory pointing to them.
+    pub fn rename_path(&mut self, from: &Path, to: &Path) {
+        for tab in &mut self.tabs.tabs {
+            let path = match tab.path.as_ref().and_then(|p| p.strip_prefix(from).ok()) {
+                Some(rest) if rest.as_os_str().is_empty() => to.to_path_buf(),
+                Some(rest) => to.join(rest),
+                None => continue,
+            };
+            tab.name = path
+                .file_name()
+                .unwrap_or(path.as_os_str())
+                .to_string_lossy()
+                .into_owned();
+            tab.path = Some(path);
+        }
+    }
+
+    pub fn activate_tab(&mut self, index: usize) {
+        self.tabs.activate(index);
+        self.show_active();
+    }
+
+    pub fn cycle_tab(&mut self, change: i32) {
+        self.tabs.cycle(change);
+        self.show_active();
+    }
+
+    /// Closes a tab, panes showing it switch to the active tab.
+    pub fn close_tab(&mut self, index: usize) {
+        let id = match self.tabs.tabs.get(index) {
+            Some(tab) => tab.id,
+            None => return,
+        };
+        self.tabs.close(index);
+        self.diagnostics.remove(&id);
+        self.buffers.remove(&id);
+        self.words.remove(id);
+        self.journals.remove(&id);
+        self.disk_times.remove(&id);
+        self.conflicts.remove(&id);
+        self.snippet = self.snippet.take().filter(|s| s.tab != id);
+        self.views.retain(|(_, tab), _| *tab != id);
+        // language servers no open file needs anymore are shut down
+        let used: HashSet<_> = self
+            .tabs
+            .tabs
+            .iter()
+            .filter_map(|t| PS.find_syntax_by_extension(&t.extension))
+            .map(|s| s.name.clone())
+            .collect();
+        self.language_servers.stop_unused(&used);
+        let active = self.tabs.active_id();
+        for pane in &mut self.panes {
+            pane.opened.retain(|t| *t != id);
+            if pane.tab == Some(id) {
+                pane.show(active);
+            }
+        }
+    }
+
+    /// Closes the completion popup, completions still asked from a language server are not shown.
+    pub fn close_completion(&mut self) {
+        self.completion = None;
+        self.completion_request = None;
+    }
+
+    pub fn focus(&mut self, pane: usize) {
+        if let Some(tab) = self.pane(pane).map(|p| p.tab) {
+            self.focused = pane;
+            self.close_completion();
+            if let Some(explorer) = &mut self.explorer {
+                explorer.focused = false;
+            }
+            if let Some(index) = tab.and_then(|t| self.tabs.index_of(t)) {
+                self.tabs.activate(index);
+            }
+        }
+    }
+
+    /// Focuses the closest pane in a direction, dx and dy are -1, 0 or 1.
+    pub fn focus_direction(&mut self, dx: f32, dy: f32) {
+        let rects = self.rects();
+        let (x, y) = match rects.iter().find(|(id, _)| *id == self.focused) {
+            Some((_, rect)) => rect.center(),
+            None => return,
+        };
+        let closest = rects
+            .iter()
+            .filter(|(id, _)| *id != self.focused)
+            .map(|(id, rect)| {
+                let (cx, cy) = rect.center();
+                (*id, cx - x, cy - y)
+            })
+            .filter(|(_, ox, oy)| ox * dx + oy * dy > 0.0)
+            .min_by(|(_, ax, ay), (_, bx, by)| {
+                (ax.abs() + ay.abs())
+                    .partial_cmp(&(bx.abs() + by.abs()))
+                    .unwrap()
+            });
+        if let Some((id, _, _)) = closest {
+            self.focus(id);
+        }
+    }
+
+    /// Splits the focused pane, the new pane shows the same tab and is focused.
+    pub fn split(&mut self, direction: Direction) {
+        let id = self.next_pane;
+        self.next_pane += 1;
+        let tab = self.focused_pane().tab;
+        self.layout.split(self.focused, id, direction);
+        let mut pane = Pane {
+            id,
+            tab: None,
+            opened: Vec::new(),
+        };
+        pane.show(tab);
+        self.panes.push(pane);
+        self.focus(id);
+    }
+
+    /// Closes the focused pane unless it is the last pane.
+    pub fn close_pane(&mut self) {
+        if self.panes.len() == 1 {
+            return;
+        }
+        let closed = self.focused;
+        self.layout.remove(closed);
+        self.panes.retain(|p| p.id != closed);
+        let mut panes = Vec::new();
+        self.layout.panes(&mut panes);
+        self.focus(panes[0]);
+    }
+
+    /// The position of every pane in percent of the editor.
+    pub fn rects(&self) -> Vec<(usize, Rect)> {
+        let mut rects = Vec::new();
+        let full = Rect {
+            x: 0.0,
+            y: 0.0,
+            width: 100.0,
+            height: 100.0,
+        };
+        self.layout.rects(full, &mut rects);
+        rects
+    }
+
+    pub fn forward(&mut self, key: KeyboardData) {
+        self.forwarded.push(key);
+    }
+
+    pub fn has_forwarded(&self) -> bool {
+        !self.forwarded.is_empty()
+    }
+
+    pub fn take_forwarded(&mut self) -> Vec<KeyboardData> {
+        std::mem::take(&mut self.forwarded)
+    }
+
+    /// The open tabs and panes, unsaved changes are kept with the tabs.
+    pub fn session(&self) -> Session {
+        let tabs = self
+            .tabs
+            .tabs
+            .iter()
+            .map(|t| {
+                // saved files are read again when the session is restored
+                let text = (t.path.is_none() || t.dirty).then(|| match self.buffers.get(&t.id) {
+                    Some(rope) => rope.to_string(),
+                    None => t.unsaved.clone().unwrap_or_else(|| t.text.clone()),
+                });
+                SessionTab {
+                    id: t.id,
+                    name: t.name.clone(),
+                    extension: t.extension.clone(),
+                    path: t.path.clone(),
+                    pinned: t.pinned,
+                    dirty: t.dirty,
+                    encoding: t.encoding,
+                    text,
+                }
+            })
+            .collect();
+        let views = self
+            .views
+            .iter()
+            .filter(|((pane, tab), _)| self.pane(*pane).map_or(false, |p| p.opened.contains(tab)))
+            .map(|((pane, tab), state)| SessionView {
+                pane: *pane,
+                tab: *tab,
+                state: state.clone(),
+            })
+            .collect();
+        Session {
+            tabs,
+            active: self.tabs.active,
+            panes: self.panes.clone(),
+            layout: self.layout.clone(),
+            focused: self.focused,
+            views,
+        }
+    }
+
+    /// Opens the tabs and panes of a session, files are read again and unsaved changes are applied to them.
+    pub fn restore(&mut self, session: Session) {
+        let tabs = session
+            .tabs
+            .into_iter()
+            .map(|t| {
+                // files are decoded in the encoding they were opened with
+                let decoded = t
+                    .path
+                    .as_ref()
+                    .and_then(|path| std::fs::read(path).ok())
+                    .map(|bytes| t.encoding.decode(&bytes));
+                let saved = match (&decoded, &t.text) {
+                    (Some(decoded), _) => decoded.text.clone(),
+                    (None, Some(text)) if t.path.is_none() && !t.dirty => text.clone(),
+                    (None, _) => String::new(),
+                };
+                let unsaved = t.text.filter(|text| *text != saved);
+                TabInfo {
+                    id: t.id,
+                    name: t.name,
+                    text: saved,
+                    dirty: unsaved.is_some(),
+                    unsaved,
+                    extension: t.extension,
+                    path: t.path,
+                    pinned: t.pinned,
+                    missing: false,
+                    encoding: t.encoding,
+                    read_only: decoded.map_or(false, |d| !d.valid),
+                }
+            })
+            .collect();
+        self.tabs = Tabs::restore(tabs, session.active);
+        self.next_pane = session.panes.iter().map(|p| p.id + 1).max().unwrap_or(0);
+        self.panes = session.panes;
+        self.layout = session.layout;
+        self.focused = session.focused;
+        self.views = session
+            .views
+            .into_iter()
+            .map(|v| ((v.pane, v.tab), v.state))
+            .collect();
+    }
+
+    pub fn record_command(&mut self, title: &str) {
+        self.recent_commands.retain(|t| t != title);
+        self.recent_commands.insert(0, title.to_string());
+        self.recent_commands.truncate(RECENT_COMMANDS);
+    }
+}
+
+#[test]
+fn split_panes() {
+    let mut workspace = Workspace::default();
+    let a = workspace.open("a", "", "rs");
+    workspace.split(Direction::Horizontal);
+    workspace.split(Direction::Vertical);
+    // splitting in the same direction adds a sibling
+    workspace.focus(0);
+    workspace.split(Direction::Horizontal);
+
+    assert_eq!(
+        workspace.layout,
+        Node::Split(
+            Direction::Horizontal,
+            vec![
+                Node::Pane(0),
+                Node::Pane(3),
+                Node::Split(Direction::Vertical, vec![Node::Pane(1), Node::Pane(2)]),
+            ]
+        )
+    );
+    assert!(workspace.panes.iter().all(|p| p.tab == Some(a)));
+    let rects = workspace.rects();
+    assert_eq!(rects[2].0, 1);
+    assert!((rects[2].1.x - 200.0 / 3.0).abs() < 0.01);
+    assert_eq!(rects[3].1.y, 50.0);
+
+    workspace.focus(1);
+    workspace.close_pane();
+    assert_eq!(
+        workspace.layout,
+        Node::Split(
+            Direction::Horizontal,
+            vec![Node::Pane(0), Node::Pane(3), Node::Pane(2)]
+        )
+    );
+    assert_eq!(workspace.focused, 0);
+}
+
+#[test]
+fn panes_show_tabs() {
+    let mut workspace = Workspace::default();
+    let a = workspace.open("a", "", "rs");
+    workspace.split(Direction::Horizontal);
+    let b = workspace.open("b", "", "rs");
+    assert_eq!(workspace.pane(1).unwrap().tab, Some(b));
+    assert_eq!(workspace.pane(1).unwrap().opened, vec![a, b]);
+
+    // the tab bar follows the focused pane
+    workspace.focus_direction(-1.0, 0.0);
+    assert_eq!(workspace.focused, 0);
+    assert_eq!(workspace.tabs.active_id(), Some(a));
+    workspace.focus_direction(-1.0, 0.0);
+    assert_eq!(workspace.focused, 0);
+
+    workspace.close_tab(0);
+    assert_eq!(workspace.pane(0).unwrap().tab, Some(b));
+    assert_eq!(workspace.pane(1).unwrap().opened, vec![b]);
+}
+
+#[test]
+fn restore_session() {
+    let mut workspace = Workspace::default();
+    let a = workspace.open("a", "fn a() {}", "rs");
+    workspace.split(Direction::Vertical);
+    let b = workspace.open("b", "", "txt");
+    workspace.tabs.toggle_pin(1);
+    // b has changes that are not saved
+    workspace.buffers.insert(b, Rope::from_str("unsaved"));
+    workspace.tabs.set_dirty(b, true);
+    let view = ViewState {
+        cursors: Cursors::new(vec![Cursor::from_start(Pos::new(3, 0))]),
+        folds: Folds::default(),
+        scroll: 2.0,
+    };
+    workspace.views.insert((1, a), view.clone());
+    // views of panes that are closed are not kept
+    workspace.views.insert((5, a), ViewState::default());
+
+    let json = serde_json::to_string(&workspace.session()).unwrap();
+    let mut restored = Workspace::default();
+    restored.restore(serde_json::from_str(&json).unwrap());
+    assert_eq!(restored.layout, workspace.layout);
+    assert_eq!(restored.panes, workspace.panes);
+    assert_eq!(restored.focused, 1);
+    assert_eq!(restored.tabs.active_id(), Some(b));
+    let restored_b = &restored.tabs.tabs[0];
+    assert!(restored_b.pinned && restored_b.dirty);
+    assert_eq!(restored_b.text, "");
+    assert_eq!(restored_b.unsaved.as_deref(), Some("unsaved"));
+    let restored_a = restored.tabs.get(a).unwrap();
+    assert_eq!(
+        (restored_a.text.as_str(), restored_a.dirty),
+        ("fn a() {}", false)
+    );
+    assert_eq!(
+        restored.views.clone().into_iter().collect::<Vec<_>>(),
+        vec![((1, a), view)]
+    );
+    // new tabs and panes do not reuse the restored ids
+    assert_eq!(restored.open("c", "", "txt"), b + 1);
+    restored.split(Direction::Horizontal);
+    assert_eq!(restored.focused, 2);
+}
+
+#[test]
+fn open_files_once() {
+    let mut workspace = Workspace::default();
+    let main = workspace.open_file(Path::new("/project/src/main.rs"), "fn main() {}");
+    assert_eq!(workspace.tabs.get(main).unwrap().name, "main.rs");
+    assert_eq!(workspace.tabs.get(main).unwrap().extension, "rs");
+
+    workspace.open("scratch", "", "txt");
+    assert_eq!(
+        workspace.open_file(Path::new("/project/src/main.rs"), ""),
+        main
+    );
+    assert_eq!(workspace.tabs.active_id(), Some(main));
+    assert_eq!(workspace.tabs.tabs.len(), 2);
+
+    // tabs follow their files when a directory is renamed
+    workspace.rename_path(Path::new("/project/src"), Path::new("/project/lib"));
+    workspace.rename_path(
+        Path::new("/project/lib/main.rs"),
+        Path::new("/project/lib/lib.rs"),
+    );
+    let tab = workspace.tabs.get(main).unwrap();
+    assert_eq!(tab.path.as_deref(), Some(Path::new("/project/lib/lib.rs")));
+    assert_eq!(tab.name, "lib.rs");
+}